@@ -0,0 +1,529 @@
+use chrono::{SecondsFormat, TimeZone, Utc};
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde_json::{Map, Value};
+use crate::*;
+
+// nested types deeper than this are treated as a broken abi
+const MAX_ABI_DEPTH: u32 = 32;
+
+impl AbiDef {
+    pub fn resolve_type<'a>(&'a self, ty: &'a str) -> &'a str {
+        let mut ty = ty;
+        for _ in 0..MAX_ABI_DEPTH {
+            match self.types.iter().find(|def| def.new_type_name.as_str() == ty) {
+                Some(def) => ty = def.ty.as_str(),
+                None => break
+            }
+        }
+        ty
+    }
+
+    pub fn get_action_type(&self, action_name: &str) -> Option<&str> {
+        self.actions.iter().find(|action| action.name.as_str() == action_name).map(|action| action.ty.as_str())
+    }
+
+    fn get_struct(&self, name: &str) -> Option<&StructDef> {
+        self.structs.iter().find(|st| st.name.as_str() == name)
+    }
+
+    fn get_variant(&self, name: &str) -> Option<&VariantDef> {
+        self.variants.as_ref().and_then(|variants| variants.iter().find(|variant| variant.name.as_str() == name))
+    }
+
+    pub fn decode_action_data(&self, action_name: &str, data: &[u8]) -> BuckyResult<Value> {
+        let ty = self.get_action_type(action_name).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "action {} not in abi", action_name)
+        })?;
+        let mut data = data.to_vec();
+        let mut buf = SerialBuffer::new(&mut data);
+        self.decode_type(ty, &mut buf)
+    }
+
+    pub fn decode_type(&self, ty: &str, buf: &mut SerialBuffer) -> BuckyResult<Value> {
+        self.decode_type_inner(ty, buf, 0)
+    }
+
+    fn decode_type_inner(&self, ty: &str, buf: &mut SerialBuffer, depth: u32) -> BuckyResult<Value> {
+        if depth > MAX_ABI_DEPTH {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "abi type {} nested too deep", ty));
+        }
+        let ty = self.resolve_type(ty);
+        if let Some(item_ty) = ty.strip_suffix("[]") {
+            let len = buf.get_var_u32()?;
+            let mut list = Vec::new();
+            for _ in 0..len {
+                list.push(self.decode_type_inner(item_ty, buf, depth + 1)?);
+            }
+            return Ok(Value::Array(list));
+        }
+        if let Some(item_ty) = ty.strip_suffix("?") {
+            return if buf.get_u8()? != 0 {
+                self.decode_type_inner(item_ty, buf, depth + 1)
+            } else {
+                Ok(Value::Null)
+            };
+        }
+        // binary extension, absent at the end of the data
+        if let Some(item_ty) = ty.strip_suffix("$") {
+            return if buf.have_read_data() {
+                self.decode_type_inner(item_ty, buf, depth + 1)
+            } else {
+                Ok(Value::Null)
+            };
+        }
+        if let Some(value) = decode_builtin(ty, buf)? {
+            return Ok(value);
+        }
+        if let Some(variant) = self.get_variant(ty) {
+            let index = buf.get_var_u32()?;
+            let item_ty = variant.types.get(index as usize).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidData, "variant {} has no type {}", ty, index)
+            })?;
+            let value = self.decode_type_inner(item_ty.as_str(), buf, depth + 1)?;
+            return Ok(Value::Array(vec![Value::String(item_ty.clone()), value]));
+        }
+        if let Some(st) = self.get_struct(ty) {
+            let mut obj = Map::new();
+            self.decode_struct(st, buf, &mut obj, depth)?;
+            return Ok(Value::Object(obj));
+        }
+        Err(cyfs_err!(BuckyErrorCode::NotSupport, "unknown abi type {}", ty))
+    }
+
+    fn decode_struct(&self, st: &StructDef, buf: &mut SerialBuffer, obj: &mut Map<String, Value>, depth: u32) -> BuckyResult<()> {
+        if depth > MAX_ABI_DEPTH {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "abi struct {} nested too deep", st.name.as_str()));
+        }
+        if !st.base.is_empty() {
+            let base = self.get_struct(self.resolve_type(st.base.as_str())).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::NotFound, "base {} of struct {} not in abi", st.base.as_str(), st.name.as_str())
+            })?;
+            self.decode_struct(base, buf, obj, depth + 1)?;
+        }
+        for field in st.fields.iter() {
+            let value = self.decode_type_inner(field.ty.as_str(), buf, depth + 1)?;
+            obj.insert(field.name.clone(), value);
+        }
+        Ok(())
+    }
+
+    pub fn encode_action_data(&self, action_name: &str, data: &Value) -> BuckyResult<Vec<u8>> {
+        let ty = self.get_action_type(action_name).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "action {} not in abi", action_name)
+        })?;
+        let mut raw = Vec::new();
+        let mut buf = SerialBuffer::new(&mut raw);
+        self.encode_type(ty, data, &mut buf)?;
+        Ok(raw)
+    }
+
+    pub fn encode_type(&self, ty: &str, value: &Value, buf: &mut SerialBuffer) -> BuckyResult<()> {
+        self.encode_type_inner(ty, value, buf, 0)
+    }
+
+    fn encode_type_inner(&self, ty: &str, value: &Value, buf: &mut SerialBuffer, depth: u32) -> BuckyResult<()> {
+        if depth > MAX_ABI_DEPTH {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "abi type {} nested too deep", ty));
+        }
+        let ty = self.resolve_type(ty);
+        if let Some(item_ty) = ty.strip_suffix("[]") {
+            let list = value.as_array().ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "expect array for {}, got {}", ty, value)
+            })?;
+            buf.push_var_u32(list.len() as u32);
+            for item in list.iter() {
+                self.encode_type_inner(item_ty, item, buf, depth + 1)?;
+            }
+            return Ok(());
+        }
+        if let Some(item_ty) = ty.strip_suffix("?") {
+            if value.is_null() {
+                buf.push_u8(0);
+                Ok(())
+            } else {
+                buf.push_u8(1);
+                self.encode_type_inner(item_ty, value, buf, depth + 1)
+            }
+        } else if let Some(item_ty) = ty.strip_suffix("$") {
+            if value.is_null() {
+                Ok(())
+            } else {
+                self.encode_type_inner(item_ty, value, buf, depth + 1)
+            }
+        } else if encode_builtin(ty, value, buf)? {
+            Ok(())
+        } else if let Some(variant) = self.get_variant(ty) {
+            // variants are ["type", value] like nodeos prints them
+            let pair = value.as_array().filter(|pair| pair.len() == 2).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "expect [type, value] for variant {}, got {}", ty, value)
+            })?;
+            let item_ty = pair[0].as_str().unwrap_or("");
+            let index = variant.types.iter().position(|vty| vty.as_str() == item_ty).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "variant {} has no type {}", ty, pair[0])
+            })?;
+            buf.push_var_u32(index as u32);
+            self.encode_type_inner(item_ty, &pair[1], buf, depth + 1)
+        } else if let Some(st) = self.get_struct(ty) {
+            let obj = value.as_object().ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "expect object for struct {}, got {}", ty, value)
+            })?;
+            self.encode_struct(st, obj, buf, depth)
+        } else {
+            Err(cyfs_err!(BuckyErrorCode::NotSupport, "unknown abi type {}", ty))
+        }
+    }
+
+    fn encode_struct(&self, st: &StructDef, obj: &Map<String, Value>, buf: &mut SerialBuffer, depth: u32) -> BuckyResult<()> {
+        if depth > MAX_ABI_DEPTH {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "abi struct {} nested too deep", st.name.as_str()));
+        }
+        if !st.base.is_empty() {
+            let base = self.get_struct(self.resolve_type(st.base.as_str())).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::NotFound, "base {} of struct {} not in abi", st.base.as_str(), st.name.as_str())
+            })?;
+            self.encode_struct(base, obj, buf, depth + 1)?;
+        }
+        for field in st.fields.iter() {
+            match obj.get(field.name.as_str()) {
+                Some(value) => self.encode_type_inner(field.ty.as_str(), value, buf, depth + 1)?,
+                // binary extensions may be left out, the fields after them too
+                None if field.ty.ends_with("$") => break,
+                None if field.ty.ends_with("?") => buf.push_u8(0),
+                None => {
+                    return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "field {} of {} missing", field.name.as_str(), st.name.as_str()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn get_le_bytes<const N: usize>(buf: &mut SerialBuffer) -> BuckyResult<[u8; N]> {
+    let mut data = [0u8; N];
+    data.copy_from_slice(buf.get_array(N)?);
+    Ok(data)
+}
+
+// 64 bit and wider integers are strings like nodeos returns them, json numbers lose precision
+fn decode_builtin(ty: &str, buf: &mut SerialBuffer) -> BuckyResult<Option<Value>> {
+    let value = match ty {
+        "bool" => Value::Bool(buf.get_u8()? != 0),
+        "int8" => Value::from(buf.get_u8()? as i8),
+        "uint8" => Value::from(buf.get_u8()?),
+        "int16" => Value::from(buf.get_u16()? as i16),
+        "uint16" => Value::from(buf.get_u16()?),
+        "int32" => Value::from(buf.get_u32()? as i32),
+        "uint32" => Value::from(buf.get_u32()?),
+        "int64" => Value::String((buf.get_u64()? as i64).to_string()),
+        "uint64" => Value::String(buf.get_u64()?.to_string()),
+        "int128" => Value::String(i128::from_le_bytes(get_le_bytes(buf)?).to_string()),
+        "uint128" => Value::String(u128::from_le_bytes(get_le_bytes(buf)?).to_string()),
+        "varint32" => Value::from(buf.get_var_int32()?),
+        "varuint32" => Value::from(buf.get_var_u32()?),
+        "float32" => Value::from(buf.get_f32()?),
+        "float64" => Value::from(buf.get_f64()?),
+        "time_point" => Value::String(decode_time_point(buf.get_u64()?)?),
+        "time_point_sec" => Value::String(time_point_sec_to_date(buf.get_u32()? as i64)),
+        "block_timestamp_type" => Value::String(block_timestamp_to_date(buf.get_u32()? as i64)),
+        "name" => Value::String(buf.get_name()?),
+        "bytes" => Value::String(hex::encode(buf.get_bytes()?)),
+        "string" => Value::String(buf.get_string()?),
+        "checksum160" => Value::String(hex::encode(buf.get_array(20)?)),
+        "checksum256" => Value::String(hex::encode(buf.get_array(32)?)),
+        "checksum512" => Value::String(hex::encode(buf.get_array(64)?)),
+        "public_key" => Value::String(buf.get_public_key()?),
+        "signature" => Value::String(buf.get_signature()?),
+        "symbol" => {
+            let (name, precision) = buf.get_symbol()?;
+            Value::String(format!("{},{}", precision, name))
+        }
+        "symbol_code" => Value::String(buf.get_symbol_code()?),
+        "asset" => Value::String(buf.get_asset()?),
+        "extended_asset" => {
+            let mut obj = Map::new();
+            obj.insert("quantity".to_string(), Value::String(buf.get_asset()?));
+            obj.insert("contract".to_string(), Value::String(buf.get_name()?));
+            Value::Object(obj)
+        }
+        _ => return Ok(None)
+    };
+    Ok(Some(value))
+}
+
+// the time_point of any data mustn't panic the decoder, those out of the dates chrono knows are refused
+fn decode_time_point(us: u64) -> BuckyResult<String> {
+    let time = i64::try_from(us / 1000).ok().and_then(|ms| Utc.timestamp_millis_opt(ms).single()).ok_or_else(|| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "time_point {} out of range", us)
+    })?;
+    Ok(time.to_rfc3339_opts(SecondsFormat::Secs, true).trim_end_matches("Z").to_string())
+}
+
+// a date before the epoch of the type or past its range would wrap to another one
+fn date_in_range<T>(ty: &str, value: &Value, v: Option<T>) -> BuckyResult<T> {
+    v.ok_or_else(|| cyfs_err!(BuckyErrorCode::InvalidParam, "{} out of range of {}", value, ty))
+}
+
+fn value_to_str<'a>(ty: &str, value: &'a Value) -> BuckyResult<&'a str> {
+    value.as_str().ok_or_else(|| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "expect string for {}, got {}", ty, value)
+    })
+}
+
+// integers are accepted as json numbers or strings
+fn value_to_i128(ty: &str, value: &Value) -> BuckyResult<i128> {
+    let num = match value {
+        Value::Number(num) => num.as_i64().map(|v| v as i128).or(num.as_u64().map(|v| v as i128)),
+        Value::String(num) => num.parse::<i128>().ok(),
+        _ => None
+    };
+    num.ok_or_else(|| cyfs_err!(BuckyErrorCode::InvalidParam, "expect integer for {}, got {}", ty, value))
+}
+
+fn value_to_int<T: TryFrom<i128>>(ty: &str, value: &Value) -> BuckyResult<T> {
+    T::try_from(value_to_i128(ty, value)?).map_err(|_| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "{} out of range of {}", value, ty)
+    })
+}
+
+fn value_to_f64(ty: &str, value: &Value) -> BuckyResult<f64> {
+    let num = match value {
+        Value::Number(num) => num.as_f64(),
+        Value::String(num) => num.parse::<f64>().ok(),
+        _ => None
+    };
+    num.ok_or_else(|| cyfs_err!(BuckyErrorCode::InvalidParam, "expect float for {}, got {}", ty, value))
+}
+
+fn value_to_hex(ty: &str, value: &Value, len: Option<usize>) -> BuckyResult<Vec<u8>> {
+    let data = hex::decode(value_to_str(ty, value)?).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "decode {} of {} failed {}", value, ty, e)
+    })?;
+    if let Some(len) = len {
+        if len != data.len() {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "{} expect {} bytes, got {}", ty, len, data.len()));
+        }
+    }
+    Ok(data)
+}
+
+// false if ty isn't a builtin type
+fn encode_builtin(ty: &str, value: &Value, buf: &mut SerialBuffer) -> BuckyResult<bool> {
+    match ty {
+        "bool" => {
+            let v = value.as_bool().ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "expect bool, got {}", value)
+            })?;
+            buf.push_u8(if v { 1 } else { 0 });
+        }
+        "int8" => buf.push_u8(value_to_int::<i8>(ty, value)? as u8),
+        "uint8" => buf.push_u8(value_to_int::<u8>(ty, value)?),
+        "int16" => buf.push_u16(value_to_int::<i16>(ty, value)? as u16),
+        "uint16" => buf.push_u16(value_to_int::<u16>(ty, value)?),
+        "int32" => buf.push_u32(value_to_int::<i32>(ty, value)? as u32),
+        "uint32" => buf.push_u32(value_to_int::<u32>(ty, value)?),
+        "int64" => buf.push_u64(value_to_int::<i64>(ty, value)? as u64),
+        "uint64" => buf.push_u64(value_to_int::<u64>(ty, value)?),
+        "int128" => buf.push_array(&value_to_i128(ty, value)?.to_le_bytes()),
+        "uint128" => {
+            let v = match value {
+                Value::String(num) => num.parse::<u128>().ok(),
+                _ => value_to_i128(ty, value).ok().and_then(|num| u128::try_from(num).ok())
+            };
+            let v = v.ok_or_else(|| cyfs_err!(BuckyErrorCode::InvalidParam, "expect uint128, got {}", value))?;
+            buf.push_array(&v.to_le_bytes());
+        }
+        "varint32" => buf.push_var_int32(value_to_int::<i32>(ty, value)?),
+        "varuint32" => buf.push_var_u32(value_to_int::<u32>(ty, value)?),
+        "float32" => buf.push_f32(value_to_f64(ty, value)? as f32),
+        "float64" => buf.push_f64(value_to_f64(ty, value)?),
+        "time_point" => {
+            let us = u64::try_from(date_to_time_point(value_to_str(ty, value)?)?).ok().and_then(|sec| sec.checked_mul(1000000));
+            buf.push_u64(date_in_range(ty, value, us)?);
+        }
+        "time_point_sec" => {
+            let sec = u32::try_from(date_to_time_point(value_to_str(ty, value)?)?).ok();
+            buf.push_u32(date_in_range(ty, value, sec)?);
+        }
+        "block_timestamp_type" => {
+            let slot = u32::try_from((date_to_time_point(value_to_str(ty, value)?)? * 1000 - 946684800000) / 500).ok();
+            buf.push_u32(date_in_range(ty, value, slot)?);
+        }
+        "name" => buf.push_name(value_to_str(ty, value)?)?,
+        "bytes" => buf.push_bytes(value_to_hex(ty, value, None)?.as_slice()),
+        "string" => buf.push_string(value_to_str(ty, value)?),
+        "checksum160" => buf.push_array(value_to_hex(ty, value, Some(20))?.as_slice()),
+        "checksum256" => buf.push_array(value_to_hex(ty, value, Some(32))?.as_slice()),
+        "checksum512" => buf.push_array(value_to_hex(ty, value, Some(64))?.as_slice()),
+        "public_key" => buf.push_public_key(value_to_str(ty, value)?)?,
+        "signature" => buf.push_signature(value_to_str(ty, value)?)?,
+        "symbol" => {
+            let symbol = value_to_str(ty, value)?;
+            let (precision, name) = symbol.split_once(',').ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "expect precision,code for symbol, got {}", symbol)
+            })?;
+            let precision = precision.parse::<u8>().map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "parse precision of {} err {}", symbol, e)
+            })?;
+            buf.push_symbol(name, precision)?;
+        }
+        "symbol_code" => buf.push_symbol_code(value_to_str(ty, value)?),
+        "asset" => buf.push_assert(value_to_str(ty, value)?)?,
+        "extended_asset" => {
+            let quantity = value.get("quantity").ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "quantity of extended_asset missing")
+            })?;
+            let contract = value.get("contract").ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "contract of extended_asset missing")
+            })?;
+            buf.push_assert(value_to_str(ty, quantity)?)?;
+            buf.push_name(value_to_str(ty, contract)?)?;
+        }
+        _ => return Ok(false)
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test_abi_serializer {
+    use serde_json::{json, Value};
+    use crate::*;
+
+    fn abi(value: Value) -> AbiDef {
+        let mut abi = json!({
+            "version": "eosio::abi/1.2",
+            "types": [],
+            "structs": [],
+            "actions": [],
+            "tables": [],
+            "ricardian_clauses": [],
+            "error_messages": [],
+            "abi_extensions": []
+        });
+        for (key, item) in value.as_object().unwrap().iter() {
+            abi[key] = item.clone();
+        }
+        serde_json::from_value(abi).unwrap()
+    }
+
+    fn encode(abi: &AbiDef, ty: &str, value: &Value) -> BuckyResult<Vec<u8>> {
+        let mut raw = Vec::new();
+        abi.encode_type(ty, value, &mut SerialBuffer::new(&mut raw))?;
+        Ok(raw)
+    }
+
+    fn decode(abi: &AbiDef, ty: &str, raw: &[u8]) -> BuckyResult<Value> {
+        let mut raw = raw.to_vec();
+        abi.decode_type(ty, &mut SerialBuffer::new(&mut raw))
+    }
+
+    #[test]
+    fn test_builtin_round_trip() {
+        let abi = abi(json!({}));
+        let private_key = DMCPrivateKey::gen_key();
+        let cases = vec![
+            ("bool", json!(true)),
+            ("int8", json!(-5)),
+            ("uint8", json!(200)),
+            ("int16", json!(-300)),
+            ("uint16", json!(60000)),
+            ("int32", json!(-70000)),
+            ("uint32", json!(4000000000u32)),
+            ("int64", json!("-9000000000")),
+            ("uint64", json!("18446744073709551615")),
+            ("int128", json!("-170141183460469231731687303715884105728")),
+            ("uint128", json!("340282366920938463463374607431768211455")),
+            ("varint32", json!(-12345)),
+            ("varuint32", json!(300)),
+            ("float32", json!(1.5)),
+            ("float64", json!(-2.25)),
+            ("time_point", json!("2023-01-02T03:04:05")),
+            ("time_point_sec", json!("2023-01-02T03:04:05")),
+            ("block_timestamp_type", json!("2023-01-02T03:04:05")),
+            ("name", json!("dmc.token")),
+            ("bytes", json!("00ff10")),
+            ("string", json!("hello")),
+            ("checksum160", json!("11".repeat(20))),
+            ("checksum256", json!("22".repeat(32))),
+            ("checksum512", json!("33".repeat(64))),
+            ("public_key", json!(private_key.get_public_key().to_string())),
+            ("signature", json!(private_key.sign(b"data", true).unwrap().to_string())),
+            ("symbol", json!("4,DMC")),
+            ("symbol_code", json!("DMC")),
+            ("asset", json!("1.0000 DMC")),
+            ("extended_asset", json!({"quantity": "1.0000 DMC", "contract": "dmc.token"})),
+        ];
+        for (ty, value) in cases.iter() {
+            let raw = encode(&abi, ty, value).unwrap();
+            assert_eq!(&decode(&abi, ty, raw.as_slice()).unwrap(), value, "{}", ty);
+        }
+
+        // integers may come as json numbers or strings
+        assert_eq!(encode(&abi, "uint64", &json!(7)).unwrap(), encode(&abi, "uint64", &json!("7")).unwrap());
+        assert!(encode(&abi, "uint8", &json!(256)).is_err());
+        assert!(encode(&abi, "checksum256", &json!("22")).is_err());
+    }
+
+    #[test]
+    fn test_date_range() {
+        let abi = abi(json!({}));
+        // dates the type can't hold are refused instead of wrapped
+        assert!(encode(&abi, "time_point", &json!("1969-12-31T23:59:59")).is_err());
+        assert!(encode(&abi, "time_point_sec", &json!("1969-12-31T23:59:59")).is_err());
+        assert!(encode(&abi, "time_point_sec", &json!("2106-02-07T06:28:16")).is_err());
+        assert!(encode(&abi, "block_timestamp_type", &json!("1999-12-31T23:59:59")).is_err());
+        // a time_point past the dates chrono knows is an error, not a panic
+        assert!(decode(&abi, "time_point", &u64::MAX.to_le_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_struct_round_trip() {
+        let abi = abi(json!({
+            "types": [{"new_type_name": "account_name", "type": "name"}],
+            "structs": [
+                {"name": "base", "base": "", "fields": [{"name": "from", "type": "account_name"}]},
+                {"name": "entry", "base": "", "fields": [{"name": "id", "type": "uint64"}, {"name": "tags", "type": "string[]"}]},
+                {"name": "transfer", "base": "base", "fields": [
+                    {"name": "amounts", "type": "uint16[]"},
+                    {"name": "entries", "type": "entry[]"},
+                    {"name": "memo", "type": "string?"},
+                    {"name": "payload", "type": "payload"},
+                    {"name": "ext", "type": "uint32$"}
+                ]}
+            ],
+            "variants": [{"name": "payload", "types": ["uint8", "string"]}],
+            "actions": [{"name": "transfer", "type": "transfer", "ricardian_contract": ""}]
+        }));
+
+        let value = json!({
+            "from": "eosio",
+            "amounts": [1, 2],
+            "entries": [{"id": "1", "tags": ["a", "b"]}, {"id": "2", "tags": []}],
+            "memo": "hi",
+            "payload": ["string", "data"],
+            "ext": 9
+        });
+        let raw = abi.encode_action_data("transfer", &value).unwrap();
+        // the base fields first, "eosio" is 6138663577826885632
+        assert_eq!(&raw[..8], &6138663577826885632u64.to_le_bytes());
+        assert_eq!(&raw[8..13], &[2, 1, 0, 2, 0]);
+        assert_eq!(abi.decode_action_data("transfer", raw.as_slice()).unwrap(), value);
+
+        // the optional and the binary extension left out
+        let value = json!({
+            "from": "eosio",
+            "amounts": [],
+            "entries": [],
+            "payload": ["uint8", 3]
+        });
+        let raw = abi.encode_action_data("transfer", &value).unwrap();
+        let mut expect = value.clone();
+        expect["memo"] = Value::Null;
+        expect["ext"] = Value::Null;
+        assert_eq!(abi.decode_action_data("transfer", raw.as_slice()).unwrap(), expect);
+
+        assert!(abi.encode_action_data("transfer", &json!({"from": "eosio"})).is_err());
+        assert!(abi.encode_action_data("transfer", &json!({"from": "eosio", "amounts": [], "entries": [], "payload": ["uint64", "1"]})).is_err());
+        assert!(abi.decode_action_data("transfer", &raw[..raw.len() - 1]).is_err());
+        assert!(abi.decode_action_data("unknown", raw.as_slice()).is_err());
+    }
+}
@@ -30,6 +30,15 @@ pub enum TransResult {
     PushTransactionArgs(PushTransactionArgs),
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DecodedAction {
+    pub receiver: String,
+    pub account: Name,
+    pub name: Name,
+    pub authorization: Vec<PermissionLevel>,
+    pub data: serde_json::Value,
+}
+
 pub struct BlockTaposInfo {
     pub block_num: i64,
     pub id: String,
@@ -117,6 +126,50 @@ impl<T: SignatureProvider> DMCApi<T> {
         Ok(list)
     }
 
+    pub async fn encode_action_data(&self, account_name: &str, action_name: &str, data: &serde_json::Value) -> BuckyResult<Vec<u8>> {
+        let abi = self.get_cached_abi(account_name, false).await?;
+        abi.abi.encode_action_data(action_name, data)
+    }
+
+    pub async fn decode_action_data(&self, account_name: &str, action_name: &str, data: &[u8]) -> BuckyResult<serde_json::Value> {
+        let abi = self.get_cached_abi(account_name, false).await?;
+        abi.abi.decode_action_data(action_name, data)
+    }
+
+    // action traces in execution order with the data decoded, inline actions follow their parent
+    pub async fn decode_action_traces(&self, trace: &TransactionTrace) -> BuckyResult<Vec<DecodedAction>> {
+        let mut traces: Vec<&ActionTrace> = Vec::new();
+        let mut stack: Vec<&ActionTrace> = trace.action_traces.iter().rev().collect();
+        while let Some(action_trace) = stack.pop() {
+            traces.push(action_trace);
+            if let Some(inline_traces) = action_trace.inline_traces.as_ref() {
+                stack.extend(inline_traces.iter().rev());
+            }
+        }
+
+        let mut list = Vec::new();
+        for action_trace in traces.into_iter() {
+            let act = &action_trace.act;
+            let data = match act.hex_data.as_ref() {
+                Some(hex_data) => {
+                    let raw = hex::decode(hex_data.as_str()).map_err(|e| {
+                        cyfs_err!(BuckyErrorCode::InvalidData, "decode hex data of {}::{} failed {}", act.account.as_str(), act.name.as_str(), e)
+                    })?;
+                    self.decode_action_data(act.account.as_str(), act.name.as_str(), raw.as_slice()).await?
+                }
+                None => serde_json::Value::Null
+            };
+            list.push(DecodedAction {
+                receiver: action_trace.receiver.clone(),
+                account: act.account.clone(),
+                name: act.name.clone(),
+                authorization: act.authorization.clone(),
+                data
+            });
+        }
+        Ok(list)
+    }
+
     fn deflate_serialized_array(&self, data: &[u8]) -> BuckyResult<Vec<u8>> {
         let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Compression::best());
         encoder.write_all(data).map_err(|e| {
@@ -331,13 +384,14 @@ impl<T: SignatureProvider> DMCApi<T> {
             }
 
             let required_keys = config.required_keys.unwrap();
+            let chain_id = self.chain_id.lock().unwrap().clone();
             self.sign_provider.sign(SignatureProviderArgs {
-                chain_id: self.chain_id.lock().unwrap().clone(),
+                chain_id,
                 required_keys,
                 serialized_transaction,
                 serialized_context_free_data,
                 abis
-            })?
+            }).await?
         } else {
             PushTransactionArgs {
                     signatures: vec![],
@@ -406,6 +460,18 @@ impl TransactionBuilder {
         Ok(self)
     }
 
+    // data already serialized, e.g. by AbiDef::encode_action_data
+    pub fn add_raw_action(mut self, contract_name: &str, action_name: &str, authorization: Vec<Authorization>, data: Vec<u8>) -> Self {
+        self.actions.push(Action {
+            account: contract_name.to_string(),
+            name: action_name.to_string(),
+            authorization,
+            data,
+            hex_data: None
+        });
+        self
+    }
+
     pub fn add_context_free_action<T: DMCSerialize>(mut self, contract_name: &str, action_name: &str, authorization: Vec<Authorization>, data: T) -> BuckyResult<Self> {
         let mut raw_buf = Vec::new();
         let mut serial_buf = SerialBuffer::new(&mut raw_buf);
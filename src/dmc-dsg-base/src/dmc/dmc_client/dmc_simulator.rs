@@ -0,0 +1,1090 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use cyfs_base::{BuckyErrorCode, BuckyResult, hash_data, HashValue};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use sha2::Digest;
+use tide::{Body, Request, Response, StatusCode};
+use crate::*;
+// the actions are decoded with the private types of dmc_client
+use super::{Increase, Redemption, Mint, Bill, UnBill, AddMerkle, ChallengeReq, ChallengeResp, Arbitration, CyfsBind};
+
+// empty blocks before the first transaction, transact refers to a block 3 behind head
+const SIM_GENESIS_BLOCKS: i64 = 10;
+const SIM_BLOCK_INTERVAL: i64 = 1;
+// default time the miner has to answer a challenge, set_challenge_timeout changes it
+const SIM_CHALLENGE_TIMEOUT: i64 = 24 * 3600;
+// get_table_rows gives 10 rows when no limit is set, like nodeos
+const SIM_DEFAULT_ROWS: usize = 10;
+
+fn sim_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut sha256 = sha2::Sha256::new();
+    sha256.update(data);
+    hex::encode(sha256.finalize())
+}
+
+// table keys and scopes are names or numbers, an all digit name is taken as a number
+fn sim_key(name: &str) -> BuckyResult<u64> {
+    if let Ok(key) = name.parse::<u64>() {
+        return Ok(key);
+    }
+    let mut raw = Vec::new();
+    SerialBuffer::new(&mut raw).push_name(name)?;
+    Ok(u64::from_le_bytes(raw.as_slice().try_into().unwrap()))
+}
+
+fn sim_decode<T: DMCDeserialize>(action: &Action) -> BuckyResult<T> {
+    let mut raw = action.data.clone();
+    let mut buf = SerialBuffer::new(&mut raw);
+    T::dmc_deserialize(&mut buf)
+}
+
+fn asset_amount(quantity: &str) -> BuckyResult<f64> {
+    let amount = quantity.split(" ").next().unwrap_or("");
+    amount.parse().map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "parse asset {} err {}", quantity, e)
+    })
+}
+
+fn dmc_pledge(amount: f64) -> Pledge {
+    Pledge {
+        quantity: format!("{:.4} DMC", amount),
+        contract: "datamall".to_string()
+    }
+}
+
+fn pst_pledge(amount: u64) -> Pledge {
+    Pledge {
+        quantity: format!("{} PST", amount),
+        contract: "datamall".to_string()
+    }
+}
+
+fn param_str(param: &Value, name: &str) -> Option<String> {
+    match param.get(name) {
+        Some(Value::String(v)) => Some(v.clone()),
+        Some(Value::Number(v)) => Some(v.to_string()),
+        _ => None
+    }
+}
+
+fn require_param(param: &Value, name: &str) -> BuckyResult<String> {
+    param_str(param, name).ok_or_else(|| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "missing param {}", name)
+    })
+}
+
+// walk from the leaf to the root the same way SinglePieceProof::verify does
+fn verify_piece(data: &[u8], index: u64, cut_merkle: &[HashValue], merkle_root: &str) -> bool {
+    let mut cur_node: [u8; 32] = {
+        let mut sha256 = sha2::Sha256::new();
+        sha256.update(data);
+        sha256.finalize().into()
+    };
+    let mut cur_index = index;
+    for node in cut_merkle.iter() {
+        let mut sha256 = sha2::Sha256::new();
+        if cur_index % 2 != 0 {
+            sha256.update(node.as_slice());
+            sha256.update(&cur_node);
+        } else {
+            sha256.update(&cur_node);
+            sha256.update(node.as_slice());
+        }
+        cur_node = sha256.finalize().into();
+        cur_index = cur_index / 2;
+    }
+    match HashValue::try_from(&cur_node[..]) {
+        Ok(root) => root.to_string() == merkle_root,
+        Err(_) => false
+    }
+}
+
+struct SimBlock {
+    block_num: i64,
+    id: String,
+    previous: String,
+    time: i64,
+    // id and receipt status
+    trxs: Vec<(String, String)>,
+}
+
+impl SimBlock {
+    fn ref_block_prefix(&self) -> i64 {
+        u32::from_str_radix(reverse_hex(&self.id[16..24]).as_str(), 16).unwrap() as i64
+    }
+}
+
+struct SimChain {
+    chain_id: String,
+    now: i64,
+    blocks: Vec<SimBlock>,
+    tables: HashMap<(u64, u64, u64), BTreeMap<u64, Value>>,
+    next_order_id: u64,
+    next_bill_id: u64,
+    challenge_timeout: i64,
+    // action name to the error its next push fails with
+    failures: HashMap<String, String>,
+    // action names whose next transaction is accepted but never packed
+    drops: HashSet<String>,
+    // action names whose next transaction is packed with a hard_fail receipt
+    hard_fails: HashSet<String>,
+    // blocks between head and the last irreversible block
+    irreversible_lag: i64,
+}
+
+impl SimChain {
+    fn new() -> Self {
+        let mut chain = Self {
+            chain_id: sha256_hex(b"dmc simulator"),
+            now: sim_now(),
+            blocks: Vec::new(),
+            tables: HashMap::new(),
+            next_order_id: 1,
+            next_bill_id: 1,
+            challenge_timeout: SIM_CHALLENGE_TIMEOUT,
+            failures: HashMap::new(),
+            drops: HashSet::new(),
+            hard_fails: HashSet::new(),
+            irreversible_lag: 0
+        };
+        for _ in 0..SIM_GENESIS_BLOCKS {
+            chain.produce_block(vec![]);
+        }
+        chain
+    }
+
+    fn head(&self) -> &SimBlock {
+        self.blocks.last().unwrap()
+    }
+
+    fn produce_block(&mut self, trxs: Vec<(String, String)>) {
+        self.now += SIM_BLOCK_INTERVAL;
+        let block_num = self.blocks.len() as i64 + 1;
+        let previous = self.blocks.last().map(|block| block.id.clone()).unwrap_or("0".repeat(64));
+        // block ids start with the block number like on chain
+        let hash = sha256_hex(format!("{}{}{}", previous, block_num, self.now).as_bytes());
+        let id = format!("{:08x}{}", block_num, &hash[8..]);
+        self.blocks.push(SimBlock {
+            block_num,
+            id,
+            previous,
+            time: self.now,
+            trxs
+        });
+        self.check_timeout();
+    }
+
+    fn get_block(&self, block_num_or_id: &str) -> BuckyResult<&SimBlock> {
+        let block = match block_num_or_id.parse::<i64>() {
+            Ok(block_num) if block_num > 0 => self.blocks.get(block_num as usize - 1),
+            Ok(_) => None,
+            Err(_) => self.blocks.iter().find(|block| block.id.as_str() == block_num_or_id)
+        };
+        block.ok_or_else(|| cyfs_err!(BuckyErrorCode::NotFound, "can't find block {}", block_num_or_id))
+    }
+
+    fn table(&mut self, code: &str, table: &str, scope: &str) -> BuckyResult<&mut BTreeMap<u64, Value>> {
+        let key = (sim_key(code)?, sim_key(table)?, sim_key(scope)?);
+        Ok(self.tables.entry(key).or_insert_with(BTreeMap::new))
+    }
+
+    fn get_row<T: DeserializeOwned>(&mut self, code: &str, table: &str, scope: &str, key: &str) -> BuckyResult<Option<T>> {
+        let key = sim_key(key)?;
+        match self.table(code, table, scope)?.get(&key) {
+            Some(row) => serde_json::from_value(row.clone()).map(|row| Some(row)).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidData, "parse row {} of {} err {}", key, table, e)
+            }),
+            None => Ok(None)
+        }
+    }
+
+    fn set_row<T: Serialize>(&mut self, code: &str, table: &str, scope: &str, key: &str, row: &T) -> BuckyResult<()> {
+        let key = sim_key(key)?;
+        let row = serde_json::to_value(row).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode row {} of {} err {}", key, table, e)
+        })?;
+        self.table(code, table, scope)?.insert(key, row);
+        Ok(())
+    }
+
+    fn remove_row(&mut self, code: &str, table: &str, scope: &str, key: &str) -> BuckyResult<()> {
+        let key = sim_key(key)?;
+        self.table(code, table, scope)?.remove(&key);
+        Ok(())
+    }
+
+    fn get_order(&mut self, order_id: u64) -> BuckyResult<DMCOrder> {
+        self.get_row("dmc.token", "dmcorder", "dmc.token", order_id.to_string().as_str())?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find order {}", order_id)
+        })
+    }
+
+    fn set_order(&mut self, order: &DMCOrder) -> BuckyResult<()> {
+        self.set_row("dmc.token", "dmcorder", "dmc.token", order.order_id.to_string().as_str(), order)
+    }
+
+    fn get_challenge(&mut self, order_id: u64) -> BuckyResult<DMCChallengeInfo> {
+        self.get_row("dmc.token", "dmchallenge", "dmc.token", order_id.to_string().as_str())?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find challenge of order {}", order_id)
+        })
+    }
+
+    fn set_challenge(&mut self, challenge: &DMCChallengeInfo) -> BuckyResult<()> {
+        self.set_row("dmc.token", "dmchallenge", "dmc.token", challenge.order_id.to_string().as_str(), challenge)
+    }
+
+    fn get_pst(&mut self, owner: &str) -> BuckyResult<u64> {
+        match self.get_row::<PstStat>("dmc.token", "pststats", "dmc.token", owner)? {
+            Some(stat) => stat.amount.get_amount(),
+            None => Ok(0)
+        }
+    }
+
+    fn set_pst(&mut self, owner: &str, amount: u64) -> BuckyResult<()> {
+        self.set_row("dmc.token", "pststats", "dmc.token", owner, &PstStat {
+            owner: owner.to_string(),
+            amount: pst_pledge(amount)
+        })
+    }
+
+    // the miner lost the challenge, its lock pledge goes to the user and the order ends
+    fn penalize(&mut self, order_id: u64) -> BuckyResult<()> {
+        let mut order = self.get_order(order_id)?;
+        order.lock_pledge = dmc_pledge(0f64);
+        order.state = DMCOrderState::OrderStateEnd as u8;
+        self.set_order(&order)
+    }
+
+    fn check_timeout(&mut self) {
+        let timeouts: Vec<u64> = match self.tables.get(&(sim_key("dmc.token").unwrap(), sim_key("dmchallenge").unwrap(), sim_key("dmc.token").unwrap())) {
+            Some(rows) => rows.values().filter_map(|row| serde_json::from_value::<DMCChallengeInfo>(row.clone()).ok()).filter(|challenge| {
+                challenge.state == DMCChallengeState::ChallengeRequest as u32
+                    && date_to_time_point(challenge.challenge_date.as_str()).map(|date| date + self.challenge_timeout <= self.now).unwrap_or(false)
+            }).map(|challenge| challenge.order_id).collect(),
+            None => return
+        };
+        for order_id in timeouts {
+            log::info!("challenge of order {} timeout", order_id);
+            let ret = self.get_challenge(order_id).and_then(|mut challenge| {
+                challenge.state = DMCChallengeState::ChallengeTimeout as u32;
+                self.set_challenge(&challenge)
+            }).and_then(|_| self.penalize(order_id));
+            if let Err(e) = ret {
+                log::error!("timeout challenge of order {} err {}", order_id, e);
+            }
+        }
+    }
+
+    fn require_auth(action: &Action, account: &str) -> BuckyResult<()> {
+        if action.authorization.iter().any(|auth| auth.actor.as_str() == account) {
+            Ok(())
+        } else {
+            Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "missing authority of {} for {}", account, action.name.as_str()))
+        }
+    }
+
+    fn apply_transaction(&mut self, trans: &Transaction) -> BuckyResult<()> {
+        // the whole transaction is reverted when one of its actions fails
+        let tables = self.tables.clone();
+        let next_bill_id = self.next_bill_id;
+        for action in trans.actions.iter() {
+            if let Err(e) = self.apply_action(action) {
+                self.tables = tables;
+                self.next_bill_id = next_bill_id;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_action(&mut self, action: &Action) -> BuckyResult<()> {
+        if let Some(reason) = self.failures.remove(action.name.as_str()) {
+            return Err(cyfs_err!(BuckyErrorCode::Failed, "{}", reason));
+        }
+        match (action.account.as_str(), action.name.as_str()) {
+            ("dmc.token", "increase") => self.on_increase(action),
+            ("dmc.token", "redemption") => self.on_redemption(action),
+            ("dmc.token", "mint") => self.on_mint(action),
+            ("dmc.token", "bill") => self.on_bill(action),
+            ("dmc.token", "unbill") => self.on_unbill(action),
+            ("dmc.token", "addmerkle") => self.on_add_merkle(action),
+            ("dmc.token", "reqchallenge") => self.on_challenge(action),
+            ("dmc.token", "anschallenge") => self.on_challenge_resp(action),
+            ("dmc.token", "arbitration") => self.on_arbitration(action),
+            ("cyfsaddrinfo", "bind") => self.on_bind(action),
+            // permissions aren't simulated, every action is authorized by its actor
+            ("dmc", "updateauth") | ("dmc", "deleteauth") | ("dmc", "linkauth") | ("dmc", "unlinkauth") => Ok(()),
+            (account, name) => Err(cyfs_err!(BuckyErrorCode::NotSupport, "action {}::{} isn't simulated", account, name))
+        }
+    }
+
+    fn get_maker(&mut self, miner: &str) -> BuckyResult<StakeInfo> {
+        Ok(self.get_row("dmc.token", "dmcmaker", "dmc.token", miner)?.unwrap_or(StakeInfo {
+            miner: miner.to_string(),
+            current_rate: "0".to_string(),
+            miner_rate: "1".to_string(),
+            total_weight: "0".to_string(),
+            total_staked: dmc_pledge(0f64)
+        }))
+    }
+
+    fn on_increase(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: Increase = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        let amount = asset_amount(params.asset.quantity.as_str())?;
+        let mut maker = self.get_maker(params.miner.as_str())?;
+        let staked = maker.get_staked()?;
+        let total_weight = maker.get_total_weight()?;
+        let weight = if staked <= 0f64 || total_weight <= 0f64 {
+            amount
+        } else {
+            amount * total_weight / staked
+        };
+        maker.total_staked = dmc_pledge(staked + amount);
+        maker.total_weight = (total_weight + weight).to_string();
+        self.set_row("dmc.token", "dmcmaker", "dmc.token", params.miner.as_str(), &maker)?;
+
+        let scope = sim_key(params.miner.as_str())?.to_string();
+        let pool_weight = match self.get_row::<MakerPoolRecord>("dmc.token", "makerpool", scope.as_str(), params.owner.as_str())? {
+            Some(pool) => pool.get_weight()?,
+            None => 0f64
+        };
+        self.set_row("dmc.token", "makerpool", scope.as_str(), params.owner.as_str(), &MakerPoolRecord {
+            owner: params.owner.clone(),
+            weight: (pool_weight + weight).to_string()
+        })
+    }
+
+    fn on_redemption(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: Redemption = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        if !(params.rate > 0f64 && params.rate <= 1f64) {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "redemption rate {} out of (0, 1]", params.rate));
+        }
+        let scope = sim_key(params.miner.as_str())?.to_string();
+        let pool: MakerPoolRecord = self.get_row("dmc.token", "makerpool", scope.as_str(), params.owner.as_str())?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "{} has no stake in {}", params.owner.as_str(), params.miner.as_str())
+        })?;
+        let mut maker = self.get_maker(params.miner.as_str())?;
+        let pool_weight = pool.get_weight()?;
+        let weight = pool_weight * params.rate;
+        let amount = maker.weight_to_dmc(weight)?;
+        maker.total_staked = dmc_pledge((maker.get_staked()? - amount).max(0f64));
+        maker.total_weight = (maker.get_total_weight()? - weight).max(0f64).to_string();
+        self.set_row("dmc.token", "dmcmaker", "dmc.token", params.miner.as_str(), &maker)?;
+        if params.rate == 1f64 {
+            self.remove_row("dmc.token", "makerpool", scope.as_str(), params.owner.as_str())
+        } else {
+            self.set_row("dmc.token", "makerpool", scope.as_str(), params.owner.as_str(), &MakerPoolRecord {
+                owner: params.owner.clone(),
+                weight: (pool_weight - weight).to_string()
+            })
+        }
+    }
+
+    fn on_mint(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: Mint = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        let amount = asset_amount(params.asset.quantity.as_str())? as u64;
+        let pst = self.get_pst(params.owner.as_str())?;
+        self.set_pst(params.owner.as_str(), pst + amount)
+    }
+
+    fn on_bill(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: Bill = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        let amount = asset_amount(params.asset.quantity.as_str())? as u64;
+        let pst = self.get_pst(params.owner.as_str())?;
+        if amount == 0 || amount > pst {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "{} has {} PST, can't bill {}", params.owner.as_str(), pst, amount));
+        }
+        self.set_pst(params.owner.as_str(), pst - amount)?;
+
+        let bill_id = self.next_bill_id;
+        self.next_bill_id += 1;
+        let date = time_point_sec_to_date(self.now);
+        let scope = sim_key(params.owner.as_str())?.to_string();
+        self.set_row("dmc.token", "billrec", scope.as_str(), bill_id.to_string().as_str(), &BillRecord {
+            primary: bill_id,
+            bill_id: bill_id.to_string(),
+            owner: params.owner.clone(),
+            matched: pst_pledge(0),
+            unmatched: pst_pledge(amount),
+            price: params.price,
+            created_at: date.clone(),
+            updated_at: date
+        })
+    }
+
+    fn on_unbill(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: UnBill = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        let scope = sim_key(params.owner.as_str())?.to_string();
+        let bill_id = params.bill_id.to_string();
+        let mut bill: BillRecord = self.get_row("dmc.token", "billrec", scope.as_str(), bill_id.as_str())?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find bill {} of {}", params.bill_id, params.owner.as_str())
+        })?;
+        let pst = self.get_pst(params.owner.as_str())?;
+        self.set_pst(params.owner.as_str(), pst + bill.unmatched.get_amount()?)?;
+        if bill.matched.get_amount()? == 0 {
+            self.remove_row("dmc.token", "billrec", scope.as_str(), bill_id.as_str())
+        } else {
+            bill.unmatched = pst_pledge(0);
+            bill.updated_at = time_point_sec_to_date(self.now);
+            self.set_row("dmc.token", "billrec", scope.as_str(), bill_id.as_str(), &bill)
+        }
+    }
+
+    fn on_add_merkle(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: AddMerkle = sim_decode(action)?;
+        Self::require_auth(action, params.sender.as_str())?;
+        let order = self.get_order(params.order_id)?;
+        let mut challenge = self.get_challenge(params.order_id)?;
+        if challenge.state != DMCChallengeState::ChallengePrepare as u32 {
+            return Err(cyfs_err!(BuckyErrorCode::ErrorState, "order {} challenge state is {}", params.order_id, challenge.state));
+        }
+        // the user commits first, the miner's root has to match it
+        if params.sender == order.user {
+            challenge.pre_merkle_root = params.merkle_root.to_string();
+            challenge.pre_data_block_count = params.data_block_count;
+        } else if params.sender == order.miner {
+            challenge.merkle_root = params.merkle_root.to_string();
+            challenge.data_block_count = params.data_block_count;
+            challenge.merkle_submitter = params.sender.clone();
+        } else {
+            return Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "{} isn't in order {}", params.sender.as_str(), params.order_id));
+        }
+        if !challenge.merkle_root.is_empty()
+            && challenge.merkle_root == challenge.pre_merkle_root
+            && challenge.data_block_count == challenge.pre_data_block_count {
+            challenge.state = DMCChallengeState::ChallengeConsistent as u32;
+        }
+        self.set_challenge(&challenge)
+    }
+
+    fn on_challenge(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: ChallengeReq = sim_decode(action)?;
+        Self::require_auth(action, params.sender.as_str())?;
+        let order = self.get_order(params.order_id)?;
+        if params.sender != order.user {
+            return Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "only the user of order {} can challenge", params.order_id));
+        }
+        let mut challenge = self.get_challenge(params.order_id)?;
+        if challenge.state != DMCChallengeState::ChallengeConsistent as u32
+            && challenge.state != DMCChallengeState::ChallengeAnswer as u32
+            && challenge.state != DMCChallengeState::ChallengeArbitrationUserPay as u32 {
+            return Err(cyfs_err!(BuckyErrorCode::ErrorState, "order {} challenge state is {}", params.order_id, challenge.state));
+        }
+        if params.data_id >= challenge.data_block_count {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "data id {} out of {} blocks", params.data_id, challenge.data_block_count));
+        }
+        challenge.data_id = params.data_id;
+        challenge.hash_data = params.hash_data.to_string();
+        challenge.nonce = params.nonce.clone();
+        challenge.challenge_times += 1;
+        challenge.state = DMCChallengeState::ChallengeRequest as u32;
+        challenge.challenge_date = time_point_sec_to_date(self.now);
+        self.set_challenge(&challenge)
+    }
+
+    fn on_challenge_resp(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: ChallengeResp = sim_decode(action)?;
+        Self::require_auth(action, params.sender.as_str())?;
+        let order = self.get_order(params.order_id)?;
+        if params.sender != order.miner {
+            return Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "only the miner of order {} can answer", params.order_id));
+        }
+        let mut challenge = self.get_challenge(params.order_id)?;
+        if challenge.state != DMCChallengeState::ChallengeRequest as u32 {
+            return Err(cyfs_err!(BuckyErrorCode::ErrorState, "order {} challenge state is {}", params.order_id, challenge.state));
+        }
+        // a wrong reply is taken but leaves the challenge open, the miner may still arbitrate
+        if hash_data(params.reply_hash.as_slice()).to_string() == challenge.hash_data {
+            challenge.state = DMCChallengeState::ChallengeAnswer as u32;
+            self.set_challenge(&challenge)?;
+        } else {
+            log::info!("order {} reply hash {} unmatched", params.order_id, params.reply_hash.to_string());
+        }
+        Ok(())
+    }
+
+    fn on_arbitration(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: Arbitration = sim_decode(action)?;
+        Self::require_auth(action, params.sender.as_str())?;
+        let order = self.get_order(params.order_id)?;
+        if params.sender != order.miner {
+            return Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "only the miner of order {} can arbitrate", params.order_id));
+        }
+        let mut challenge = self.get_challenge(params.order_id)?;
+        if challenge.state != DMCChallengeState::ChallengeRequest as u32 {
+            return Err(cyfs_err!(BuckyErrorCode::ErrorState, "order {} challenge state is {}", params.order_id, challenge.state));
+        }
+        // the user is wrong when the proven piece doesn't give the hash it challenged with
+        let proven = verify_piece(params.data.as_slice(), challenge.data_id, params.cut_merkle.as_slice(), challenge.merkle_root.as_str());
+        let reply_hash = hash_data(vec![params.data.as_slice(), challenge.nonce.as_bytes()].concat().as_slice());
+        if proven && hash_data(reply_hash.as_slice()).to_string() != challenge.hash_data {
+            challenge.state = DMCChallengeState::ChallengeArbitrationUserPay as u32;
+            self.set_challenge(&challenge)
+        } else {
+            challenge.state = DMCChallengeState::ChallengeArbitrationMinerPay as u32;
+            self.set_challenge(&challenge)?;
+            self.penalize(params.order_id)
+        }
+    }
+
+    fn on_bind(&mut self, action: &Action) -> BuckyResult<()> {
+        let params: CyfsBind = sim_decode(action)?;
+        Self::require_auth(action, params.owner.as_str())?;
+        self.set_row("cyfsaddrinfo", "accountmap", "cyfsaddrinfo", params.owner.as_str(), &CyfsAccount {
+            account: params.owner.clone(),
+            address: params.address.clone()
+        })
+    }
+
+    fn on_rpc(&mut self, method: &str, body: &str) -> BuckyResult<Value> {
+        let param: Value = if body.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(body).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "parse {} params err {}", method, e)
+            })?
+        };
+        let ret = match method {
+            "get_info" => serde_json::to_value(self.get_info()),
+            "get_block" => serde_json::to_value(self.get_block_result(require_param(&param, "block_num_or_id")?.as_str())?),
+            "get_block_info" => serde_json::to_value(self.get_block_info(require_param(&param, "block_num")?.as_str())?),
+            "get_block_header_state" => serde_json::to_value(self.get_block_header_state(require_param(&param, "block_num_or_id")?.as_str())?),
+            "get_table_rows" => serde_json::to_value(self.get_table_rows(&param)?),
+            "get_raw_abi" => serde_json::to_value(self.get_raw_abi(require_param(&param, "account_name")?.as_str())?),
+            "push_transaction" => serde_json::to_value(self.push_transaction(&param)?),
+            _ => return Err(cyfs_err!(BuckyErrorCode::NotSupport, "{} isn't simulated", method))
+        };
+        ret.map_err(|e| cyfs_err!(BuckyErrorCode::Failed, "encode {} result err {}", method, e))
+    }
+
+    fn get_info(&self) -> GetInfoResult {
+        let head = self.head();
+        let irreversible = &self.blocks[std::cmp::max(head.block_num - self.irreversible_lag, 1) as usize - 1];
+        GetInfoResult {
+            server_version: "simulator".to_string(),
+            chain_id: self.chain_id.clone(),
+            head_block_num: head.block_num,
+            head_block_time: Some(time_point_sec_to_date(head.time)),
+            last_irreversible_block_num: irreversible.block_num,
+            last_irreversible_block_id: irreversible.id.clone(),
+            last_irreversible_block_time: Some(time_point_sec_to_date(irreversible.time)),
+            block_cpu_limit: 0,
+            block_net_limit: 0,
+            server_version_string: None,
+            fork_db_head_block_num: Some(head.block_num),
+            fork_db_head_block_id: Some(head.id.clone()),
+            server_full_version_string: None,
+            first_block_num: Some(1)
+        }
+    }
+
+    fn get_block_result(&self, block_num_or_id: &str) -> BuckyResult<GetBlockResult> {
+        let block = self.get_block(block_num_or_id)?;
+        Ok(GetBlockResult {
+            timestamp: time_point_sec_to_date(block.time),
+            producer: "dmc".to_string(),
+            confirmed: 0,
+            previous: block.previous.clone(),
+            transaction_mroot: "0".repeat(64),
+            action_mroot: "0".repeat(64),
+            schedule_version: 0,
+            producer_signature: "".to_string(),
+            id: block.id.clone(),
+            block_num: block.block_num,
+            ref_block_prefix: block.ref_block_prefix(),
+            transactions: block.trxs.iter().map(|(trx_id, status)| BlockTransactionReceipt {
+                status: status.clone(),
+                trx: json!({ "id": trx_id })
+            }).collect()
+        })
+    }
+
+    fn get_block_info(&self, block_num: &str) -> BuckyResult<GetBlockInfoResult> {
+        let block = self.get_block(block_num)?;
+        Ok(GetBlockInfoResult {
+            timestamp: time_point_sec_to_date(block.time),
+            producer: "dmc".to_string(),
+            confirmed: 0,
+            previous: block.previous.clone(),
+            transaction_mroot: "0".repeat(64),
+            action_mroot: "0".repeat(64),
+            schedule_version: 0,
+            producer_signature: "".to_string(),
+            id: block.id.clone(),
+            block_num: block.block_num,
+            ref_block_num: block.block_num & 0xffff,
+            ref_block_prefix: block.ref_block_prefix()
+        })
+    }
+
+    fn get_block_header_state(&self, block_num_or_id: &str) -> BuckyResult<GetBlockHeaderStateResult> {
+        let block = self.get_block(block_num_or_id)?;
+        Ok(GetBlockHeaderStateResult {
+            id: block.id.clone(),
+            header: SignedBlockHeader {
+                timestamp: time_point_sec_to_date(block.time)
+            },
+            block_num: block.block_num
+        })
+    }
+
+    fn get_table_rows(&mut self, param: &Value) -> BuckyResult<GetTableRowsResult<Value>> {
+        let code = require_param(param, "code")?;
+        let table = require_param(param, "table")?;
+        let scope = require_param(param, "scope")?;
+        let lower_bound = match param_str(param, "lower_bound") {
+            Some(bound) if !bound.is_empty() => sim_key(bound.as_str())?,
+            _ => 0
+        };
+        let upper_bound = match param_str(param, "upper_bound") {
+            Some(bound) if !bound.is_empty() => sim_key(bound.as_str())?,
+            _ => u64::MAX
+        };
+        let limit = param.get("limit").and_then(|v| v.as_i64()).map(|v| v.max(0) as usize).unwrap_or(SIM_DEFAULT_ROWS);
+        let reverse = param.get("reverse").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let rows = self.table(code.as_str(), table.as_str(), scope.as_str())?;
+        let mut matched: Vec<(u64, Value)> = if lower_bound > upper_bound {
+            vec![]
+        } else {
+            rows.range(lower_bound..=upper_bound).map(|(key, row)| (*key, row.clone())).collect()
+        };
+        if reverse {
+            matched.reverse();
+        }
+        let more = matched.len() > limit;
+        let next_key = if more { matched[limit].0.to_string() } else { "".to_string() };
+        matched.truncate(limit);
+        Ok(GetTableRowsResult {
+            rows: matched.into_iter().map(|(_, row)| row).collect(),
+            more,
+            next_key
+        })
+    }
+
+    // every contract has an empty abi, only actions built by this crate can be pushed
+    fn get_raw_abi(&self, account_name: &str) -> BuckyResult<GetRawAbiResult> {
+        let abi = AbiDef {
+            version: "eosio::abi/1.1".to_string(),
+            types: vec![],
+            structs: vec![],
+            actions: vec![],
+            tables: vec![],
+            ricardian_clauses: vec![],
+            error_messages: vec![],
+            abi_extensions: vec![],
+            variants: None,
+            action_results: None,
+            kv_tables: None
+        }.to_raw_abi()?;
+        Ok(GetRawAbiResult {
+            account_name: account_name.to_string(),
+            code_hash: "0".repeat(64),
+            abi_hash: sha256_hex(abi.as_slice()),
+            abi: base64::encode(abi.as_slice())
+        })
+    }
+
+    fn push_transaction(&mut self, param: &Value) -> BuckyResult<TransactResult> {
+        let packed_trx = hex::decode(require_param(param, "packed_trx")?.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidParam, "decode packed_trx err {}", e)
+        })?;
+        let compression = param.get("compression").and_then(|v| v.as_i64()).unwrap_or(0);
+        let mut serialized_transaction = if compression == 1 {
+            let mut data = Vec::new();
+            flate2::read::ZlibDecoder::new(packed_trx.as_slice()).read_to_end(&mut data).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidParam, "inflate packed_trx err {}", e)
+            })?;
+            data
+        } else {
+            packed_trx
+        };
+        let trx_id = sha256_hex(serialized_transaction.as_slice());
+        let trans = Transaction::dmc_deserialize(&mut SerialBuffer::new(&mut serialized_transaction))?;
+        // signatures aren't checked, the expiration is
+        if date_to_time_point(trans.expiration.as_str())? < self.now {
+            return Err(cyfs_err!(BuckyErrorCode::Timeout, "transaction {} expired at {}", trx_id, trans.expiration));
+        }
+        let take_action = |set: &mut HashSet<String>| trans.actions.iter().any(|action| set.remove(action.name.as_str()));
+        if take_action(&mut self.drops) {
+            log::info!("simulator dropped trx {}", trx_id);
+        } else if take_action(&mut self.hard_fails) {
+            log::info!("simulator packed failed trx {} in block {}", trx_id, self.blocks.len() + 1);
+            self.produce_block(vec![(trx_id.clone(), "hard_fail".to_string())]);
+        } else {
+            self.apply_transaction(&trans)?;
+            log::info!("simulator packed trx {} in block {}", trx_id, self.blocks.len() + 1);
+            self.produce_block(vec![(trx_id.clone(), "executed".to_string())]);
+        }
+        Ok(TransactResult {
+            transaction_id: trx_id,
+            processed: None
+        })
+    }
+
+    fn tracker_order(order: &DMCOrder) -> BuckyResult<TrackerDMCOrder> {
+        Ok(TrackerDMCOrder {
+            id: order.order_id.to_string(),
+            user: TrackerDMCUser { id: order.user.clone() },
+            miner: TrackerDMCUser { id: order.miner.clone() },
+            bill: TrackerDMCBill { id: order.bill_id.clone() },
+            state: order.state,
+            user_pledge_amount: order.user_pledge.get_quantity(),
+            latest_settlement_date: order.latest_settlement_date.clone(),
+            miner_lock_pst_amount: order.miner_pledge.get_quantity(),
+            miner_lock_pst_symbol: "PST".to_string()
+        })
+    }
+
+    // only the find_order filters the clients send are understood
+    fn find_order(&mut self, query: &str) -> BuckyResult<Value> {
+        let capture = |pattern: &str| -> Option<String> {
+            regex::Regex::new(pattern).unwrap().captures(query).map(|c| c[1].to_string())
+        };
+        let skip = capture(r"skip:\s*(\d+)").and_then(|v| v.parse().ok()).unwrap_or(0usize);
+        let limit = capture(r"limit:\s*(\d+)").and_then(|v| v.parse().ok()).unwrap_or(SIM_DEFAULT_ROWS);
+        let miner_id = capture(r#"miner_id:\s*"([^"]*)""#);
+        let user_id = capture(r#"user_id:\s*"([^"]*)""#);
+        let id = capture(r#"[{\s,]id:\s*"([^"]*)""#);
+        let state = capture(r"state:\s*(\d+)").and_then(|v| v.parse::<u8>().ok());
+
+        let mut orders = Vec::new();
+        // newest first, like order: "-createdAt"
+        for row in self.table("dmc.token", "dmcorder", "dmc.token")?.values().rev() {
+            let order: DMCOrder = serde_json::from_value(row.clone()).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidData, "parse order err {}", e)
+            })?;
+            if miner_id.as_ref().map(|v| *v != order.miner).unwrap_or(false)
+                || user_id.as_ref().map(|v| *v != order.user).unwrap_or(false)
+                || id.as_ref().map(|v| *v != order.order_id.to_string()).unwrap_or(false)
+                || state.map(|v| v != order.state).unwrap_or(false) {
+                continue;
+            }
+            orders.push(order);
+        }
+        let list: BuckyResult<Vec<TrackerDMCOrder>> = orders.iter().skip(skip).take(limit).map(|order| Self::tracker_order(order)).collect();
+        Ok(json!({
+            "data": {
+                "find_order": list?
+            }
+        }))
+    }
+}
+
+type SimState = Arc<Mutex<SimChain>>;
+
+fn sim_response(ret: BuckyResult<Value>) -> Response {
+    let (status, body) = match ret {
+        Ok(body) => (StatusCode::Ok, body),
+        Err(e) => {
+            log::info!("simulator err {}", e);
+            (StatusCode::InternalServerError, json!({
+                "code": 500,
+                "message": "Internal Service Error",
+                "error": {
+                    "name": format!("{:?}", e.code()),
+                    "what": e.to_string()
+                }
+            }))
+        }
+    };
+    let mut resp = Response::new(status);
+    resp.set_body(Body::from_string(body.to_string()));
+    resp.set_content_type(tide::http::mime::JSON);
+    resp
+}
+
+// an in process DMC node and tracker, serves the rpc the clients use from tables kept in memory
+pub struct DMCSimulator {
+    chain: SimState,
+    server: String,
+}
+
+impl DMCSimulator {
+    pub async fn start() -> BuckyResult<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "bind simulator err {}", e)
+        })?;
+        let addr = listener.local_addr().map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "get simulator addr err {}", e)
+        })?;
+        let chain = Arc::new(Mutex::new(SimChain::new()));
+
+        let mut app = tide::with_state(chain.clone());
+        app.at("/v1/chain/:method").post(|mut req: Request<SimState>| async move {
+            let method = req.param("method")?.to_string();
+            let body = req.body_string().await?;
+            let ret = req.state().lock().unwrap().on_rpc(method.as_str(), body.as_str());
+            Ok(sim_response(ret))
+        });
+        app.at("/1.1").post(|mut req: Request<SimState>| async move {
+            let body = req.body_string().await?;
+            let ret = req.state().lock().unwrap().find_order(body.as_str());
+            Ok(sim_response(ret))
+        });
+        async_std::task::spawn(async move {
+            if let Err(e) = app.listen(listener).await {
+                log::error!("simulator listen err {}", e);
+            }
+        });
+
+        Ok(Self {
+            chain,
+            server: format!("http://{}", addr)
+        })
+    }
+
+    // url of both the chain rpc and the tracker
+    pub fn server(&self) -> &str {
+        self.server.as_str()
+    }
+
+    pub fn now(&self) -> i64 {
+        self.chain.lock().unwrap().now
+    }
+
+    pub fn head_block_num(&self) -> i64 {
+        self.chain.lock().unwrap().head().block_num
+    }
+
+    pub fn produce_block(&self) {
+        self.chain.lock().unwrap().produce_block(vec![]);
+    }
+
+    // move the clock on and produce a block, challenges not answered in time time out
+    pub fn advance(&self, secs: i64) {
+        let mut chain = self.chain.lock().unwrap();
+        chain.now += secs;
+        chain.produce_block(vec![]);
+    }
+
+    pub fn set_challenge_timeout(&self, secs: i64) {
+        self.chain.lock().unwrap().challenge_timeout = secs;
+    }
+
+    // the next transaction with the action fails with reason
+    pub fn fail_next(&self, action: &str, reason: &str) {
+        self.chain.lock().unwrap().failures.insert(action.to_string(), reason.to_string());
+    }
+
+    // the next transaction with the action is taken but lost before any block, it can only expire
+    pub fn drop_next(&self, action: &str) {
+        self.chain.lock().unwrap().drops.insert(action.to_string());
+    }
+
+    // the next transaction with the action is packed with a hard_fail receipt, nothing is applied
+    pub fn hard_fail_next(&self, action: &str) {
+        self.chain.lock().unwrap().hard_fails.insert(action.to_string());
+    }
+
+    // blocks are irreversible blocks behind head, 0 by default
+    pub fn set_irreversible_lag(&self, blocks: i64) {
+        self.chain.lock().unwrap().irreversible_lag = blocks;
+    }
+
+    pub fn set_row<T: Serialize>(&self, code: &str, table: &str, scope: &str, key: &str, row: &T) -> BuckyResult<()> {
+        self.chain.lock().unwrap().set_row(code, table, scope, key, row)
+    }
+
+    pub fn get_row<T: DeserializeOwned>(&self, code: &str, table: &str, scope: &str, key: &str) -> BuckyResult<Option<T>> {
+        self.chain.lock().unwrap().get_row(code, table, scope, key)
+    }
+
+    pub fn remove_row(&self, code: &str, table: &str, scope: &str, key: &str) -> BuckyResult<()> {
+        self.chain.lock().unwrap().remove_row(code, table, scope, key)
+    }
+
+    // an order in delivery with the next order id, change it before add_order as needed
+    pub fn new_order(&self, user: &str, miner: &str, pst: u64) -> DMCOrder {
+        let chain = self.chain.lock().unwrap();
+        let date = time_point_sec_to_date(chain.now);
+        DMCOrder {
+            order_id: chain.next_order_id,
+            user: user.to_string(),
+            miner: miner.to_string(),
+            bill_id: "0".to_string(),
+            user_pledge: dmc_pledge(pst as f64 * 24f64),
+            miner_pledge: pst_pledge(pst),
+            price: dmc_pledge(pst as f64),
+            settlement_pledge: dmc_pledge(0f64),
+            lock_pledge: dmc_pledge(pst as f64 * 2f64),
+            state: DMCOrderState::OrderStateDeliver as u8,
+            latest_settlement_date: date.clone(),
+            deliver_start_date: date
+        }
+    }
+
+    // adds the order and its challenge row waiting for merkle roots
+    pub fn add_order(&self, order: DMCOrder) -> BuckyResult<u64> {
+        let mut chain = self.chain.lock().unwrap();
+        chain.next_order_id = std::cmp::max(chain.next_order_id, order.order_id + 1);
+        chain.set_order(&order)?;
+        chain.set_challenge(&DMCChallengeInfo {
+            order_id: order.order_id,
+            pre_merkle_root: "".to_string(),
+            pre_data_block_count: 0,
+            merkle_root: "".to_string(),
+            data_block_count: 0,
+            merkle_submitter: "".to_string(),
+            data_id: 0,
+            hash_data: "".to_string(),
+            challenge_times: 0,
+            nonce: "".to_string(),
+            state: DMCChallengeState::ChallengePrepare as u32,
+            challenge_date: time_point_sec_to_date(chain.now)
+        })?;
+        Ok(order.order_id)
+    }
+
+    pub fn get_order(&self, order_id: u64) -> BuckyResult<Option<DMCOrder>> {
+        self.get_row("dmc.token", "dmcorder", "dmc.token", order_id.to_string().as_str())
+    }
+
+    pub fn get_challenge(&self, order_id: u64) -> BuckyResult<Option<DMCChallengeInfo>> {
+        self.get_row("dmc.token", "dmchallenge", "dmc.token", order_id.to_string().as_str())
+    }
+
+    // one settlement period, the price moves from the user pledge to the miner
+    pub fn settle_order(&self, order_id: u64) -> BuckyResult<()> {
+        let mut chain = self.chain.lock().unwrap();
+        let mut order = chain.get_order(order_id)?;
+        let user_pledge = asset_amount(order.user_pledge.quantity.as_str())?;
+        let price = asset_amount(order.price.quantity.as_str())?;
+        if user_pledge < price {
+            order.state = DMCOrderState::OrderStateEnd as u8;
+            order.lock_pledge = dmc_pledge(0f64);
+        } else {
+            order.user_pledge = dmc_pledge(user_pledge - price);
+        }
+        order.latest_settlement_date = time_point_sec_to_date(chain.now);
+        chain.set_order(&order)
+    }
+
+    pub fn set_pst(&self, owner: &str, amount: u64) -> BuckyResult<()> {
+        self.chain.lock().unwrap().set_pst(owner, amount)
+    }
+
+    pub fn set_pst_trans_info(&self, info: &PstTransInfo) -> BuckyResult<()> {
+        self.set_row("dmc.token", "priceavg", "dmc.token", "0", info)
+    }
+}
+
+#[cfg(test)]
+mod test_simulator {
+    use cyfs_base::*;
+    use sha2::Digest;
+    use crate::*;
+
+    fn client(sim: &DMCSimulator, account: &str) -> DMCClient<LocalDMCTxSender<SimpleSignatureProvider>> {
+        let sender = LocalDMCTxSender::new(account, sim.server(), SimpleSignatureProvider::new(vec![]).unwrap());
+        DMCClient::new(account, sim.server(), sim.server(), sender)
+    }
+
+    fn node_hash(left: &[u8], right: &[u8]) -> HashValue {
+        let mut sha256 = sha2::Sha256::new();
+        sha256.update(left);
+        sha256.update(right);
+        let hash: [u8; 32] = sha256.finalize().into();
+        HashValue::try_from(&hash[..]).unwrap()
+    }
+
+    #[test]
+    fn test_challenge_flow() {
+        async_std::task::block_on(async {
+            let sim = DMCSimulator::start().await.unwrap();
+            let user = client(&sim, "user");
+            let miner = client(&sim, "miner");
+            let order_id = sim.add_order(sim.new_order("user", "miner", 1)).unwrap();
+            let id = order_id.to_string();
+
+            // four pieces, a two level tree
+            let pieces: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 1024]).collect();
+            let leafs: Vec<HashValue> = pieces.iter().map(|piece| hash_data(piece.as_slice())).collect();
+            let left = node_hash(leafs[0].as_slice(), leafs[1].as_slice());
+            let right = node_hash(leafs[2].as_slice(), leafs[3].as_slice());
+            let root = node_hash(left.as_slice(), right.as_slice());
+
+            user.add_merkle(id.as_str(), root.clone(), 4).await.unwrap();
+            miner.add_merkle(id.as_str(), root.clone(), 4).await.unwrap();
+            assert_eq!(sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeConsistent as u32);
+
+            // the miner has the piece and answers
+            let reply = hash_data(vec![pieces[1].as_slice(), b"n1"].concat().as_slice());
+            user.challenge(id.as_str(), 1, hash_data(reply.as_slice()), "n1".to_string()).await.unwrap();
+            assert_eq!(sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeRequest as u32);
+            miner.add_challenge_resp(id.as_str(), reply).await.unwrap();
+            assert_eq!(sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeAnswer as u32);
+
+            // the user challenges with a wrong hash, the miner proves the piece
+            user.challenge(id.as_str(), 2, hash_data(b"wrong"), "n2".to_string()).await.unwrap();
+            miner.arbitration(id.as_str(), pieces[2].clone(), vec![leafs[3].clone(), left.clone()]).await.unwrap();
+            assert_eq!(sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeArbitrationUserPay as u32);
+
+            // nobody answers, the miner pays
+            let reply = hash_data(vec![pieces[3].as_slice(), b"n3"].concat().as_slice());
+            user.challenge(id.as_str(), 3, hash_data(reply.as_slice()), "n3".to_string()).await.unwrap();
+            sim.set_challenge_timeout(60);
+            sim.advance(61);
+            let challenge = sim.get_challenge(order_id).unwrap().unwrap();
+            assert_eq!(challenge.state, DMCChallengeState::ChallengeTimeout as u32);
+            assert_eq!(challenge.challenge_times, 3);
+            let order = sim.get_order(order_id).unwrap().unwrap();
+            assert_eq!(order.state, DMCOrderState::OrderStateEnd as u8);
+            assert_eq!(order.lock_pledge.get_quantity(), "0.0000");
+
+            let orders = miner.get_miner_orders(None).await.unwrap();
+            assert_eq!(orders.len(), 1);
+            assert_eq!(orders[0].id, id);
+            assert!(user.get_order_by_id("100").await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn test_stake_and_bill() {
+        async_std::task::block_on(async {
+            let sim = DMCSimulator::start().await.unwrap();
+            let miner = client(&sim, "miner");
+
+            miner.stake("100").await.unwrap();
+            let stake_info = miner.get_stake_info("miner").await.unwrap();
+            assert_eq!(stake_info.get_staked().unwrap(), 100f64);
+            miner.unstake(0.25).await.unwrap();
+            let stake_info = miner.get_stake_info("miner").await.unwrap();
+            assert_eq!(stake_info.get_staked().unwrap(), 75f64);
+            let pool = miner.get_maker_pool("miner", "miner").await.unwrap().unwrap();
+            assert_eq!(stake_info.weight_to_dmc(pool.get_weight().unwrap()).unwrap(), 75f64);
+
+            miner.mint("10").await.unwrap();
+            miner.bill("6".to_string(), 1.5, "".to_string()).await.unwrap();
+            assert_eq!(miner.get_pst_amount("miner").await.unwrap(), 4);
+            let bills = miner.get_bill_list("miner", None).await.unwrap();
+            assert_eq!(bills.len(), 1);
+            assert_eq!(bills[0].unmatched.get_amount().unwrap(), 6);
+
+            // withdraw part of it, the rest is billed again
+            miner.withdraw_bill(bills[0].primary, Some(2)).await.unwrap();
+            assert_eq!(miner.get_pst_amount("miner").await.unwrap(), 6);
+            let bills = miner.get_bill_list("miner", None).await.unwrap();
+            assert_eq!(bills.len(), 1);
+            assert_eq!(bills[0].unmatched.get_amount().unwrap(), 4);
+
+            // a failed action reverts the whole transaction
+            sim.fail_next("bill", "no");
+            assert!(miner.reprice_bill(bills[0].primary, 2f64).await.is_err());
+            assert_eq!(miner.get_bill_list("miner", None).await.unwrap()[0].price, 1.5);
+        });
+    }
+}
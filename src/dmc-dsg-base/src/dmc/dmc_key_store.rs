@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use cyfs_base::{bucky_time_now, BuckyErrorCode, BuckyResult};
+use crate::*;
+
+pub struct DMCKeyInfo {
+    pub alias: String,
+    pub public_key: String,
+}
+
+pub struct DMCKeyStore {
+    dir: PathBuf,
+}
+
+impl DMCKeyStore {
+    // the keystore dir is only open to its owner, a dir created by an older version is narrowed
+    pub fn open(dir: &Path) -> BuckyResult<Self> {
+        if !dir.exists() {
+            let mut builder = std::fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::DirBuilderExt;
+                builder.mode(0o700);
+            }
+            builder.create(dir).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::IoError, "create {} failed {}", dir.display(), e)
+            })?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::IoError, "set permissions of {} failed {}", dir.display(), e)
+            })?;
+        }
+        Ok(Self {
+            dir: dir.to_path_buf()
+        })
+    }
+
+    pub fn open_default() -> BuckyResult<Self> {
+        Self::open(cyfs_util::get_app_data_dir(DMCDsgConfig::APP_NAME).join("keystore").as_path())
+    }
+
+    fn check_alias(alias: &str) -> BuckyResult<()> {
+        if alias.is_empty() || !alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "invalid key alias {}", alias));
+        }
+        Ok(())
+    }
+
+    pub fn key_path(&self, alias: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", alias))
+    }
+
+    pub fn contains(&self, alias: &str) -> bool {
+        Self::check_alias(alias).is_ok() && self.key_path(alias).exists()
+    }
+
+    fn save_key(&self, alias: &str, private_key: &DMCPrivateKey, passphrase: &str) -> BuckyResult<String> {
+        let encrypted = EncryptedDMCKey::encrypt(private_key, passphrase)?;
+        encrypted.save(self.key_path(alias).as_path())?;
+        Ok(encrypted.public_key)
+    }
+
+    pub fn import(&self, alias: &str, private_key: &str, passphrase: &str) -> BuckyResult<String> {
+        Self::check_alias(alias)?;
+        if self.key_path(alias).exists() {
+            return Err(cyfs_err!(BuckyErrorCode::AlreadyExists, "key alias {} already exists", alias));
+        }
+        let private_key = DMCPrivateKey::from_str(private_key)?;
+        self.save_key(alias, &private_key, passphrase)
+    }
+
+    pub fn generate(&self, alias: &str, passphrase: &str) -> BuckyResult<String> {
+        Self::check_alias(alias)?;
+        if self.key_path(alias).exists() {
+            return Err(cyfs_err!(BuckyErrorCode::AlreadyExists, "key alias {} already exists", alias));
+        }
+        self.save_key(alias, &DMCPrivateKey::gen_key(), passphrase)
+    }
+
+    pub fn list(&self) -> BuckyResult<Vec<DMCKeyInfo>> {
+        let dir = std::fs::read_dir(self.dir.as_path()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", self.dir.display(), e)
+        })?;
+        let mut list = Vec::new();
+        for entry in dir {
+            let path = entry.map_err(|e| {
+                cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", self.dir.display(), e)
+            })?.path();
+            if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+                continue;
+            }
+            let alias = path.file_stem().unwrap().to_string_lossy().to_string();
+            let encrypted = EncryptedDMCKey::load(path.as_path())?;
+            list.push(DMCKeyInfo {
+                alias,
+                public_key: encrypted.public_key
+            });
+        }
+        list.sort_by(|a, b| a.alias.cmp(&b.alias));
+        Ok(list)
+    }
+
+    pub fn get_public_key(&self, alias: &str) -> BuckyResult<String> {
+        Ok(self.load_encrypted(alias)?.public_key)
+    }
+
+    fn load_encrypted(&self, alias: &str) -> BuckyResult<EncryptedDMCKey> {
+        Self::check_alias(alias)?;
+        let path = self.key_path(alias);
+        if !path.exists() {
+            return Err(cyfs_err!(BuckyErrorCode::NotFound, "key alias {} not found", alias));
+        }
+        EncryptedDMCKey::load(path.as_path())
+    }
+
+    pub fn load(&self, alias: &str, passphrase: &str) -> BuckyResult<DMCPrivateKey> {
+        self.load_encrypted(alias)?.decrypt(passphrase)
+    }
+
+    pub fn export(&self, alias: &str, passphrase: &str) -> BuckyResult<String> {
+        self.load(alias, passphrase)?.to_legacy_string()
+    }
+
+    // replace the key under alias with a new one, the old key is kept as <alias>.<time>.bak.
+    // nothing else learns the new key, the caller confirms with the user first;
+    // a light key is rotated with rotate_light_key, which moves the chain and the miner along
+    pub fn rotate(&self, alias: &str, passphrase: &str) -> BuckyResult<String> {
+        let old_key = self.load(alias, passphrase)?;
+        let backup_path = self.dir.join(format!("{}.{}.bak", alias, bucky_time_now()));
+        EncryptedDMCKey::encrypt(&old_key, passphrase)?.save(backup_path.as_path())?;
+        self.save_key(alias, &DMCPrivateKey::gen_key(), passphrase)
+    }
+
+    pub fn remove(&self, alias: &str) -> BuckyResult<()> {
+        self.load_encrypted(alias)?;
+        std::fs::remove_file(self.key_path(alias)).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "remove key {} failed {}", alias, e)
+        })
+    }
+}
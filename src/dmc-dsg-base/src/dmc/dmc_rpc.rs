@@ -109,7 +109,9 @@ pub struct TransactionTrace {
 #[derive(Serialize, Deserialize)]
 pub struct TransactResult {
     pub transaction_id: String,
-    // pub processed: TransactionTrace,
+    // kept as json, a trace field nodeos adds mustn't fail the push
+    #[serde(default)]
+    pub processed: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -144,6 +146,7 @@ pub struct GetInfoResult {
     pub server_version: String,
     pub chain_id: String,
     pub head_block_num: i64,
+    pub head_block_time: Option<String>,
     pub last_irreversible_block_num: i64,
     pub last_irreversible_block_id: String,
     pub last_irreversible_block_time: Option<String>,
@@ -285,11 +288,20 @@ impl DMCDeserialize for Authority {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct LinkedAction {
+    pub account: String,
+    pub action: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Permission {
     pub perm_name: String,
     pub parent: String,
     pub required_auth: Authority,
+    // only reported by newer nodes
+    #[serde(default)]
+    pub linked_actions: Option<Vec<LinkedAction>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -435,6 +447,23 @@ pub struct GetBlockHeaderStateResult {
     pub block_num: i64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct BlockTransactionReceipt {
+    pub status: String,
+    pub trx: serde_json::Value,
+}
+
+impl BlockTransactionReceipt {
+    // trx is the id for deferred transactions and the packed transaction otherwise
+    pub fn trx_id(&self) -> Option<String> {
+        match &self.trx {
+            serde_json::Value::String(id) => Some(id.clone()),
+            serde_json::Value::Object(obj) => obj.get("id").and_then(|id| id.as_str()).map(|id| id.to_string()),
+            _ => None
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetBlockResult {
     pub timestamp: String,
@@ -448,6 +477,8 @@ pub struct GetBlockResult {
     pub id: String,
     pub block_num: i64,
     pub ref_block_prefix: i64,
+    #[serde(default)]
+    pub transactions: Vec<BlockTransactionReceipt>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -697,6 +728,11 @@ impl DMCRpc {
         Err(cyfs_err!(BuckyErrorCode::Failed, "get_account failed"))
     }
 
+    pub async fn get_permissions(&self, account_name: &str) -> BuckyResult<Vec<Permission>> {
+        let account = self.get_account::<Option<serde_json::Value>, Option<serde_json::Value>>(account_name).await?;
+        Ok(account.permissions)
+    }
+
     pub async fn get_accounts_by_authorizers(&self, accounts: Vec<PermissionLevel>, keys: Vec<String>) -> BuckyResult<GetAccountsByAuthorizersResult> {
         let url = format!("{}/v1/chain/get_accounts_by_authorizers", self.server.as_str());
         let req = GetAccountsByAuthorizers {
@@ -44,7 +44,7 @@ fn string_to_key(s: &str, _ty: KeyType, suffix: &str) -> BuckyResult<Vec<u8>> {
     Ok(data[0..data.len()-4].to_vec())
 }
 
-fn key_to_string(key_data: &[u8], suffix: &str, prefix: &str) -> String {
+pub(crate) fn key_to_string(key_data: &[u8], suffix: &str, prefix: &str) -> String {
     let mut digest = digest_suffix_ripemd160(key_data, suffix);
     let mut tmp = Vec::from(key_data);
     tmp.append(&mut digest);
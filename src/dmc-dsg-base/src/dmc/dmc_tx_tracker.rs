@@ -0,0 +1,338 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde::{Serialize, Deserialize};
+use crate::*;
+
+// blocks read from chain in one poll, about half a minute of blocks, a lagging scan catches up over several polls
+const MAX_SCAN_BLOCKS: i64 = 60;
+// the transaction may be packed before track is called, rescan some blocks before head
+const TRACK_RESCAN_BLOCKS: i64 = 20;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum TrackedTxState {
+    Pending,
+    Included(i64),
+    Irreversible(i64),
+    Expired,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrackedTx<T> {
+    pub trx_id: String,
+    pub action: String,
+    pub order_id: String,
+    pub payload: T,
+    pub pushed_block_num: i64,
+    pub expire_at: i64,
+    pub retries: u32,
+    // the blocks since pushed_block_num were scanned again after the transaction expired
+    #[serde(default)]
+    pub rescanned: bool,
+    pub state: TrackedTxState,
+}
+
+impl<T> TrackedTx<T> {
+    pub fn is_finished(&self) -> bool {
+        match self.state {
+            TrackedTxState::Pending | TrackedTxState::Included(_) => false,
+            _ => true
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DMCTxTrackerState<T> {
+    pub scanned_block_num: i64,
+    pub scanned_block_time: i64,
+    pub txs: Vec<TrackedTx<T>>,
+}
+
+impl<T> Default for DMCTxTrackerState<T> {
+    fn default() -> Self {
+        Self {
+            scanned_block_num: 0,
+            scanned_block_time: 0,
+            txs: vec![]
+        }
+    }
+}
+
+impl<T> DMCTxTrackerState<T> {
+    pub fn take_finished(&mut self) -> Vec<TrackedTx<T>> {
+        let mut finished = Vec::new();
+        let mut pending = Vec::new();
+        for tx in self.txs.drain(..) {
+            if tx.is_finished() {
+                finished.push(tx);
+            } else {
+                pending.push(tx);
+            }
+        }
+        self.txs = pending;
+        finished
+    }
+}
+
+pub struct DMCTxTracker {
+    rpc: DMCRpc,
+    expire_seconds: i64,
+}
+
+impl DMCTxTracker {
+    pub fn new(server: &str, expire_seconds: i64) -> Self {
+        Self {
+            rpc: DMCRpc::new(server),
+            expire_seconds
+        }
+    }
+
+    fn get_head_time(info: &GetInfoResult) -> BuckyResult<i64> {
+        match info.head_block_time.as_ref() {
+            Some(time) => date_to_time_point(time.as_str()),
+            None => Ok(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64)
+        }
+    }
+
+    pub async fn track<T>(
+        &self,
+        state: &mut DMCTxTrackerState<T>,
+        ret: TransResult,
+        action: &str,
+        order_id: &str,
+        payload: T,
+        retries: u32
+    ) -> BuckyResult<String> {
+        let trx_id = match ret {
+            TransResult::TransactResult(ret) => ret.transaction_id,
+            _ => {
+                return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "{} of order {} isn't broadcast", action, order_id));
+            }
+        };
+        let info = self.rpc.get_info().await?;
+        // the transaction expires at most expire_seconds after the head block it refers to
+        let expire_at = Self::get_head_time(&info)? + self.expire_seconds;
+        let pushed_block_num = std::cmp::max(info.head_block_num - TRACK_RESCAN_BLOCKS, 1);
+        // nothing was waiting, skip ahead over the blocks since the last tracked transaction
+        // the scan never moves back here, only an expired transaction rewinds it in poll
+        let idle = state.txs.iter().all(|tx| tx.is_finished());
+        if state.scanned_block_num == 0 || (idle && state.scanned_block_num < pushed_block_num) {
+            state.scanned_block_num = pushed_block_num - 1;
+        }
+        log::info!("track {} of order {} trx {} expire at {}", action, order_id, trx_id.as_str(), expire_at);
+
+        state.txs.push(TrackedTx {
+            trx_id: trx_id.clone(),
+            action: action.to_string(),
+            order_id: order_id.to_string(),
+            payload,
+            pushed_block_num,
+            expire_at,
+            retries,
+            rescanned: false,
+            state: TrackedTxState::Pending
+        });
+        Ok(trx_id)
+    }
+
+    // look for pending transactions in new blocks, then move them to irreversible or expired
+    pub async fn poll<T>(&self, state: &mut DMCTxTrackerState<T>) -> BuckyResult<()> {
+        if state.txs.iter().all(|tx| tx.is_finished()) {
+            return Ok(());
+        }
+        let info = self.rpc.get_info().await?;
+        let end_block_num = std::cmp::min(info.head_block_num, state.scanned_block_num + MAX_SCAN_BLOCKS);
+        while state.scanned_block_num < end_block_num {
+            let block_num = state.scanned_block_num + 1;
+            let block = self.rpc.get_block(block_num.to_string()).await?;
+            for receipt in block.transactions.iter() {
+                let trx_id = match receipt.trx_id() {
+                    Some(trx_id) => trx_id,
+                    None => continue
+                };
+                for tx in state.txs.iter_mut() {
+                    if tx.trx_id != trx_id || tx.is_finished() {
+                        continue;
+                    }
+                    if receipt.status.as_str() == "executed" {
+                        log::info!("{} of order {} trx {} included in block {}", tx.action, tx.order_id, trx_id, block_num);
+                        tx.state = TrackedTxState::Included(block_num);
+                    } else {
+                        log::error!("{} of order {} trx {} {} in block {}", tx.action, tx.order_id, trx_id, receipt.status.as_str(), block_num);
+                        tx.state = TrackedTxState::Failed(format!("{} in block {}", receipt.status.as_str(), block_num));
+                    }
+                }
+            }
+            state.scanned_block_num = block_num;
+            state.scanned_block_time = date_to_time_point(block.timestamp.as_str())?;
+        }
+
+        let mut rewind_to = None;
+        for tx in state.txs.iter_mut() {
+            match tx.state {
+                TrackedTxState::Included(block_num) => {
+                    if block_num <= info.last_irreversible_block_num {
+                        log::info!("{} of order {} trx {} is irreversible", tx.action, tx.order_id, tx.trx_id);
+                        tx.state = TrackedTxState::Irreversible(block_num);
+                    }
+                }
+                TrackedTxState::Pending => {
+                    // every block up to the expiration is scanned and the transaction isn't in them
+                    if state.scanned_block_time > tx.expire_at {
+                        if tx.rescanned {
+                            log::error!("{} of order {} trx {} expired", tx.action, tx.order_id, tx.trx_id);
+                            tx.state = TrackedTxState::Expired;
+                        } else {
+                            // it may be packed in a block scanned before it was tracked, look once more from where it was pushed
+                            log::info!("{} of order {} trx {} not found before expiration, rescan from block {}", tx.action, tx.order_id, tx.trx_id, tx.pushed_block_num);
+                            tx.rescanned = true;
+                            rewind_to = Some(std::cmp::min(rewind_to.unwrap_or(tx.pushed_block_num), tx.pushed_block_num));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(block_num) = rewind_to {
+            if block_num <= state.scanned_block_num {
+                state.scanned_block_num = block_num - 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_dmc_tx_tracker {
+    use std::str::FromStr;
+    use cyfs_base::*;
+    use crate::*;
+
+    struct Scenario {
+        sim: DMCSimulator,
+        miner: DMCClient<LocalDMCTxSender<SimpleSignatureProvider>>,
+        tracker: DMCTxTracker,
+        order_id: String,
+    }
+
+    impl Scenario {
+        async fn start() -> Self {
+            let sim = DMCSimulator::start().await.unwrap();
+            let sender = LocalDMCTxSender::new("miner", sim.server(), SimpleSignatureProvider::new(vec![]).unwrap());
+            let miner = DMCClient::new("miner", sim.server(), sim.server(), sender);
+            let order_id = sim.add_order(sim.new_order("user", "miner", 1)).unwrap().to_string();
+            let tracker = DMCTxTracker::new(sim.server(), DMC_TX_EXPIRE_SECONDS);
+            Self {
+                sim,
+                miner,
+                tracker,
+                order_id
+            }
+        }
+
+        async fn add_merkle(&self, state: &mut DMCTxTrackerState<()>, retries: u32) -> String {
+            let root = HashValue::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+            let ret = self.miner.add_merkle(self.order_id.as_str(), root, 4).await.unwrap();
+            self.tracker.track(state, ret, "addmerkle", self.order_id.as_str(), (), retries).await.unwrap()
+        }
+
+        fn merkle_submitted(&self) -> bool {
+            !self.sim.get_challenge(self.order_id.parse().unwrap()).unwrap().unwrap().merkle_root.is_empty()
+        }
+    }
+
+    #[test]
+    fn test_included_then_irreversible() {
+        async_std::task::block_on(async {
+            let scenario = Scenario::start().await;
+            scenario.sim.set_irreversible_lag(3);
+            let mut state = DMCTxTrackerState::default();
+            scenario.add_merkle(&mut state, 0).await;
+            let block_num = scenario.sim.head_block_num();
+
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Included(block_num));
+            assert_eq!(state.scanned_block_num, block_num);
+            assert!(state.take_finished().is_empty());
+
+            // included stays until the block is irreversible
+            scenario.sim.produce_block();
+            scenario.sim.produce_block();
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Included(block_num));
+            scenario.sim.produce_block();
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Irreversible(block_num));
+
+            let finished = state.take_finished();
+            assert_eq!(finished.len(), 1);
+            assert!(state.txs.is_empty());
+            assert!(scenario.merkle_submitted());
+        });
+    }
+
+    #[test]
+    fn test_expired_and_sent_again() {
+        async_std::task::block_on(async {
+            let scenario = Scenario::start().await;
+            let mut state = DMCTxTrackerState::default();
+            scenario.sim.drop_next("addmerkle");
+            let lost_trx_id = scenario.add_merkle(&mut state, 0).await;
+
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Pending);
+
+            // past the expiration the blocks since it was pushed are looked at once more, then it's expired
+            scenario.sim.advance(DMC_TX_EXPIRE_SECONDS + 1);
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Pending);
+            assert!(state.txs[0].rescanned);
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Expired);
+            let expired = state.take_finished();
+            assert_eq!(expired.len(), 1);
+            assert!(!scenario.merkle_submitted());
+
+            // sent again the transaction refers to a newer block, so it's a new transaction
+            let trx_id = scenario.add_merkle(&mut state, expired[0].retries + 1).await;
+            assert_ne!(trx_id, lost_trx_id);
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert!(matches!(state.txs[0].state, TrackedTxState::Irreversible(_)));
+            assert_eq!(state.txs[0].retries, 1);
+            assert!(scenario.merkle_submitted());
+        });
+    }
+
+    #[test]
+    fn test_failed_in_block() {
+        async_std::task::block_on(async {
+            let scenario = Scenario::start().await;
+            let mut state = DMCTxTrackerState::default();
+            scenario.sim.hard_fail_next("addmerkle");
+            scenario.add_merkle(&mut state, 0).await;
+            let block_num = scenario.sim.head_block_num();
+
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert_eq!(state.txs[0].state, TrackedTxState::Failed(format!("hard_fail in block {}", block_num)));
+            assert_eq!(state.take_finished().len(), 1);
+            assert!(!scenario.merkle_submitted());
+        });
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        async_std::task::block_on(async {
+            let scenario = Scenario::start().await;
+            let mut state = DMCTxTrackerState::default();
+            scenario.sim.set_irreversible_lag(1);
+            scenario.add_merkle(&mut state, 0).await;
+            scenario.tracker.poll(&mut state).await.unwrap();
+
+            // the miner saves the state between polls
+            let mut state: DMCTxTrackerState<()> = serde_json::from_str(serde_json::to_string(&state).unwrap().as_str()).unwrap();
+            scenario.sim.produce_block();
+            scenario.tracker.poll(&mut state).await.unwrap();
+            assert!(matches!(state.txs[0].state, TrackedTxState::Irreversible(_)));
+        });
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, HashMap};
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde::{Serialize, Deserialize};
+use crate::*;
+
+const DAY_SECONDS: u64 = 24 * 3600;
+// entries older than this are merged per day, order and kind
+const LEDGER_DAILY_AFTER: u64 = 31 * DAY_SECONDS;
+// entries older than this are merged per month, order and kind
+const LEDGER_MONTHLY_AFTER: u64 = 366 * DAY_SECONDS;
+// order_id of the stake reward entries, order ids are numbers
+pub const STAKE_LEDGER_ID: &str = "stake";
+
+fn parse_asset(pledge: &Pledge) -> BuckyResult<f64> {
+    pledge.get_quantity().parse().map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse {} err {}", pledge.quantity.as_str(), e)
+    })
+}
+
+fn is_penalty(challenge_state: u32) -> bool {
+    challenge_state == DMCChallengeState::ChallengeArbitrationMinerPay as u32
+        || challenge_state == DMCChallengeState::ChallengeTimeout as u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LedgerEntryKind {
+    // DMC the user pledge paid for the order
+    Income,
+    // DMC of lock_pledge lost in a failed challenge
+    Penalty,
+    PledgeLocked,
+    PledgeReleased,
+    // DMC the stake of the miner grew by without new weight
+    StakeReward,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub time: u64,
+    pub order_id: String,
+    pub kind: LedgerEntryKind,
+    pub amount: f64,
+    pub memo: String,
+}
+
+// what the ledger saw of an order last time, entries come from the changes
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderSnapshot {
+    pub state: u8,
+    pub user_pledge: f64,
+    pub lock_pledge: f64,
+    pub miner_pledge: u64,
+    pub challenge_times: u64,
+    pub challenge_state: u32,
+}
+
+impl OrderSnapshot {
+    pub fn new(order: &DMCOrder, challenge: Option<&DMCChallengeInfo>) -> BuckyResult<Self> {
+        Ok(Self {
+            state: order.state,
+            user_pledge: parse_asset(&order.user_pledge)?,
+            lock_pledge: parse_asset(&order.lock_pledge)?,
+            miner_pledge: order.miner_pledge.get_amount()?,
+            challenge_times: challenge.map(|v| v.challenge_times).unwrap_or(0),
+            challenge_state: challenge.map(|v| v.state).unwrap_or(DMCChallengeState::ChallengePrepare as u32),
+        })
+    }
+
+    // the order row is gone from chain, whatever was locked is back
+    pub fn closed(&self) -> Self {
+        Self {
+            state: DMCOrderState::OrderStateEnd as u8,
+            lock_pledge: 0f64,
+            miner_pledge: 0,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.state == DMCOrderState::OrderStateEnd as u8
+    }
+}
+
+// history before the first snapshot of an order is unknown, only its pledge is recorded then
+pub fn diff_order(order_id: &str, prev: Option<&OrderSnapshot>, cur: &OrderSnapshot, now: u64) -> Vec<LedgerEntry> {
+    let mut entries = Vec::new();
+    let mut push = |kind: LedgerEntryKind, amount: f64, memo: String| {
+        entries.push(LedgerEntry {
+            time: now,
+            order_id: order_id.to_string(),
+            kind,
+            amount,
+            memo
+        });
+    };
+
+    let prev = match prev {
+        Some(prev) => prev,
+        None => {
+            if cur.lock_pledge > 0f64 {
+                push(LedgerEntryKind::PledgeLocked, cur.lock_pledge, "first seen".to_string());
+            }
+            return entries;
+        }
+    };
+
+    // the user pledge goes down by the price every settlement and up when the user renews
+    if cur.user_pledge < prev.user_pledge {
+        push(LedgerEntryKind::Income, prev.user_pledge - cur.user_pledge, "".to_string());
+    }
+
+    let penalized = is_penalty(cur.challenge_state)
+        && (!is_penalty(prev.challenge_state) || cur.challenge_times != prev.challenge_times);
+    let memo = if penalized {
+        format!("challenge {} state {}", cur.challenge_times, cur.challenge_state)
+    } else {
+        "".to_string()
+    };
+    if cur.lock_pledge > prev.lock_pledge {
+        push(LedgerEntryKind::PledgeLocked, cur.lock_pledge - prev.lock_pledge, memo);
+    } else if cur.lock_pledge < prev.lock_pledge {
+        let kind = if penalized { LedgerEntryKind::Penalty } else { LedgerEntryKind::PledgeReleased };
+        push(kind, prev.lock_pledge - cur.lock_pledge, memo);
+    } else if penalized {
+        push(LedgerEntryKind::Penalty, 0f64, memo);
+    }
+    entries
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EarningsPeriod {
+    Daily,
+    Monthly,
+    Order,
+}
+
+impl EarningsPeriod {
+    pub fn parse(period: &str) -> BuckyResult<Self> {
+        match period {
+            "daily" => Ok(Self::Daily),
+            "monthly" => Ok(Self::Monthly),
+            "order" => Ok(Self::Order),
+            _ => Err(cyfs_err!(BuckyErrorCode::InvalidParam, "unknown period {}, expect daily, monthly or order", period))
+        }
+    }
+
+    fn key(&self, entry: &LedgerEntry) -> String {
+        let date = time_point_sec_to_date(entry.time as i64);
+        match self {
+            Self::Daily => date[..10].to_string(),
+            Self::Monthly => date[..7].to_string(),
+            Self::Order => entry.order_id.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EarningsRow {
+    pub key: String,
+    pub income: f64,
+    pub stake_reward: f64,
+    pub penalty: f64,
+    pub pledge_locked: f64,
+    pub pledge_released: f64,
+    pub net: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EarningsReport {
+    pub rows: Vec<EarningsRow>,
+    // pledge of the orders not ended yet
+    pub locked_dmc: f64,
+    pub locked_pst: u64,
+    pub staked: f64,
+    pub stake_rate: String,
+    pub updated_at: u64,
+}
+
+impl EarningsReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = "key,income,stake_reward,penalty,pledge_locked,pledge_released,net\n".to_string();
+        for row in self.rows.iter() {
+            csv.push_str(format!("{},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4}\n",
+                                 row.key, row.income, row.stake_reward, row.penalty, row.pledge_locked, row.pledge_released, row.net).as_str());
+        }
+        csv
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LedgerState {
+    pub orders: HashMap<String, OrderSnapshot>,
+    pub entries: Vec<LedgerEntry>,
+    pub staked: f64,
+    // total weight of the stake when staked was read
+    #[serde(default)]
+    pub stake_weight: f64,
+    pub stake_rate: String,
+    pub updated_at: u64,
+}
+
+impl LedgerState {
+    pub fn update_order(&mut self, order_id: &str, cur: OrderSnapshot, now: u64) {
+        let mut entries = diff_order(order_id, self.orders.get(order_id), &cur, now);
+        self.entries.append(&mut entries);
+        self.orders.insert(order_id.to_string(), cur);
+    }
+
+    // staking and unstaking move the staked DMC and the weight together, rewards only raise the DMC of each weight
+    pub fn update_stake(&mut self, staked: f64, total_weight: f64, stake_rate: String, now: u64) {
+        if self.stake_weight > 0f64 && total_weight > 0f64 {
+            let reward = staked - self.staked / self.stake_weight * total_weight;
+            // below the precision of the asset
+            if reward >= 0.0001 {
+                self.entries.push(LedgerEntry {
+                    time: now,
+                    order_id: STAKE_LEDGER_ID.to_string(),
+                    kind: LedgerEntryKind::StakeReward,
+                    amount: reward,
+                    memo: format!("rate {}", stake_rate)
+                });
+            }
+        }
+        self.staked = staked;
+        self.stake_weight = total_weight;
+        self.stake_rate = stake_rate;
+    }
+
+    // the entries are saved on every sync, old ones are merged so the state doesn't keep growing.
+    // merged entries stay in their day and month, so the reports sum to the same
+    pub fn compact(&mut self, now: u64) {
+        let mut entries: Vec<LedgerEntry> = Vec::new();
+        let mut merged: HashMap<(String, String, LedgerEntryKind), usize> = HashMap::new();
+        for entry in self.entries.drain(..) {
+            let age = now.saturating_sub(entry.time);
+            let period = if age > LEDGER_MONTHLY_AFTER {
+                EarningsPeriod::Monthly
+            } else if age > LEDGER_DAILY_AFTER {
+                EarningsPeriod::Daily
+            } else {
+                entries.push(entry);
+                continue;
+            };
+            let key = (period.key(&entry), entry.order_id.clone(), entry.kind);
+            match merged.get(&key) {
+                Some(index) => {
+                    let to = &mut entries[*index];
+                    to.amount += entry.amount;
+                    to.memo = "".to_string();
+                }
+                None => {
+                    merged.insert(key, entries.len());
+                    entries.push(entry);
+                }
+            }
+        }
+        self.entries = entries;
+    }
+
+    pub fn report(&self, period: EarningsPeriod) -> EarningsReport {
+        let mut rows: BTreeMap<String, EarningsRow> = BTreeMap::new();
+        for entry in self.entries.iter() {
+            let key = period.key(entry);
+            let row = rows.entry(key.clone()).or_insert_with(|| EarningsRow {
+                key,
+                ..Default::default()
+            });
+            match entry.kind {
+                LedgerEntryKind::Income => row.income += entry.amount,
+                LedgerEntryKind::Penalty => row.penalty += entry.amount,
+                LedgerEntryKind::PledgeLocked => row.pledge_locked += entry.amount,
+                LedgerEntryKind::PledgeReleased => row.pledge_released += entry.amount,
+                LedgerEntryKind::StakeReward => row.stake_reward += entry.amount,
+            }
+            row.net = row.income + row.stake_reward - row.penalty;
+        }
+
+        let active = self.orders.values().filter(|order| !order.is_end());
+        EarningsReport {
+            rows: rows.into_values().collect(),
+            locked_dmc: active.clone().map(|order| order.lock_pledge).sum(),
+            locked_pst: active.map(|order| order.miner_pledge).sum(),
+            staked: self.staked,
+            stake_rate: self.stake_rate.clone(),
+            updated_at: self.updated_at
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_earnings {
+    use crate::*;
+
+    fn snapshot(user_pledge: f64, lock_pledge: f64, challenge_times: u64, challenge_state: DMCChallengeState) -> OrderSnapshot {
+        OrderSnapshot {
+            state: DMCOrderState::OrderStateDeliver as u8,
+            user_pledge,
+            lock_pledge,
+            miner_pledge: 10,
+            challenge_times,
+            challenge_state: challenge_state as u32,
+        }
+    }
+
+    #[test]
+    fn test_diff_order() {
+        let first = snapshot(100f64, 20f64, 0, DMCChallengeState::ChallengeConsistent);
+        let entries = diff_order("1", None, &first, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, LedgerEntryKind::PledgeLocked);
+
+        // one settlement paid
+        let settled = snapshot(90f64, 20f64, 0, DMCChallengeState::ChallengeConsistent);
+        let entries = diff_order("1", Some(&first), &settled, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, LedgerEntryKind::Income);
+        assert_eq!(entries[0].amount, 10f64);
+
+        // a failed challenge takes part of the lock pledge
+        let penalized = snapshot(90f64, 15f64, 1, DMCChallengeState::ChallengeTimeout);
+        let entries = diff_order("1", Some(&settled), &penalized, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, LedgerEntryKind::Penalty);
+        assert_eq!(entries[0].amount, 5f64);
+
+        // nothing changed, no entry again for the same challenge
+        assert!(diff_order("1", Some(&penalized), &penalized, 0).is_empty());
+
+        let entries = diff_order("1", Some(&penalized), &penalized.closed(), 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, LedgerEntryKind::PledgeReleased);
+        assert_eq!(entries[0].amount, 15f64);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut state = LedgerState::default();
+        state.update_order("1", snapshot(100f64, 20f64, 0, DMCChallengeState::ChallengeConsistent), 0);
+        state.update_order("1", snapshot(90f64, 20f64, 0, DMCChallengeState::ChallengeConsistent), 3600);
+        state.update_order("2", snapshot(50f64, 10f64, 0, DMCChallengeState::ChallengeConsistent), 24 * 3600);
+        state.update_order("2", snapshot(40f64, 8f64, 1, DMCChallengeState::ChallengeArbitrationMinerPay), 24 * 3600);
+
+        let daily = state.report(EarningsPeriod::Daily);
+        assert_eq!(daily.rows.len(), 2);
+        assert_eq!(daily.rows[0].key.as_str(), "1970-01-01");
+        assert_eq!(daily.rows[0].income, 10f64);
+        assert_eq!(daily.rows[1].penalty, 2f64);
+        assert_eq!(daily.rows[1].net, 8f64);
+        assert_eq!(daily.locked_dmc, 28f64);
+        assert_eq!(daily.locked_pst, 20);
+
+        let monthly = state.report(EarningsPeriod::Monthly);
+        assert_eq!(monthly.rows.len(), 1);
+        assert_eq!(monthly.rows[0].key.as_str(), "1970-01");
+        assert_eq!(monthly.rows[0].net, 18f64);
+
+        let order = state.report(EarningsPeriod::Order);
+        assert_eq!(order.rows.len(), 2);
+        assert_eq!(order.to_csv().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_stake_reward() {
+        let mut state = LedgerState::default();
+        // the first read has nothing to compare with
+        state.update_stake(100f64, 100f64, "0.1".to_string(), 0);
+        assert!(state.entries.is_empty());
+
+        // staking more adds weight at the same DMC per weight
+        state.update_stake(150f64, 150f64, "0.1".to_string(), 3600);
+        assert!(state.entries.is_empty());
+
+        // rewards raise the DMC without weight
+        state.update_stake(165f64, 150f64, "0.1".to_string(), 7200);
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entries[0].kind, LedgerEntryKind::StakeReward);
+        assert_eq!(state.entries[0].order_id.as_str(), STAKE_LEDGER_ID);
+        assert!((state.entries[0].amount - 15f64).abs() < 1e-9);
+
+        // unstaking half takes DMC and weight out together
+        state.update_stake(82.5f64, 75f64, "0.1".to_string(), 10800);
+        assert_eq!(state.entries.len(), 1);
+
+        let daily = state.report(EarningsPeriod::Daily);
+        assert_eq!(daily.rows.len(), 1);
+        assert!((daily.rows[0].stake_reward - 15f64).abs() < 1e-9);
+        assert!((daily.rows[0].net - 15f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut state = LedgerState::default();
+        let day = 24 * 3600;
+        // two settlements a day for 400 days
+        for i in 0..800u64 {
+            state.update_order("1", snapshot(10000f64 - i as f64, 20f64, 0, DMCChallengeState::ChallengeConsistent), i * day / 2);
+        }
+        let now = 400 * day;
+        let daily = state.report(EarningsPeriod::Daily);
+        let monthly = state.report(EarningsPeriod::Monthly);
+
+        assert_eq!(state.entries.len(), 800);
+        state.compact(now);
+        // the first 34 days by month: the first pledge, january and february income,
+        // days 34 to 368 by day, the last 31 days untouched
+        assert_eq!(state.entries.len(), 3 + 335 + 62);
+        let recent = state.entries.iter().filter(|entry| entry.time + 31 * day >= now).count();
+        assert_eq!(recent, 62);
+
+        let compacted = state.report(EarningsPeriod::Monthly);
+        assert_eq!(compacted.rows.len(), monthly.rows.len());
+        for (row, expect) in compacted.rows.iter().zip(monthly.rows.iter()) {
+            assert_eq!(row.key, expect.key);
+            assert_eq!(row.income, expect.income);
+        }
+        let compacted_daily = state.report(EarningsPeriod::Daily);
+        assert_eq!(compacted_daily.rows.last().unwrap().income, daily.rows.last().unwrap().income);
+
+        // compacting again changes nothing
+        let len = state.entries.len();
+        state.compact(now);
+        assert_eq!(state.entries.len(), len);
+    }
+}
@@ -0,0 +1,238 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use aes::Aes256;
+use block_modes::{BlockMode, Cbc};
+use block_modes::block_padding::Pkcs7;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde::{Serialize, Deserialize};
+use crate::*;
+
+type Aes256Cbc = Cbc<Aes256, Pkcs7>;
+
+// version 2 derives the key with PBKDF2-HMAC-SHA256
+const KEYSTORE_VERSION: u32 = 2;
+const KDF_PBKDF2_ROUNDS: u32 = 600_000;
+
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> Vec<u8> {
+    let mut key = vec![0u8; 32];
+    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(passphrase.as_bytes(), salt, rounds, key.as_mut_slice());
+    key
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedDMCKey {
+    pub version: u32,
+    pub public_key: String,
+    pub kdf_rounds: u32,
+    pub salt: String,
+    pub iv: String,
+    pub cipher_text: String,
+}
+
+impl EncryptedDMCKey {
+    pub fn encrypt(private_key: &DMCPrivateKey, passphrase: &str) -> BuckyResult<Self> {
+        Self::encrypt_with_rounds(private_key, passphrase, KDF_PBKDF2_ROUNDS)
+    }
+
+    fn encrypt_with_rounds(private_key: &DMCPrivateKey, passphrase: &str, kdf_rounds: u32) -> BuckyResult<Self> {
+        let mut salt = [0u8; 16];
+        random_data(&mut salt);
+        let mut iv = [0u8; 16];
+        random_data(&mut iv);
+
+        let key = derive_key(passphrase, &salt, kdf_rounds);
+        let cipher = Aes256Cbc::new_from_slices(key.as_slice(), &iv).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::CryptoError, "create cipher failed {:?}", e)
+        })?;
+        let cipher_text = cipher.encrypt_vec(private_key.to_legacy_string()?.as_bytes());
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            public_key: private_key.get_public_key().to_legacy_string()?,
+            kdf_rounds,
+            salt: hex::encode(salt),
+            iv: hex::encode(iv),
+            cipher_text: hex::encode(cipher_text)
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> BuckyResult<DMCPrivateKey> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(cyfs_err!(BuckyErrorCode::NotSupport, "keystore version {} of key {} isn't supported, import the key again", self.version, self.public_key.as_str()));
+        }
+        let salt = hex::decode(self.salt.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "decode salt {} failed {}", self.salt.as_str(), e)
+        })?;
+        let iv = hex::decode(self.iv.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "decode iv {} failed {}", self.iv.as_str(), e)
+        })?;
+        let cipher_text = hex::decode(self.cipher_text.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "decode cipher text failed {}", e)
+        })?;
+
+        let key = derive_key(passphrase, salt.as_slice(), self.kdf_rounds);
+        let cipher = Aes256Cbc::new_from_slices(key.as_slice(), iv.as_slice()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::CryptoError, "create cipher failed {:?}", e)
+        })?;
+        let plain = cipher.decrypt_vec(cipher_text.as_slice()).map_err(|_| {
+            cyfs_err!(BuckyErrorCode::CryptoError, "decrypt key {} failed, wrong passphrase?", self.public_key.as_str())
+        })?;
+        let plain = String::from_utf8(plain).map_err(|_| {
+            cyfs_err!(BuckyErrorCode::CryptoError, "decrypt key {} failed, wrong passphrase?", self.public_key.as_str())
+        })?;
+        let private_key = DMCPrivateKey::from_str(plain.as_str())?;
+        if private_key.get_public_key().to_legacy_string()? != self.public_key {
+            return Err(cyfs_err!(BuckyErrorCode::CryptoError, "decrypt key {} failed, wrong passphrase?", self.public_key.as_str()));
+        }
+        Ok(private_key)
+    }
+
+    pub fn load(path: &Path) -> BuckyResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", path.display(), e)
+        })?;
+        serde_json::from_str(content.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "parse {} failed {}", path.display(), e)
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> BuckyResult<()> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode key {} failed {}", self.public_key.as_str(), e)
+        })?;
+        write_private_file(path, content.as_bytes())
+    }
+}
+
+// key files are readable by the owner only, also when an existing file is overwritten
+fn write_private_file(path: &Path, content: &[u8]) -> BuckyResult<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "open {} failed {}", path.display(), e)
+    })?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "set permissions of {} failed {}", path.display(), e)
+        })?;
+    }
+    file.write_all(content).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "write {} failed {}", path.display(), e)
+    })
+}
+
+pub struct KeystoreSignatureProvider {
+    provider: SimpleSignatureProvider,
+}
+
+impl KeystoreSignatureProvider {
+    pub fn new(key_files: Vec<PathBuf>, passphrase: &str) -> BuckyResult<Self> {
+        let mut keys = Vec::new();
+        for path in key_files.iter() {
+            keys.push(EncryptedDMCKey::load(path.as_path())?.decrypt(passphrase)?);
+        }
+        Ok(Self {
+            provider: SimpleSignatureProvider::from_keys(keys)?
+        })
+    }
+
+    pub fn from_key_store(key_store: &DMCKeyStore, aliases: Vec<String>, passphrase: &str) -> BuckyResult<Self> {
+        let mut keys = Vec::new();
+        for alias in aliases.iter() {
+            keys.push(key_store.load(alias.as_str(), passphrase)?);
+        }
+        Ok(Self {
+            provider: SimpleSignatureProvider::from_keys(keys)?
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SignatureProvider for KeystoreSignatureProvider {
+    fn get_available_keys(&self) -> &Vec<String> {
+        self.provider.get_available_keys()
+    }
+
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
+        self.provider.sign(args).await
+    }
+}
+
+#[cfg(test)]
+mod test_keystore {
+    use cyfs_base::BuckyErrorCode;
+    use crate::*;
+    use super::derive_key;
+
+    fn encrypted(private_key: &DMCPrivateKey, passphrase: &str) -> EncryptedDMCKey {
+        // a few rounds keep the tests fast, decrypt reads them from the key
+        EncryptedDMCKey::encrypt_with_rounds(private_key, passphrase, 16).unwrap()
+    }
+
+    #[test]
+    fn test_derive_key() {
+        // the common PBKDF2-HMAC-SHA256 vectors
+        assert_eq!(hex::encode(derive_key("password", b"salt", 1)), "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b");
+        assert_eq!(hex::encode(derive_key("password", b"salt", 4096)), "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let private_key = DMCPrivateKey::gen_key();
+        let key = encrypted(&private_key, "passphrase");
+        assert_eq!(key.version, 2);
+        assert_eq!(key.public_key, private_key.get_public_key().to_legacy_string().unwrap());
+        let decrypted = key.decrypt("passphrase").unwrap();
+        assert_eq!(decrypted.to_legacy_string().unwrap(), private_key.to_legacy_string().unwrap());
+
+        // a fresh salt and iv every time
+        let other = encrypted(&private_key, "passphrase");
+        assert_ne!(key.salt, other.salt);
+        assert_ne!(key.cipher_text, other.cipher_text);
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let key = encrypted(&DMCPrivateKey::gen_key(), "passphrase");
+        for passphrase in ["", "Passphrase", "passphrase "].iter() {
+            assert_eq!(key.decrypt(passphrase).unwrap_err().code(), BuckyErrorCode::CryptoError);
+        }
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let mut key = encrypted(&DMCPrivateKey::gen_key(), "passphrase");
+        key.version = 1;
+        assert_eq!(key.decrypt("passphrase").unwrap_err().code(), BuckyErrorCode::NotSupport);
+        key.version = 3;
+        assert_eq!(key.decrypt("passphrase").unwrap_err().code(), BuckyErrorCode::NotSupport);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("dmc-keystore-test-{}", std::process::id()));
+        let key_store = DMCKeyStore::open(dir.as_path()).unwrap();
+        assert_eq!(std::fs::metadata(dir.as_path()).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let private_key = DMCPrivateKey::gen_key();
+        let path = key_store.key_path("test");
+        std::fs::write(path.as_path(), "").unwrap();
+        std::fs::set_permissions(path.as_path(), std::fs::Permissions::from_mode(0o644)).unwrap();
+        encrypted(&private_key, "passphrase").save(path.as_path()).unwrap();
+        assert_eq!(std::fs::metadata(path.as_path()).unwrap().permissions().mode() & 0o777, 0o600);
+        let loaded = EncryptedDMCKey::load(path.as_path()).unwrap().decrypt("passphrase").unwrap();
+        assert_eq!(loaded.to_legacy_string().unwrap(), private_key.to_legacy_string().unwrap());
+
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+}
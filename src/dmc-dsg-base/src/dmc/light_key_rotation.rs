@@ -0,0 +1,260 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use cyfs_base::*;
+use crate::*;
+
+// the light permission of the miner account on chain
+#[async_trait::async_trait]
+pub trait LightKeyChain: Send + Sync {
+    async fn get_light_keys(&self) -> BuckyResult<Vec<String>>;
+    // returns once the chain shows the keys
+    async fn set_light_keys(&self, keys: Vec<String>) -> BuckyResult<()>;
+}
+
+// the running miner, it signs with the light key it's given
+#[async_trait::async_trait]
+pub trait LightKeyMiner: Send + Sync {
+    async fn set_light_key(&self, dmc_account: &str, light_key: &DMCPrivateKey) -> BuckyResult<()>;
+    // the public key the miner signs with
+    async fn get_light_key(&self, dmc_account: &str) -> BuckyResult<String>;
+}
+
+pub enum RotateStep {
+    // the keys on chain once the new key is added
+    KeyAdded(Vec<String>),
+    // the public key the miner confirmed
+    MinerSwitched(String),
+    // the keys on chain once the old keys are dropped
+    OldKeysDropped(Vec<String>),
+}
+
+pub struct RotateProgress {
+    pub tries: usize,
+    pub interval: Duration,
+    pub on_step: Box<dyn Fn(RotateStep) + Send + Sync>,
+}
+
+impl Default for RotateProgress {
+    fn default() -> Self {
+        Self {
+            tries: 60,
+            interval: Duration::from_secs(10),
+            on_step: Box::new(|_| {}),
+        }
+    }
+}
+
+// rotates the light key without a window where the miner can't sign:
+// add the new key next to the old ones, switch the miner and wait until it confirms, then drop the old keys.
+// an interrupted rotation is continued by running it again with the same new key
+pub async fn rotate_light_key<CHAIN: LightKeyChain, MINER: LightKeyMiner>(
+    chain: &CHAIN,
+    miner: &MINER,
+    dmc_account: &str,
+    new_private_key: &DMCPrivateKey,
+    progress: RotateProgress) -> BuckyResult<()> {
+    let new_key = new_private_key.get_public_key().to_legacy_string()?;
+
+    let chain_keys = chain.get_light_keys().await?;
+    if !chain_keys.contains(&new_key) {
+        let mut keys = chain_keys.clone();
+        keys.push(new_key.clone());
+        chain.set_light_keys(keys).await?;
+    }
+    (progress.on_step)(RotateStep::KeyAdded(chain.get_light_keys().await?));
+
+    miner.set_light_key(dmc_account, new_private_key).await?;
+    let mut confirmed = false;
+    for _ in 0..progress.tries {
+        match miner.get_light_key(dmc_account).await {
+            Ok(key) if key == new_key => {
+                confirmed = true;
+                break;
+            }
+            Ok(key) => {
+                log::info!("miner still signs with {}", key);
+            }
+            Err(e) => {
+                log::error!("get miner light key err {}", e);
+            }
+        }
+        async_std::task::sleep(progress.interval).await;
+    }
+    if !confirmed {
+        return Err(cyfs_err!(BuckyErrorCode::Timeout, "miner didn't switch to light key {}, the old key is kept on chain", new_key));
+    }
+    (progress.on_step)(RotateStep::MinerSwitched(new_key.clone()));
+
+    chain.set_light_keys(vec![new_key]).await?;
+    (progress.on_step)(RotateStep::OldKeysDropped(chain.get_light_keys().await?));
+    Ok(())
+}
+
+// the service holding the light key and signing with it
+#[async_trait::async_trait]
+pub trait LightKeySigner: Send + Sync {
+    async fn set_dmc_account(&self, req: &SetDMCAccount) -> BuckyResult<()>;
+}
+
+// the miner side of SetDMCAccount and GetDMCKey: the key is handed to the signer,
+// and only reported once the signer took it
+pub struct LightKeyHandler<SIGNER: LightKeySigner> {
+    signer: SIGNER,
+    // account and public key the signer signs with
+    current: Mutex<Option<(String, String)>>,
+}
+
+impl<SIGNER: LightKeySigner> LightKeyHandler<SIGNER> {
+    pub fn new(signer: SIGNER, current: Option<(String, String)>) -> Self {
+        Self {
+            signer,
+            current: Mutex::new(current)
+        }
+    }
+
+    // returns the account and public key to persist
+    pub async fn on_set_dmc_account(&self, req: &SetDMCAccount) -> BuckyResult<(String, String)> {
+        let public_key = DMCPrivateKey::from_str(req.dmc_key.as_str())?.get_public_key().to_legacy_string()?;
+        self.signer.set_dmc_account(req).await?;
+        let current = (req.dmc_account.clone(), public_key);
+        *self.current.lock().unwrap() = Some(current.clone());
+        Ok(current)
+    }
+
+    pub fn on_get_dmc_key(&self, dmc_account: &str) -> BuckyResult<String> {
+        match self.current.lock().unwrap().as_ref() {
+            Some((account, public_key)) if account.as_str() == dmc_account => Ok(public_key.clone()),
+            _ => Err(cyfs_err!(BuckyErrorCode::NotFound, "no light key of {} was set through the miner", dmc_account))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_light_key_rotation {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use cyfs_base::*;
+    use crate::*;
+
+    struct MemChain {
+        keys: Mutex<Vec<String>>,
+        history: Mutex<Vec<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LightKeyChain for Arc<MemChain> {
+        async fn get_light_keys(&self) -> BuckyResult<Vec<String>> {
+            Ok(self.keys.lock().unwrap().clone())
+        }
+
+        async fn set_light_keys(&self, keys: Vec<String>) -> BuckyResult<()> {
+            self.history.lock().unwrap().push(keys.clone());
+            *self.keys.lock().unwrap() = keys;
+            Ok(())
+        }
+    }
+
+    struct MemSigner {
+        fail: bool,
+        keys: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LightKeySigner for MemSigner {
+        async fn set_dmc_account(&self, req: &SetDMCAccount) -> BuckyResult<()> {
+            if self.fail {
+                return Err(cyfs_err!(BuckyErrorCode::Failed, "signer is down"));
+            }
+            self.keys.lock().unwrap().push(req.dmc_key.clone());
+            Ok(())
+        }
+    }
+
+    // the cli talks to the handler of the miner service
+    #[async_trait::async_trait]
+    impl LightKeyMiner for LightKeyHandler<MemSigner> {
+        async fn set_light_key(&self, dmc_account: &str, light_key: &DMCPrivateKey) -> BuckyResult<()> {
+            self.on_set_dmc_account(&SetDMCAccount {
+                dmc_account: dmc_account.to_string(),
+                dmc_key: light_key.to_legacy_string()?
+            }).await.map(|_| ())
+        }
+
+        async fn get_light_key(&self, dmc_account: &str) -> BuckyResult<String> {
+            self.on_get_dmc_key(dmc_account)
+        }
+    }
+
+    fn progress() -> RotateProgress {
+        RotateProgress {
+            tries: 2,
+            interval: Duration::from_millis(1),
+            on_step: Box::new(|_| {}),
+        }
+    }
+
+    #[test]
+    fn test_rotate() {
+        async_std::task::block_on(async {
+            let old_key = DMCPrivateKey::gen_key();
+            let old_public = old_key.get_public_key().to_legacy_string().unwrap();
+            let new_key = DMCPrivateKey::gen_key();
+            let new_public = new_key.get_public_key().to_legacy_string().unwrap();
+            let chain = Arc::new(MemChain {
+                keys: Mutex::new(vec![old_public.clone()]),
+                history: Mutex::new(vec![])
+            });
+            let signed = Arc::new(Mutex::new(vec![]));
+            let miner = LightKeyHandler::new(MemSigner { fail: false, keys: signed.clone() }, Some(("miner".to_string(), old_public.clone())));
+            assert_eq!(miner.on_get_dmc_key("miner").unwrap(), old_public);
+            assert!(miner.on_get_dmc_key("other").is_err());
+
+            let steps = Arc::new(Mutex::new(vec![]));
+            let on_step = {
+                let steps = steps.clone();
+                move |step: RotateStep| {
+                    steps.lock().unwrap().push(match step {
+                        RotateStep::KeyAdded(keys) => keys,
+                        RotateStep::MinerSwitched(key) => vec![key],
+                        RotateStep::OldKeysDropped(keys) => keys,
+                    });
+                }
+            };
+            rotate_light_key(&chain, &miner, "miner", &new_key, RotateProgress {
+                on_step: Box::new(on_step),
+                ..progress()
+            }).await.unwrap();
+            assert_eq!(*steps.lock().unwrap(), vec![vec![old_public.clone(), new_public.clone()], vec![new_public.clone()], vec![new_public.clone()]]);
+            // both keys are on chain while the miner switches, then only the new one
+            assert_eq!(*chain.history.lock().unwrap(), vec![vec![old_public.clone(), new_public.clone()], vec![new_public.clone()]]);
+            assert_eq!(*signed.lock().unwrap(), vec![new_key.to_legacy_string().unwrap()]);
+            assert_eq!(miner.on_get_dmc_key("miner").unwrap(), new_public);
+
+            // running it again changes nothing on chain but the final set
+            rotate_light_key(&chain, &miner, "miner", &new_key, progress()).await.unwrap();
+            assert_eq!(chain.history.lock().unwrap().len(), 3);
+            assert_eq!(*chain.keys.lock().unwrap(), vec![new_public]);
+        })
+    }
+
+    #[test]
+    fn test_signer_refuses() {
+        async_std::task::block_on(async {
+            let old_key = DMCPrivateKey::gen_key();
+            let old_public = old_key.get_public_key().to_legacy_string().unwrap();
+            let new_key = DMCPrivateKey::gen_key();
+            let new_public = new_key.get_public_key().to_legacy_string().unwrap();
+            let chain = Arc::new(MemChain {
+                keys: Mutex::new(vec![old_public.clone()]),
+                history: Mutex::new(vec![])
+            });
+            let miner = LightKeyHandler::new(MemSigner { fail: true, keys: Arc::new(Mutex::new(vec![])) }, Some(("miner".to_string(), old_public.clone())));
+
+            assert!(rotate_light_key(&chain, &miner, "miner", &new_key, progress()).await.is_err());
+            // the old key stays, the miner can still sign
+            assert_eq!(*chain.keys.lock().unwrap(), vec![old_public.clone(), new_public]);
+            assert_eq!(miner.on_get_dmc_key("miner").unwrap(), old_public);
+        })
+    }
+}
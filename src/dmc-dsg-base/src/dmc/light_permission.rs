@@ -0,0 +1,164 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use cyfs_base::BuckyResult;
+use crate::*;
+
+pub const LIGHT_PERMISSION: &'static str = "light";
+pub const LIGHT_PERMISSION_PARENT: &'static str = "active";
+
+// actions the miner signs with the light key, create_light_auth links all of them to light
+pub const LIGHT_LINKED_ACTIONS: [(&'static str, &'static str); 6] = [
+    ("cyfsaddrinfo", "bind"),
+    ("dmc.token", "addmerkle"),
+    ("dmc.token", "reqchallenge"),
+    ("dmc.token", "anschallenge"),
+    ("dmc.token", "arbitration"),
+    ("dmc.token", "paychallenge"),
+];
+
+pub enum LightPermissionIssue {
+    MissingPermission,
+    WrongParent { parent: String },
+    WrongThreshold { threshold: u32 },
+    MissingKey { key: String },
+    KeyWeightTooLow { key: String, weight: u16 },
+    UnexpectedKey { key: String },
+    UnexpectedAccount { account: String },
+    UnexpectedWait { wait_sec: u32 },
+    LinkedActionsUnknown,
+    ActionNotLinked { code: String, ty: String },
+    ActionLinkedToOther { code: String, ty: String, permission: String },
+}
+
+impl LightPermissionIssue {
+    // the chain action that repairs the issue, all of them must be signed by <account>@active
+    pub fn fix(&self, account: &str) -> String {
+        match self {
+            Self::MissingPermission | Self::WrongParent { .. } => {
+                format!("run `dmc-dsg-miner-cli create_light_auth {} <owner_alias>`", account)
+            }
+            Self::WrongThreshold { .. } | Self::KeyWeightTooLow { .. } | Self::UnexpectedAccount { .. } | Self::UnexpectedWait { .. } => {
+                format!("updateauth account={} permission={} parent={} with threshold 1 and only the light key at weight 1", account, LIGHT_PERMISSION, LIGHT_PERMISSION_PARENT)
+            }
+            Self::MissingKey { key } => {
+                format!("run `dmc-dsg-miner-cli rotate_light_key {} <owner_alias>` or set_dmc_account with the key {} already on chain", account, key)
+            }
+            Self::UnexpectedKey { key } => {
+                format!("updateauth account={} permission={} parent={} without key {}", account, LIGHT_PERMISSION, LIGHT_PERMISSION_PARENT, key)
+            }
+            Self::LinkedActionsUnknown => {
+                format!("use a node reporting linked_actions in get_account, or check the links of {} manually", account)
+            }
+            Self::ActionNotLinked { code, ty } => {
+                format!("linkauth account={} code={} type={} requirement={}", account, code, ty, LIGHT_PERMISSION)
+            }
+            Self::ActionLinkedToOther { code, ty, .. } => {
+                format!("unlinkauth account={} code={} type={}, then linkauth account={} code={} type={} requirement={}",
+                        account, code, ty, account, code, ty, LIGHT_PERMISSION)
+            }
+        }
+    }
+}
+
+impl Display for LightPermissionIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPermission => write!(f, "permission {} not found", LIGHT_PERMISSION),
+            Self::WrongParent { parent } => write!(f, "permission {} has parent {}, expect {}", LIGHT_PERMISSION, parent, LIGHT_PERMISSION_PARENT),
+            Self::WrongThreshold { threshold } => write!(f, "permission {} has threshold {}, expect 1", LIGHT_PERMISSION, threshold),
+            Self::MissingKey { key } => write!(f, "light key {} isn't in permission {}", key, LIGHT_PERMISSION),
+            Self::KeyWeightTooLow { key, weight } => write!(f, "key {} has weight {}, it can't sign for {} alone", key, weight, LIGHT_PERMISSION),
+            Self::UnexpectedKey { key } => write!(f, "unknown key {} in permission {}", key, LIGHT_PERMISSION),
+            Self::UnexpectedAccount { account } => write!(f, "account {} can sign for permission {}", account, LIGHT_PERMISSION),
+            Self::UnexpectedWait { wait_sec } => write!(f, "permission {} has a wait of {} seconds", LIGHT_PERMISSION, wait_sec),
+            Self::LinkedActionsUnknown => write!(f, "the node doesn't report linked actions"),
+            Self::ActionNotLinked { code, ty } => write!(f, "{}::{} isn't linked to {}", code, ty, LIGHT_PERMISSION),
+            Self::ActionLinkedToOther { code, ty, permission } => write!(f, "{}::{} is linked to {}, expect {}", code, ty, permission, LIGHT_PERMISSION),
+        }
+    }
+}
+
+fn same_key(a: &str, b: &str) -> bool {
+    match (DMCPublicKey::from_str(a), DMCPublicKey::from_str(b)) {
+        (Ok(a), Ok(b)) => a.key.serialize_compressed() == b.key.serialize_compressed(),
+        _ => a == b
+    }
+}
+
+// compare the permissions of an account with what create_light_auth sets up,
+// light_key is checked when the expected key is known
+pub fn audit_light_permission(permissions: &Vec<Permission>, light_key: Option<&str>) -> BuckyResult<Vec<LightPermissionIssue>> {
+    let mut issues = Vec::new();
+    let light = permissions.iter().find(|perm| perm.perm_name.as_str() == LIGHT_PERMISSION);
+    if light.is_none() {
+        issues.push(LightPermissionIssue::MissingPermission);
+        return Ok(issues);
+    }
+    let light = light.unwrap();
+
+    if light.parent.as_str() != LIGHT_PERMISSION_PARENT {
+        issues.push(LightPermissionIssue::WrongParent { parent: light.parent.clone() });
+    }
+    let auth = &light.required_auth;
+    if auth.threshold != 1 {
+        issues.push(LightPermissionIssue::WrongThreshold { threshold: auth.threshold });
+    }
+    if let Some(light_key) = light_key {
+        match auth.keys.iter().find(|weight| same_key(weight.key.as_str(), light_key)) {
+            Some(weight) => {
+                if (weight.weight as u32) < auth.threshold {
+                    issues.push(LightPermissionIssue::KeyWeightTooLow { key: weight.key.clone(), weight: weight.weight });
+                }
+            }
+            None => {
+                issues.push(LightPermissionIssue::MissingKey { key: light_key.to_string() });
+            }
+        }
+        // a second key is only expected in the middle of rotate_light_key
+        for weight in auth.keys.iter() {
+            if !same_key(weight.key.as_str(), light_key) {
+                issues.push(LightPermissionIssue::UnexpectedKey { key: weight.key.clone() });
+            }
+        }
+    } else {
+        for weight in auth.keys.iter() {
+            if (weight.weight as u32) < auth.threshold {
+                issues.push(LightPermissionIssue::KeyWeightTooLow { key: weight.key.clone(), weight: weight.weight });
+            }
+        }
+    }
+    for account in auth.accounts.iter() {
+        issues.push(LightPermissionIssue::UnexpectedAccount {
+            account: format!("{}@{}", account.permission.actor, account.permission.permission)
+        });
+    }
+    for wait in auth.waits.iter() {
+        issues.push(LightPermissionIssue::UnexpectedWait { wait_sec: wait.wait_sec });
+    }
+
+    if permissions.iter().all(|perm| perm.linked_actions.is_none()) {
+        issues.push(LightPermissionIssue::LinkedActionsUnknown);
+        return Ok(issues);
+    }
+    for (code, ty) in LIGHT_LINKED_ACTIONS.iter() {
+        let linked = permissions.iter().find(|perm| {
+            perm.linked_actions.as_ref().map(|actions| actions.iter().any(|action| {
+                action.account.as_str() == *code && action.action.as_ref().map(|v| v.as_str() == *ty).unwrap_or(false)
+            })).unwrap_or(false)
+        });
+        match linked {
+            Some(perm) if perm.perm_name.as_str() == LIGHT_PERMISSION => {}
+            Some(perm) => {
+                issues.push(LightPermissionIssue::ActionLinkedToOther {
+                    code: code.to_string(),
+                    ty: ty.to_string(),
+                    permission: perm.perm_name.clone()
+                });
+            }
+            None => {
+                issues.push(LightPermissionIssue::ActionNotLinked { code: code.to_string(), ty: ty.to_string() });
+            }
+        }
+    }
+    Ok(issues)
+}
@@ -0,0 +1,90 @@
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use crate::*;
+
+// the hash of the merkle trees and challenges of a contract, its id is saved with the contract.
+// contracts without an id use sha256
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MerkleHashAlgorithm {
+    Sha256,
+}
+
+impl Default for MerkleHashAlgorithm {
+    fn default() -> Self {
+        Self::Sha256
+    }
+}
+
+impl MerkleHashAlgorithm {
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Sha256 => 0,
+        }
+    }
+
+    pub fn from_id(id: u32) -> BuckyResult<Self> {
+        match id {
+            0 => Ok(Self::Sha256),
+            _ => Err(cyfs_err!(BuckyErrorCode::NotSupport, "merkle hash algorithm {} isn't supported", id))
+        }
+    }
+
+    pub fn hash_data(&self, data: &[u8]) -> [u8;32] {
+        match self {
+            Self::Sha256 => Sha256Hasher::hash_data(data),
+        }
+    }
+
+    pub fn hash_pair(&self, left: &[u8], right: &[u8]) -> [u8;32] {
+        match self {
+            Self::Sha256 => Sha256Hasher::hash_pair(left, right),
+        }
+    }
+
+    // the answer to a challenge is hash(piece + nonce), the consumer posts the hash of it
+    pub fn challenge_hash(&self, piece: &[u8], nonce: &str) -> HashValue {
+        HashValue::from(&self.hash_data(vec![piece, nonce.as_bytes()].concat().as_slice()))
+    }
+}
+
+pub trait MerkleHasher: 'static + Send + Sync {
+    const ALGORITHM: MerkleHashAlgorithm;
+    fn hash_data(data: &[u8]) -> [u8;32];
+    fn hash_pair(left: &[u8], right: &[u8]) -> [u8;32];
+}
+
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    const ALGORITHM: MerkleHashAlgorithm = MerkleHashAlgorithm::Sha256;
+
+    fn hash_data(data: &[u8]) -> [u8;32] {
+        Sha256::digest(data).into()
+    }
+
+    fn hash_pair(left: &[u8], right: &[u8]) -> [u8;32] {
+        let mut sha256 = Sha256::new();
+        sha256.update(left);
+        sha256.update(right);
+        sha256.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod test_merkle_hash {
+    use cyfs_base::*;
+    use crate::*;
+
+    #[test]
+    fn test_sha256_default() {
+        let algorithm = MerkleHashAlgorithm::from_id(MerkleHashAlgorithm::default().id()).unwrap();
+        assert_eq!(algorithm, MerkleHashAlgorithm::Sha256);
+        assert!(MerkleHashAlgorithm::from_id(100).is_err());
+
+        // the same hashes as before the algorithm was recorded
+        let piece = vec![7u8; 1024];
+        assert_eq!(HashValue::from(&algorithm.hash_data(piece.as_slice())), hash_data(piece.as_slice()));
+        assert_eq!(algorithm.challenge_hash(piece.as_slice(), "nonce"), hash_data(vec![piece.as_slice(), &b"nonce"[..]].concat().as_slice()));
+    }
+}
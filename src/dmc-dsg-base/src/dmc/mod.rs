@@ -5,7 +5,18 @@ mod serial_buffer;
 mod serialize;
 mod dmc_api;
 mod signature_provider;
+mod keystore_signature_provider;
+mod remote_signature_provider;
+mod dmc_key_store;
 mod dmc_client;
+mod light_permission;
+mod light_key_rotation;
+mod dmc_tx_tracker;
+mod abi_serializer;
+mod offline_transaction;
+mod earnings;
+mod merkle_hash;
+mod proof_verifier;
 
 pub use dmc_rpc::*;
 pub use dmc_secret_key::*;
@@ -14,4 +25,15 @@ pub use serial_buffer::*;
 pub use serialize::*;
 pub use dmc_api::*;
 pub use signature_provider::*;
+pub use keystore_signature_provider::*;
+pub use remote_signature_provider::*;
+pub use dmc_key_store::*;
 pub use dmc_client::*;
+pub use light_permission::*;
+pub use light_key_rotation::*;
+pub use dmc_tx_tracker::*;
+pub use abi_serializer::*;
+pub use offline_transaction::*;
+pub use earnings::*;
+pub use merkle_hash::*;
+pub use proof_verifier::*;
@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::sync::Arc;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde::{Serialize, Deserialize};
+use crate::*;
+
+// the longest lifetime nodes accept, leaves time to carry the file to the offline machine and back
+pub const OFFLINE_TX_EXPIRE_SECONDS: i64 = 3600;
+
+fn decode_hex(name: &str, data: &str) -> BuckyResult<Vec<u8>> {
+    hex::decode(data).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "decode {} failed {}", name, e)
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OfflineTransactionAbi {
+    pub account_name: String,
+    pub abi: String,
+}
+
+// an unsigned transaction with everything the offline signer needs, binary fields are hex
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OfflineTransaction {
+    pub chain_id: String,
+    pub required_keys: Vec<String>,
+    pub serialized_transaction: String,
+    pub serialized_context_free_data: Option<String>,
+    pub abis: Vec<OfflineTransactionAbi>,
+    pub signatures: Vec<String>,
+}
+
+impl OfflineTransaction {
+    // fill tapos and collect the keys of the authorizations from chain, nothing is signed here
+    pub async fn build(server: &str, trans: Transaction) -> BuckyResult<Self> {
+        let rpc = Arc::new(DMCRpc::new(server));
+        let info = rpc.get_info().await?;
+
+        let mut required_keys: Vec<String> = Vec::new();
+        for action in trans.actions.iter() {
+            for auth in action.authorization.iter() {
+                let permissions = rpc.get_permissions(auth.actor.as_str()).await?;
+                let permission = permissions.iter().find(|perm| perm.perm_name == auth.permission).ok_or_else(|| {
+                    cyfs_err!(BuckyErrorCode::NotFound, "permission {}@{} not found", auth.actor.as_str(), auth.permission.as_str())
+                })?;
+                for weight in permission.required_auth.keys.iter() {
+                    if !required_keys.contains(&weight.key) {
+                        required_keys.push(weight.key.clone());
+                    }
+                }
+            }
+        }
+        if required_keys.is_empty() {
+            return Err(cyfs_err!(BuckyErrorCode::NotSupport, "the transaction can't be signed by keys only"));
+        }
+
+        let api = DMCApi::new(rpc, SimpleSignatureProvider::from_keys(vec![])?);
+        let abis = api.get_transaction_abis(&trans, false).await?;
+        let ret = api.transact(trans, TransactionConfig {
+            broadcast: Some(false),
+            sign: Some(false),
+            read_only_trx: None,
+            return_failure_traces: None,
+            required_keys: None,
+            compression: None,
+            blocks_behind: Some(3),
+            use_last_irreversible: None,
+            expire_seconds: Some(OFFLINE_TX_EXPIRE_SECONDS)
+        }).await?;
+        let args = match ret {
+            TransResult::PushTransactionArgs(args) => args,
+            _ => {
+                return Err(cyfs_err!(BuckyErrorCode::Failed, "unexpected transact result"));
+            }
+        };
+
+        let mut offline_abis: Vec<OfflineTransactionAbi> = Vec::new();
+        for abi in abis.into_iter() {
+            if offline_abis.iter().all(|item| item.account_name != abi.account_name) {
+                offline_abis.push(OfflineTransactionAbi {
+                    account_name: abi.account_name,
+                    abi: hex::encode(abi.abi)
+                });
+            }
+        }
+
+        Ok(Self {
+            chain_id: info.chain_id,
+            required_keys,
+            serialized_transaction: hex::encode(args.serialized_transaction),
+            serialized_context_free_data: args.serialized_context_free_data.map(|data| hex::encode(data)),
+            abis: offline_abis,
+            signatures: vec![]
+        })
+    }
+
+    pub fn load(path: &Path) -> BuckyResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", path.display(), e)
+        })?;
+        serde_json::from_str(content.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "parse {} failed {}", path.display(), e)
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> BuckyResult<()> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode transaction failed {}", e)
+        })?;
+        std::fs::write(path, content).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::IoError, "write {} failed {}", path.display(), e)
+        })
+    }
+
+    pub fn get_transaction(&self) -> BuckyResult<Transaction> {
+        let mut data = decode_hex("serialized_transaction", self.serialized_transaction.as_str())?;
+        let mut buf = SerialBuffer::new(&mut data);
+        Transaction::dmc_deserialize(&mut buf)
+    }
+
+    pub fn get_abi(&self, account_name: &str) -> BuckyResult<AbiDef> {
+        let abi = self.abis.iter().find(|abi| abi.account_name.as_str() == account_name).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "abi of {} not in transaction file", account_name)
+        })?;
+        let mut raw_abi = decode_hex("abi", abi.abi.as_str())?;
+        AbiDef::parse(&mut raw_abi)
+    }
+
+    // readable lines of the transaction, action data is decoded by the abis carried in the file
+    pub fn decode(&self) -> BuckyResult<Vec<String>> {
+        let trans = self.get_transaction()?;
+        let mut lines = vec![
+            format!("chain id: {}", self.chain_id.as_str()),
+            format!("expiration: {}", trans.expiration.as_str()),
+            format!("ref block: {} prefix {}", trans.ref_block_num, trans.ref_block_prefix),
+        ];
+        for action in trans.context_free_actions.iter().chain(trans.actions.iter()) {
+            let auths: Vec<String> = action.authorization.iter().map(|auth| format!("{}@{}", auth.actor, auth.permission)).collect();
+            lines.push(format!("action: {}::{} by {}", action.account.as_str(), action.name.as_str(), auths.join(",")));
+            let data = self.get_abi(action.account.as_str()).and_then(|abi| abi.decode_action_data(action.name.as_str(), action.data.as_slice()));
+            match data {
+                Ok(data) => lines.push(format!("  data: {}", data.to_string())),
+                Err(e) => lines.push(format!("  data: {} (decode failed {})", hex::encode(action.data.as_slice()), e)),
+            }
+        }
+        lines.push(format!("required keys: {}", self.required_keys.join(",")));
+        lines.push(format!("signatures: {}", self.signatures.len()));
+        Ok(lines)
+    }
+
+    fn sign_args(&self) -> BuckyResult<SignatureProviderArgs> {
+        let mut abis = Vec::new();
+        for abi in self.abis.iter() {
+            abis.push(BinaryAbi {
+                account_name: abi.account_name.clone(),
+                abi: decode_hex("abi", abi.abi.as_str())?
+            });
+        }
+        let serialized_context_free_data = match self.serialized_context_free_data.as_ref() {
+            Some(data) => Some(decode_hex("serialized_context_free_data", data.as_str())?),
+            None => None
+        };
+        Ok(SignatureProviderArgs {
+            chain_id: self.chain_id.clone(),
+            required_keys: self.required_keys.clone(),
+            serialized_transaction: decode_hex("serialized_transaction", self.serialized_transaction.as_str())?,
+            serialized_context_free_data,
+            abis
+        })
+    }
+
+    // add the signatures of the keys the provider holds, the file can go through several signers
+    pub async fn sign<T: SignatureProvider>(&mut self, provider: &T) -> BuckyResult<usize> {
+        let args = self.sign_args()?;
+        let serialized_transaction = args.serialized_transaction.clone();
+        let signed = provider.sign(args).await?;
+        if signed.serialized_transaction != serialized_transaction {
+            return Err(cyfs_err!(BuckyErrorCode::Failed, "signer changed the transaction"));
+        }
+        let mut count = 0;
+        for signature in signed.signatures.into_iter() {
+            if !self.signatures.contains(&signature) {
+                self.signatures.push(signature);
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Err(cyfs_err!(BuckyErrorCode::NotFound, "none of the required keys {} is available", self.required_keys.join(",")));
+        }
+        Ok(count)
+    }
+
+    pub fn to_push_args(&self) -> BuckyResult<PushTransactionArgs> {
+        if self.signatures.is_empty() {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "transaction isn't signed"));
+        }
+        let args = self.sign_args()?;
+        Ok(PushTransactionArgs {
+            signatures: self.signatures.clone(),
+            compression: None,
+            serialized_transaction: args.serialized_transaction,
+            serialized_context_free_data: args.serialized_context_free_data
+        })
+    }
+
+    pub async fn push(&self, server: &str) -> BuckyResult<TransactResult> {
+        let args = self.to_push_args()?;
+        DMCRpc::new(server).push_transaction(args).await
+    }
+}
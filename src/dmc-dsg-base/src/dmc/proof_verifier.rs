@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::str::FromStr;
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use sha2::Digest;
+use crate::*;
+
+// checks a piece proof with nothing but the proof and the challenge row on chain, no cyfs stack is needed
+
+pub const PROOF_PIECE_SIZE: u64 = 1024;
+
+// the data of the arbitration action, the piece and the siblings from the leaf up, all in hex
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArbitrationProof {
+    pub data: String,
+    pub cut_merkle: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct PieceProof {
+    pub piece_index: u64,
+    pub piece: Vec<u8>,
+    pub path_list: Vec<[u8;32]>,
+}
+
+pub fn piece_proof_root(algorithm: MerkleHashAlgorithm, piece_index: u64, piece: &[u8], path_list: &[[u8;32]]) -> [u8;32] {
+    let mut cur_node = algorithm.hash_data(piece);
+    let mut cur_index = piece_index;
+    for node in path_list.iter() {
+        cur_node = if cur_index % 2 != 0 {
+            algorithm.hash_pair(node, &cur_node)
+        } else {
+            algorithm.hash_pair(&cur_node, node)
+        };
+        cur_index = cur_index / 2;
+    }
+    cur_node
+}
+
+// siblings on the path of a tree with leaf_count leafs, an odd last node pairs with itself
+pub fn merkle_path_len(leaf_count: u64) -> usize {
+    let mut len = 0;
+    let mut nodes = leaf_count;
+    while nodes > 1 {
+        nodes = (nodes + 1) / 2;
+        len += 1;
+    }
+    len
+}
+
+impl PieceProof {
+    pub fn from_arbitration(data_id: u64, proof: &ArbitrationProof) -> BuckyResult<Self> {
+        let piece = hex::decode(proof.data.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "decode proof data err {}", e)
+        })?;
+        let mut path_list = Vec::new();
+        for hash in proof.cut_merkle.iter() {
+            let hash = HashValue::from_str(hash.as_str())?;
+            path_list.push(hash.as_slice().try_into().unwrap());
+        }
+        Ok(Self {
+            piece_index: data_id,
+            piece,
+            path_list
+        })
+    }
+
+    // the same layout as the raw encoded SinglePieceProof of the miner
+    pub fn decode_raw(buf: &[u8]) -> BuckyResult<Self> {
+        let (piece_index, buf) = u64::raw_decode(buf)?;
+        let (len, buf) = USize::raw_decode(buf)?;
+        if buf.len() < len.0 {
+            return Err(cyfs_err!(BuckyErrorCode::OutOfLimit, "proof piece len {} is out of limit", len.0));
+        }
+        let piece = buf[..len.0].to_vec();
+        let (len, buf) = USize::raw_decode(&buf[len.0..])?;
+        if buf.len() != len.0 * 32 {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "proof path len {} mismatch {} bytes", len.0, buf.len()));
+        }
+        let path_list = buf.chunks(32).map(|v| v.try_into().unwrap()).collect();
+        Ok(Self {
+            piece_index,
+            piece,
+            path_list
+        })
+    }
+
+    // a proof file is the json of the arbitration action or a raw encoded proof
+    pub fn decode(data_id: u64, buf: &[u8]) -> BuckyResult<Self> {
+        match serde_json::from_slice::<ArbitrationProof>(buf) {
+            Ok(proof) => Self::from_arbitration(data_id, &proof),
+            Err(_) => Self::decode_raw(buf)
+        }
+    }
+
+    pub fn root(&self, algorithm: MerkleHashAlgorithm) -> [u8;32] {
+        piece_proof_root(algorithm, self.piece_index, self.piece.as_slice(), self.path_list.as_slice())
+    }
+
+    pub fn verify(&self, algorithm: MerkleHashAlgorithm, root: &[u8;32]) -> bool {
+        &self.root(algorithm) == root
+    }
+}
+
+// pieces chosen by the seed, the prover can't pick them and the verifier can pick them again
+pub fn sample_piece_indices(piece_count: u64, sample_count: usize, seed: &[u8]) -> Vec<u64> {
+    let sample_count = std::cmp::min(sample_count as u64, piece_count) as usize;
+    let mut indices = BTreeSet::new();
+    let mut counter = 0u64;
+    while indices.len() < sample_count {
+        let mut sha256 = sha2::Sha256::new();
+        sha256.update(seed);
+        sha256.update(counter.to_be_bytes());
+        let hash: [u8;32] = sha256.finalize().into();
+        indices.insert(u64::from_be_bytes(hash[..8].try_into().unwrap()) % piece_count);
+        counter += 1;
+    }
+    indices.into_iter().collect()
+}
+
+// several pieces and the siblings that can't be computed from them, the same layout as the raw encoded MultiPieceProof of the miner
+#[derive(Clone)]
+pub struct PieceSetProof {
+    pub leaf_count: u64,
+    pub pieces: Vec<(u64, Vec<u8>)>,
+    pub hash_list: Vec<[u8;32]>,
+}
+
+impl PieceSetProof {
+    pub fn decode_raw(buf: &[u8]) -> BuckyResult<Self> {
+        let (leaf_count, buf) = u64::raw_decode(buf)?;
+        let (len, mut buf) = USize::raw_decode(buf)?;
+        let mut pieces = Vec::new();
+        for _ in 0..len.0 {
+            let (index, tmp) = u64::raw_decode(buf)?;
+            let (piece_len, tmp) = USize::raw_decode(tmp)?;
+            if tmp.len() < piece_len.0 {
+                return Err(cyfs_err!(BuckyErrorCode::OutOfLimit, "proof piece len {} is out of limit", piece_len.0));
+            }
+            pieces.push((index, tmp[..piece_len.0].to_vec()));
+            buf = &tmp[piece_len.0..];
+        }
+        let (len, buf) = USize::raw_decode(buf)?;
+        if buf.len() != len.0 * 32 {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidData, "proof hash list len {} mismatch {} bytes", len.0, buf.len()));
+        }
+        let hash_list = buf.chunks(32).map(|v| v.try_into().unwrap()).collect();
+        Ok(Self {
+            leaf_count,
+            pieces,
+            hash_list
+        })
+    }
+
+    // None if the pieces and the hash list don't make up one tree
+    pub fn root(&self, algorithm: MerkleHashAlgorithm) -> Option<[u8;32]> {
+        let mut nodes = BTreeMap::new();
+        for (index, piece) in self.pieces.iter() {
+            if *index >= self.leaf_count {
+                return None;
+            }
+            nodes.insert(*index, algorithm.hash_data(piece.as_slice()));
+        }
+        if nodes.is_empty() || nodes.len() != self.pieces.len() {
+            return None;
+        }
+
+        let mut hash_iter = self.hash_list.iter();
+        let mut layer_len = self.leaf_count;
+        while layer_len > 1 {
+            let mut next = BTreeMap::new();
+            for (index, node) in nodes.iter() {
+                if next.contains_key(&(index / 2)) {
+                    continue;
+                }
+                let sibling_index = index ^ 1;
+                let sibling = if sibling_index >= layer_len {
+                    node
+                } else if let Some(sibling) = nodes.get(&sibling_index) {
+                    sibling
+                } else {
+                    hash_iter.next()?
+                };
+                let hash = if index % 2 == 0 {
+                    algorithm.hash_pair(node, sibling)
+                } else {
+                    algorithm.hash_pair(sibling, node)
+                };
+                next.insert(index / 2, hash);
+            }
+            nodes = next;
+            layer_len = (layer_len + 1) / 2;
+        }
+        if hash_iter.next().is_some() {
+            return None;
+        }
+        nodes.remove(&0)
+    }
+
+    pub fn verify(&self, algorithm: MerkleHashAlgorithm, root: &[u8;32]) -> bool {
+        self.root(algorithm).as_ref() == Some(root)
+    }
+}
+
+pub struct ProofReport {
+    pub root: HashValue,
+    // the root is the merkle_root on chain
+    pub root_matched: bool,
+    // the piece is the challenged one and in the data_block_count pieces
+    pub index_matched: bool,
+    // the path is as long as a tree of data_block_count pieces
+    pub depth_matched: bool,
+    // the piece answers the hash_data and nonce of the consumer
+    pub challenge_matched: bool,
+}
+
+impl ProofReport {
+    pub fn is_valid(&self) -> bool {
+        self.root_matched && self.index_matched && self.depth_matched
+    }
+}
+
+pub fn verify_challenge_proof(challenge: &DMCChallengeInfo, proof: &PieceProof, algorithm: MerkleHashAlgorithm) -> BuckyResult<ProofReport> {
+    let merkle_root = HashValue::from_str(challenge.merkle_root.as_str())?;
+    let root = HashValue::from(&proof.root(algorithm));
+    let hash = algorithm.challenge_hash(proof.piece.as_slice(), challenge.nonce.as_str());
+    Ok(ProofReport {
+        root_matched: root == merkle_root,
+        root,
+        index_matched: proof.piece_index == challenge.data_id
+            && proof.piece_index < challenge.data_block_count
+            && proof.piece.len() as u64 <= PROOF_PIECE_SIZE,
+        depth_matched: proof.path_list.len() == merkle_path_len(challenge.data_block_count),
+        challenge_matched: HashValue::from(&algorithm.hash_data(hash.as_slice())).to_string() == challenge.hash_data,
+    })
+}
+
+// a piece set proof answers a challenge whose seed sampled sample_count pieces of the order
+pub fn verify_sampled_proof(merkle_root: &str, data_block_count: u64, proof: &PieceSetProof, algorithm: MerkleHashAlgorithm, sample_count: usize, seed: &[u8]) -> BuckyResult<ProofReport> {
+    let merkle_root = HashValue::from_str(merkle_root)?;
+    let root = proof.root(algorithm).map(|root| HashValue::from(&root));
+    let indices: Vec<u64> = proof.pieces.iter().map(|(index, _)| *index).collect();
+    Ok(ProofReport {
+        root_matched: root.as_ref() == Some(&merkle_root),
+        root: root.unwrap_or_default(),
+        index_matched: proof.pieces.iter().all(|(index, piece)| *index < data_block_count && piece.len() as u64 <= PROOF_PIECE_SIZE),
+        depth_matched: proof.leaf_count == data_block_count,
+        challenge_matched: data_block_count > 0 && indices == sample_piece_indices(data_block_count, sample_count, seed),
+    })
+}
+
+#[cfg(test)]
+mod test_proof_verifier {
+    use cyfs_base::*;
+    use sha2::{Digest, Sha256};
+    use crate::*;
+
+    fn hash_pair(left: &[u8;32], right: &[u8;32]) -> [u8;32] {
+        let mut sha256 = Sha256::new();
+        sha256.update(left);
+        sha256.update(right);
+        sha256.finalize().into()
+    }
+
+    #[test]
+    fn test_verify() {
+        let pieces: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i; PROOF_PIECE_SIZE as usize]).collect();
+        let leafs: Vec<[u8;32]> = pieces.iter().map(|v| Sha256::digest(v.as_slice()).into()).collect();
+        let left = hash_pair(&leafs[0], &leafs[1]);
+        let right = hash_pair(&leafs[2], &leafs[2]);
+        let root = hash_pair(&left, &right);
+
+        let nonce = "nonce".to_string();
+        let hash = hash_data(vec![pieces[2].as_slice(), nonce.as_bytes()].concat().as_slice());
+        let challenge = DMCChallengeInfo {
+            order_id: 1,
+            pre_merkle_root: HashValue::from(&root).to_string(),
+            pre_data_block_count: 3,
+            merkle_root: HashValue::from(&root).to_string(),
+            data_block_count: 3,
+            merkle_submitter: "miner".to_string(),
+            data_id: 2,
+            hash_data: hash_data(hash.as_slice()).to_string(),
+            challenge_times: 1,
+            nonce,
+            state: DMCChallengeState::ChallengeRequest as u32,
+            challenge_date: "".to_string(),
+        };
+
+        let arbitration = ArbitrationProof {
+            data: hex::encode(pieces[2].as_slice()),
+            cut_merkle: vec![HashValue::from(&leafs[2]).to_string(), HashValue::from(&left).to_string()],
+        };
+        let proof = PieceProof::decode(2, serde_json::to_vec(&arbitration).unwrap().as_slice()).unwrap();
+        assert!(proof.verify(MerkleHashAlgorithm::Sha256, &root));
+        let report = verify_challenge_proof(&challenge, &proof, MerkleHashAlgorithm::Sha256).unwrap();
+        assert!(report.is_valid());
+        assert!(report.challenge_matched);
+
+        let mut raw = Vec::new();
+        raw.extend_from_slice(2u64.to_vec().unwrap().as_slice());
+        raw.extend_from_slice(USize(pieces[2].len()).to_vec().unwrap().as_slice());
+        raw.extend_from_slice(pieces[2].as_slice());
+        raw.extend_from_slice(USize(2).to_vec().unwrap().as_slice());
+        raw.extend_from_slice(&leafs[2]);
+        raw.extend_from_slice(&left);
+        let raw_proof = PieceProof::decode(0, raw.as_slice()).unwrap();
+        assert_eq!(raw_proof.piece_index, 2);
+        assert!(raw_proof.verify(MerkleHashAlgorithm::Sha256, &root));
+
+        let mut bad = proof.clone();
+        bad.piece[0] = 1;
+        let report = verify_challenge_proof(&challenge, &bad, MerkleHashAlgorithm::Sha256).unwrap();
+        assert!(!report.root_matched);
+        assert!(!report.challenge_matched);
+
+        let mut short = proof.clone();
+        short.path_list.pop();
+        assert!(!verify_challenge_proof(&challenge, &short, MerkleHashAlgorithm::Sha256).unwrap().is_valid());
+    }
+
+    fn encode_piece_set(leaf_count: u64, pieces: &[(u64, &[u8])], hash_list: &[[u8;32]]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        raw.extend_from_slice(leaf_count.to_vec().unwrap().as_slice());
+        raw.extend_from_slice(USize(pieces.len()).to_vec().unwrap().as_slice());
+        for (index, piece) in pieces.iter() {
+            raw.extend_from_slice(index.to_vec().unwrap().as_slice());
+            raw.extend_from_slice(USize(piece.len()).to_vec().unwrap().as_slice());
+            raw.extend_from_slice(piece);
+        }
+        raw.extend_from_slice(USize(hash_list.len()).to_vec().unwrap().as_slice());
+        for hash in hash_list.iter() {
+            raw.extend_from_slice(hash);
+        }
+        raw
+    }
+
+    #[test]
+    fn test_verify_piece_set() {
+        let pieces: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i; PROOF_PIECE_SIZE as usize]).collect();
+        let leafs: Vec<[u8;32]> = pieces.iter().map(|v| Sha256::digest(v.as_slice()).into()).collect();
+        let left = hash_pair(&leafs[0], &leafs[1]);
+        let right = hash_pair(&leafs[2], &leafs[2]);
+        let root = hash_pair(&left, &right);
+        let merkle_root = HashValue::from(&root).to_string();
+
+        // the sibling of piece 0 is sent, the last piece pairs with itself
+        let raw = encode_piece_set(3, &[(0, pieces[0].as_slice()), (2, pieces[2].as_slice())], &[leafs[1]]);
+        let proof = PieceSetProof::decode_raw(raw.as_slice()).unwrap();
+        assert_eq!(proof.pieces.len(), 2);
+        assert!(proof.verify(MerkleHashAlgorithm::Sha256, &root));
+        let report = verify_sampled_proof(merkle_root.as_str(), 3, &proof, MerkleHashAlgorithm::Sha256, 3, b"seed").unwrap();
+        assert!(report.is_valid());
+        // 3 samples of 3 pieces are all of them
+        assert!(!report.challenge_matched);
+
+        let raw = encode_piece_set(3, &[(0, pieces[0].as_slice()), (1, pieces[1].as_slice()), (2, pieces[2].as_slice())], &[]);
+        let proof = PieceSetProof::decode_raw(raw.as_slice()).unwrap();
+        let report = verify_sampled_proof(merkle_root.as_str(), 3, &proof, MerkleHashAlgorithm::Sha256, 3, b"seed").unwrap();
+        assert!(report.is_valid());
+        assert!(report.challenge_matched);
+
+        // a spare sibling or a missing one breaks the tree
+        let raw = encode_piece_set(3, &[(0, pieces[0].as_slice()), (2, pieces[2].as_slice())], &[leafs[1], leafs[1]]);
+        assert!(PieceSetProof::decode_raw(raw.as_slice()).unwrap().root(MerkleHashAlgorithm::Sha256).is_none());
+        let raw = encode_piece_set(3, &[(0, pieces[0].as_slice()), (2, pieces[2].as_slice())], &[]);
+        assert!(PieceSetProof::decode_raw(raw.as_slice()).unwrap().root(MerkleHashAlgorithm::Sha256).is_none());
+
+        let mut bad = PieceSetProof::decode_raw(encode_piece_set(3, &[(0, pieces[0].as_slice()), (2, pieces[2].as_slice())], &[leafs[1]]).as_slice()).unwrap();
+        bad.pieces[1].1[0] = 1;
+        assert!(!verify_sampled_proof(merkle_root.as_str(), 3, &bad, MerkleHashAlgorithm::Sha256, 3, b"seed").unwrap().root_matched);
+        assert!(!verify_sampled_proof(merkle_root.as_str(), 4, &proof, MerkleHashAlgorithm::Sha256, 3, b"seed").unwrap().depth_matched);
+    }
+}
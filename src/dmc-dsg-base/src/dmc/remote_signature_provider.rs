@@ -0,0 +1,230 @@
+use std::str::FromStr;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use serde::{Serialize, Deserialize};
+use crate::*;
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoteGetKeysResp {
+    pub keys: Option<Vec<String>>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoteSignReq {
+    pub chain_id: String,
+    pub required_keys: Vec<String>,
+    pub serialized_transaction: String,
+    pub serialized_context_free_data: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoteSignResp {
+    pub signatures: Option<Vec<String>>,
+    pub error: Option<String>,
+}
+
+#[cfg(unix)]
+#[derive(Serialize)]
+struct RemoteSignerCall<'a, T> {
+    method: &'a str,
+    params: &'a T,
+}
+
+pub enum RemoteSignerEndpoint {
+    Http(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl RemoteSignerEndpoint {
+    pub fn parse(endpoint: &str) -> BuckyResult<Self> {
+        if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            let endpoint = if endpoint.ends_with("/") {
+                endpoint[0..endpoint.len() - 1].to_string()
+            } else {
+                endpoint.to_string()
+            };
+            return Ok(Self::Http(endpoint));
+        }
+        #[cfg(unix)]
+        if endpoint.starts_with("unix:") {
+            return Ok(Self::Unix(std::path::PathBuf::from(&endpoint["unix:".len()..])));
+        }
+        Err(cyfs_err!(BuckyErrorCode::InvalidParam, "unsupported remote signer endpoint {}", endpoint))
+    }
+
+    async fn call<REQ: Serialize + Sync, RESP: for<'de> Deserialize<'de>>(&self, method: &str, req: &REQ) -> BuckyResult<RESP> {
+        match self {
+            Self::Http(server) => {
+                let url = format!("{}/v1/signer/{}", server.as_str(), method);
+                let data = serde_json::to_string(req).map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::Failed, "encode json err {}", e)
+                })?;
+                http_post_request3(url.as_str(), data.as_bytes(), Some("application/json")).await
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                use async_std::io::prelude::BufReadExt;
+                use async_std::io::WriteExt;
+
+                let mut data = serde_json::to_string(&RemoteSignerCall {
+                    method,
+                    params: req
+                }).map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::Failed, "encode json err {}", e)
+                })?;
+                data.push('\n');
+
+                let mut stream = async_std::os::unix::net::UnixStream::connect(path.as_path()).await.map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::ConnectFailed, "connect {} failed {}", path.display(), e)
+                })?;
+                stream.write_all(data.as_bytes()).await.map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::ConnectFailed, "write {} failed {}", path.display(), e)
+                })?;
+
+                let mut line = String::new();
+                async_std::io::BufReader::new(stream).read_line(&mut line).await.map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::ConnectFailed, "read {} failed {}", path.display(), e)
+                })?;
+                serde_json::from_str(line.as_str()).map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::InvalidData, "parse {} failed {}", line.as_str(), e)
+                })
+            }
+        }
+    }
+}
+
+// the remote side is not trusted, every signature must match one of the required keys
+// and every required key must be covered by one of the signatures
+fn check_remote_signatures(sign_hash: &[u8], required_keys: &Vec<String>, signatures: &Vec<String>) -> BuckyResult<()> {
+    if signatures.len() == 0 {
+        return Err(cyfs_err!(BuckyErrorCode::InvalidSignature, "remote signer returned no signature"));
+    }
+
+    let mut signed = vec![false; required_keys.len()];
+    for signature in signatures.iter() {
+        let sign = DMCSignature::from_str(signature.as_str())?;
+        let mut verified = false;
+        for (i, key) in required_keys.iter().enumerate() {
+            if sign.verify(sign_hash, &DMCPublicKey::from_str(key.as_str())?, false)? {
+                signed[i] = true;
+                verified = true;
+                break;
+            }
+        }
+        if !verified {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidSignature, "remote signature {} doesn't match required keys", signature));
+        }
+    }
+
+    for (i, key) in required_keys.iter().enumerate() {
+        if !signed[i] {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidSignature, "remote signer didn't sign with required key {}", key));
+        }
+    }
+    Ok(())
+}
+
+pub struct RemoteSignatureProvider<P: SignPolicy> {
+    endpoint: RemoteSignerEndpoint,
+    available_keys: Vec<String>,
+    policy: P,
+}
+
+impl<P: SignPolicy> RemoteSignatureProvider<P> {
+    pub async fn open(endpoint: &str, policy: P) -> BuckyResult<Self> {
+        let endpoint = RemoteSignerEndpoint::parse(endpoint)?;
+        let resp: RemoteGetKeysResp = endpoint.call("get_available_keys", &()).await?;
+        if resp.error.is_some() {
+            return Err(cyfs_err!(BuckyErrorCode::Failed, "get available keys err {}", resp.error.as_ref().unwrap()));
+        }
+        Ok(Self {
+            endpoint,
+            available_keys: resp.keys.unwrap_or(Vec::new()),
+            policy
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: SignPolicy> SignatureProvider for RemoteSignatureProvider<P> {
+    fn get_available_keys(&self) -> &Vec<String> {
+        &self.available_keys
+    }
+
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
+        let trans = args.get_transaction()?;
+        self.policy.approve(args.chain_id.as_str(), &trans)?;
+
+        let req = RemoteSignReq {
+            chain_id: args.chain_id.clone(),
+            required_keys: args.required_keys.clone(),
+            serialized_transaction: hex::encode(args.serialized_transaction.as_slice()),
+            serialized_context_free_data: args.serialized_context_free_data.as_ref().map(|data| hex::encode(data.as_slice()))
+        };
+        let resp: RemoteSignResp = self.endpoint.call("sign", &req).await?;
+        if resp.error.is_some() {
+            return Err(cyfs_err!(BuckyErrorCode::Reject, "remote signer rejected: {}", resp.error.as_ref().unwrap()));
+        }
+        let signatures = resp.signatures.unwrap_or(Vec::new());
+
+        check_remote_signatures(args.sign_hash()?.as_slice(), &args.required_keys, &signatures)?;
+
+        Ok(PushTransactionArgs {
+            signatures,
+            compression: None,
+            serialized_transaction: args.serialized_transaction,
+            serialized_context_free_data: args.serialized_context_free_data
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_remote_signature_provider {
+    use super::check_remote_signatures;
+    use crate::*;
+
+    fn sign_hash() -> Vec<u8> {
+        (0..32u8).collect()
+    }
+
+    #[test]
+    fn test_all_keys_signed() {
+        let key1 = DMCPrivateKey::gen_key();
+        let key2 = DMCPrivateKey::gen_key();
+        let required_keys = vec![key1.get_public_key().to_string(), key2.get_public_key().to_string()];
+        let signatures = vec![
+            key2.sign(sign_hash().as_slice(), false).unwrap().to_string(),
+            key1.sign(sign_hash().as_slice(), false).unwrap().to_string(),
+        ];
+        assert!(check_remote_signatures(sign_hash().as_slice(), &required_keys, &signatures).is_ok());
+    }
+
+    #[test]
+    fn test_no_signature() {
+        let key = DMCPrivateKey::gen_key();
+        let required_keys = vec![key.get_public_key().to_string()];
+        assert!(check_remote_signatures(sign_hash().as_slice(), &required_keys, &Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_required_key_unsigned() {
+        let key1 = DMCPrivateKey::gen_key();
+        let key2 = DMCPrivateKey::gen_key();
+        let required_keys = vec![key1.get_public_key().to_string(), key2.get_public_key().to_string()];
+        let signatures = vec![key1.sign(sign_hash().as_slice(), false).unwrap().to_string()];
+        assert!(check_remote_signatures(sign_hash().as_slice(), &required_keys, &signatures).is_err());
+    }
+
+    #[test]
+    fn test_unknown_signer() {
+        let key = DMCPrivateKey::gen_key();
+        let other = DMCPrivateKey::gen_key();
+        let required_keys = vec![key.get_public_key().to_string()];
+        let signatures = vec![
+            key.sign(sign_hash().as_slice(), false).unwrap().to_string(),
+            other.sign(sign_hash().as_slice(), false).unwrap().to_string(),
+        ];
+        assert!(check_remote_signatures(sign_hash().as_slice(), &required_keys, &signatures).is_err());
+    }
+}
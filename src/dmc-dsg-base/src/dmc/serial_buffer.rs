@@ -4,7 +4,7 @@ use regex::Regex;
 use crate::*;
 
 fn is_negative(bignum: &[u8]) -> bool {
-    bignum[bignum.len() - 1] != 0
+    bignum[bignum.len() - 1] & 0x80 != 0
 }
 
 fn negate(bignum: &mut [u8]) {
@@ -334,9 +334,7 @@ impl<'a> SerialBuffer<'a> {
 
     pub fn push_symbol_code(&mut self, name: &str) {
         let mut buf = name.as_bytes().to_vec();
-        if buf.len() < 8 {
-            buf.push(0);
-        }
+        buf.resize(8, 0);
         self.push_array(&buf[0..8]);
     }
 
@@ -426,10 +424,14 @@ impl<'a> SerialBuffer<'a> {
 
         let symbol = self.get_symbol()?;
         let (name, precision) = symbol;
-        let mut s = format!("{}", i64::from_be_bytes(a));
+        // amount is little endian like push_assert writes it, the last precision digits are the fraction
+        let amount = i64::from_le_bytes(a);
+        let mut s = format!("{:0width$}", amount.unsigned_abs(), width = precision as usize + 1);
         if precision > 0 {
-            let (first, second) = s.split_at(precision as usize);
-            s = format!("{}.{}", first, second);
+            s.insert(s.len() - precision as usize, '.');
+        }
+        if amount < 0 {
+            s.insert(0, '-');
         }
         Ok(format!("{} {}", s, name))
     }
@@ -499,4 +501,21 @@ impl<'a> SerialBuffer<'a> {
         };
         private_key.to_string()
     }
+
+    pub fn push_signature(&mut self, s: &str) -> BuckyResult<()> {
+        let signature = DMCSignature::from_str(s)?;
+        self.push_u8(signature.get_type() as u8);
+        self.push_array(signature.to_binary().as_slice());
+        Ok(())
+    }
+
+    pub fn get_signature(&mut self) -> BuckyResult<String> {
+        let key_type = self.get_u8()?;
+        let data = self.get_array(65)?;
+        match key_type {
+            0 => Ok(super::dmc_secret_key::key_to_string(data, "K1", "SIG_K1_")),
+            1 => Ok(super::dmc_secret_key::key_to_string(data, "R1", "SIG_R1_")),
+            v @ _ => Err(cyfs_err!(BuckyErrorCode::NotSupport, "unsupport {} signature type", v))
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 use cyfs_base::{BuckyErrorCode, BuckyResult};
 use sha2::Digest;
@@ -12,9 +13,49 @@ pub struct SignatureProviderArgs {
     pub abis: Vec<BinaryAbi>,
 }
 
+impl SignatureProviderArgs {
+    pub fn sign_hash(&self) -> BuckyResult<Vec<u8>> {
+        let chain_id = hex::decode(self.chain_id.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidParam, "decode chain_id {} failed {:?}", self.chain_id.as_str(), e)
+        })?;
+        let hash = if self.serialized_context_free_data.is_some() {
+            let mut sha256 = sha2::Sha256::new();
+            sha256.update(self.serialized_context_free_data.as_ref().unwrap().as_slice());
+            sha256.finalize().to_vec()
+        } else {
+            let mut buf = Vec::<u8>::new();
+            buf.resize(32, 0);
+            buf
+        };
+        let sign_buf = vec![chain_id, self.serialized_transaction.clone(), hash].concat();
+
+        let mut sha256 = sha2::Sha256::new();
+        sha256.update(sign_buf.as_slice());
+        Ok(sha256.finalize().to_vec())
+    }
+
+    pub fn get_transaction(&self) -> BuckyResult<Transaction> {
+        let mut data = self.serialized_transaction.clone();
+        let mut buf = SerialBuffer::new(&mut data);
+        Transaction::dmc_deserialize(&mut buf)
+    }
+}
+
+#[async_trait::async_trait]
 pub trait SignatureProvider: Send + Sync {
     fn get_available_keys(&self) -> &Vec<String>;
-    fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs>;
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs>;
+}
+
+#[async_trait::async_trait]
+impl SignatureProvider for Box<dyn SignatureProvider> {
+    fn get_available_keys(&self) -> &Vec<String> {
+        self.as_ref().get_available_keys()
+    }
+
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
+        self.as_ref().sign(args).await
+    }
 }
 
 pub struct SimpleSignatureProvider {
@@ -24,10 +65,17 @@ pub struct SimpleSignatureProvider {
 
 impl SimpleSignatureProvider {
     pub fn new(priv_keys: Vec<String>) -> BuckyResult<Self> {
+        let mut keys = Vec::new();
+        for key in priv_keys.into_iter() {
+            keys.push(DMCPrivateKey::from_str(key.as_str())?);
+        }
+        Self::from_keys(keys)
+    }
+
+    pub fn from_keys(priv_keys: Vec<DMCPrivateKey>) -> BuckyResult<Self> {
         let mut available_keys = Vec::new();
         let mut keys = HashMap::new();
-        for key in priv_keys.into_iter() {
-            let private_key = DMCPrivateKey::from_str(key.as_str())?;
+        for private_key in priv_keys.into_iter() {
             let public_key = private_key.get_public_key().to_legacy_string()?;
             log::info!("public_key {}", public_key.as_str());
             available_keys.push(public_key.clone());
@@ -37,29 +85,14 @@ impl SimpleSignatureProvider {
     }
 }
 
+#[async_trait::async_trait]
 impl SignatureProvider for SimpleSignatureProvider {
     fn get_available_keys(&self) -> &Vec<String> {
         &self.available_keys
     }
 
-    fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
-        let chain_id = hex::decode(args.chain_id.as_str()).map_err(|e| {
-            cyfs_err!(BuckyErrorCode::InvalidParam, "decode chain_id {} failed {:?}", args.chain_id.as_str(), e)
-        })?;
-        let hash = if args.serialized_context_free_data.is_some() {
-            let mut sha256 = sha2::Sha256::new();
-            sha256.update(args.serialized_context_free_data.as_ref().unwrap().as_slice());
-            sha256.finalize().to_vec()
-        } else {
-            let mut buf = Vec::<u8>::new();
-            buf.resize(32, 0);
-            buf
-        };
-        let sign_buf = vec![chain_id, args.serialized_transaction.clone(), hash].concat();
-
-        let mut sha256 = sha2::Sha256::new();
-        sha256.update(sign_buf.as_slice());
-        let sign_hash = sha256.finalize().to_vec();
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
+        let sign_hash = args.sign_hash()?;
 
         let mut signatures = Vec::new();
         for key in args.required_keys.iter() {
@@ -79,3 +112,179 @@ impl SignatureProvider for SimpleSignatureProvider {
         })
     }
 }
+
+pub trait SignPolicy: Send + Sync {
+    fn approve(&self, chain_id: &str, trans: &Transaction) -> BuckyResult<()>;
+}
+
+impl SignPolicy for Box<dyn SignPolicy> {
+    fn approve(&self, chain_id: &str, trans: &Transaction) -> BuckyResult<()> {
+        self.as_ref().approve(chain_id, trans)
+    }
+}
+
+pub struct ApproveAllPolicy;
+
+impl SignPolicy for ApproveAllPolicy {
+    fn approve(&self, _chain_id: &str, _trans: &Transaction) -> BuckyResult<()> {
+        Ok(())
+    }
+}
+
+pub struct ActionWhitelistPolicy {
+    actions: Vec<(String, String)>,
+}
+
+impl ActionWhitelistPolicy {
+    pub fn new(actions: Vec<(String, String)>) -> Self {
+        Self {
+            actions
+        }
+    }
+
+    // actions the miner signs with the light permission while serving orders
+    pub fn light() -> Self {
+        Self::new(vec![
+            ("dmc.token".to_string(), "addmerkle".to_string()),
+            ("dmc.token".to_string(), "anschallenge".to_string()),
+            ("dmc.token".to_string(), "arbitration".to_string()),
+        ])
+    }
+
+    fn is_allowed(&self, action: &Action) -> bool {
+        self.actions.iter().any(|(account, name)| account == &action.account && name == &action.name)
+    }
+}
+
+impl SignPolicy for ActionWhitelistPolicy {
+    fn approve(&self, _chain_id: &str, trans: &Transaction) -> BuckyResult<()> {
+        for action in trans.context_free_actions.iter().chain(trans.actions.iter()) {
+            if !self.is_allowed(action) {
+                return Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "action {}::{} is not in sign whitelist", action.account, action.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct PolicySignatureProvider<T: SignatureProvider, P: SignPolicy> {
+    provider: T,
+    policy: P,
+}
+
+impl<T: SignatureProvider, P: SignPolicy> PolicySignatureProvider<T, P> {
+    pub fn new(provider: T, policy: P) -> Self {
+        Self {
+            provider,
+            policy
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: SignatureProvider, P: SignPolicy> SignatureProvider for PolicySignatureProvider<T, P> {
+    fn get_available_keys(&self) -> &Vec<String> {
+        self.provider.get_available_keys()
+    }
+
+    async fn sign(&self, args: SignatureProviderArgs) -> BuckyResult<PushTransactionArgs> {
+        let trans = args.get_transaction()?;
+        self.policy.approve(args.chain_id.as_str(), &trans)?;
+        self.provider.sign(args).await
+    }
+}
+
+pub type WhitelistSignatureProvider<T> = PolicySignatureProvider<T, ActionWhitelistPolicy>;
+
+// what a remote signer is asked to sign, checked before the request leaves
+pub enum SignPolicyType {
+    ApproveAll,
+    Whitelist(Vec<(String, String)>),
+}
+
+impl SignPolicyType {
+    pub fn create(self) -> Box<dyn SignPolicy> {
+        match self {
+            Self::ApproveAll => Box::new(ApproveAllPolicy),
+            Self::Whitelist(actions) => Box::new(ActionWhitelistPolicy::new(actions)),
+        }
+    }
+}
+
+pub enum SignatureProviderType {
+    Simple(Vec<String>),
+    Keystore {
+        key_files: Vec<PathBuf>,
+        passphrase: String,
+    },
+    Remote {
+        endpoint: String,
+        policy: SignPolicyType,
+    },
+}
+
+pub struct SignatureProviderConfig {
+    pub provider: SignatureProviderType,
+    pub light_only: bool,
+}
+
+pub async fn create_signature_provider(config: SignatureProviderConfig) -> BuckyResult<Box<dyn SignatureProvider>> {
+    let provider: Box<dyn SignatureProvider> = match config.provider {
+        SignatureProviderType::Simple(keys) => {
+            Box::new(SimpleSignatureProvider::new(keys)?)
+        }
+        SignatureProviderType::Keystore { key_files, passphrase } => {
+            Box::new(KeystoreSignatureProvider::new(key_files, passphrase.as_str())?)
+        }
+        SignatureProviderType::Remote { endpoint, policy } => {
+            Box::new(RemoteSignatureProvider::open(endpoint.as_str(), policy.create()).await?)
+        }
+    };
+
+    if config.light_only {
+        Ok(Box::new(WhitelistSignatureProvider::new(provider, ActionWhitelistPolicy::light())))
+    } else {
+        Ok(provider)
+    }
+}
+
+#[cfg(test)]
+mod test_signature_provider {
+    use cyfs_base::BuckyErrorCode;
+    use crate::*;
+
+    fn transaction(actions: &[(&str, &str)]) -> Transaction {
+        let mut builder = TransactionBuilder::new();
+        for (account, name) in actions.iter() {
+            builder = builder.add_raw_action(account, name, vec![Authorization {
+                actor: "alice".to_string(),
+                permission: "light".to_string()
+            }], vec![]);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_light_policy() {
+        let policy = ActionWhitelistPolicy::light();
+        for name in ["addmerkle", "anschallenge", "arbitration"].iter() {
+            assert!(policy.approve("", &transaction(&[("dmc.token", name)])).is_ok());
+        }
+        assert!(policy.approve("", &transaction(&[("dmc.token", "addmerkle"), ("dmc.token", "arbitration")])).is_ok());
+
+        let err = policy.approve("", &transaction(&[("dmc.token", "bill")])).unwrap_err();
+        assert_eq!(err.code(), BuckyErrorCode::PermissionDenied);
+        // the same name in another contract isn't whitelisted
+        assert!(policy.approve("", &transaction(&[("eosio.token", "addmerkle")])).is_err());
+        // one action out of the list refuses the whole transaction
+        assert!(policy.approve("", &transaction(&[("dmc.token", "addmerkle"), ("dmc.token", "transfer")])).is_err());
+    }
+
+    #[test]
+    fn test_policy_type() {
+        let policy = SignPolicyType::Whitelist(vec![("dmc.token".to_string(), "mint".to_string())]).create();
+        assert!(policy.approve("", &transaction(&[("dmc.token", "mint")])).is_ok());
+        assert!(policy.approve("", &transaction(&[("dmc.token", "bill")])).is_err());
+        assert!(SignPolicyType::ApproveAll.create().approve("", &transaction(&[("dmc.token", "bill")])).is_ok());
+    }
+}
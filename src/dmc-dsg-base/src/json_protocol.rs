@@ -11,6 +11,12 @@ pub enum JsonProtocol {
     SetHttpDomainResp,
     GetStat = 12,
     GetStatResp = 13,
+    GetPricingDecisions = 14,
+    GetPricingDecisionsResp = 15,
+    GetAutoMintState = 16,
+    GetAutoMintStateResp = 17,
+    GetEarningsReport = 18,
+    GetEarningsReportResp = 19,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -24,3 +30,13 @@ pub struct GetChunkMerkleHashReq {
     pub chunk_list: Vec<String>,
     pub chunk_size: u32,
 }
+
+// pieces of the order sampled by the id of a dsg challenge, posted to the consumer after the proof of the challenge
+#[derive(Serialize, Deserialize)]
+pub struct SampledProofReq {
+    pub challenge_id: String,
+    pub order_id: String,
+    pub sample_count: u32,
+    // hex of the raw encoded MultiPieceProof
+    pub proof: String,
+}
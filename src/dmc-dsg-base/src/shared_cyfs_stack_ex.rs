@@ -243,6 +243,10 @@ pub trait CyfsClient: Send + Sync + 'static {
         object_id: ObjectId,
         object_raw: Vec<u8>
     ) -> BuckyResult<T>;
+    // free bytes of the disks of the local device
+    async fn get_free_space(&self) -> BuckyResult<u64> {
+        Err(cyfs_err!(BuckyErrorCode::NotSupport, "free space not supported"))
+    }
 }
 
 #[async_trait::async_trait]
@@ -378,6 +382,13 @@ impl CyfsClient for SharedCyfsStack {
             Ok(T::clone_from_slice(object_raw.as_slice())?)
         }
     }
+
+    async fn get_free_space(&self) -> BuckyResult<u64> {
+        let resp = self.util().get_system_info(UtilGetSystemInfoOutputRequest {
+            common: Default::default()
+        }).await?;
+        Ok(resp.info.hdd_disk_avail + resp.info.ssd_disk_avail)
+    }
 }
 
 pub type SharedCyfsStackRef = Arc<SharedCyfsStack>;
@@ -1,10 +1,12 @@
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use async_std::task::JoinHandle;
 use cyfs_base::{BuckyErrorCode, BuckyResult, NamedObject, ObjectDesc, ObjectId, OwnerObjectDesc, RawConvertTo};
-use cyfs_core::{DecApp, DecAppObj};
-use cyfs_lib::{SharedCyfsStack, UtilGetSystemInfoOutputRequest};
-use dmc_dsg_base::{Authority, DMCClient, DSGJSON, JSONObject, JsonProtocol, KeyWeight, SimpleSignatureProvider, cyfs_err, SetDMCAccount, DMCPrivateKey, CyfsPath, CyfsClient, DMCDsgConfig, LocalDMCTxSender, DMCTxSender};
+use cyfs_lib::SharedCyfsStack;
+use dmc_dsg_base::{Authority, DMCClient, DSGJSON, JSONObject, JsonProtocol, KeyWeight, SimpleSignatureProvider, cyfs_err, SetDMCAccount, DMCPrivateKey, CyfsPath, CyfsClient, LocalDMCTxSender, DMCTxSender, DMCKeyStore, DMCPublicKey, DMCRpc, SignatureProvider, SignatureProviderConfig, SignatureProviderType, SignPolicyType, audit_light_permission, LIGHT_LINKED_ACTIONS, LIGHT_PERMISSION, LIGHT_PERMISSION_PARENT, OfflineTransaction, stake_transaction, mint_transaction, bill_transaction, TransResult, TransactionTrace, space_to_pst, stake_to_pst, EarningsPeriod, EarningsReport, DMCTxTracker, TrackedTxState, DMC_TX_EXPIRE_SECONDS, LightKeyChain, LightKeyMiner, RotateProgress, RotateStep, rotate_light_key};
+use crate::{print_offline_transaction, read_new_passphrase, read_passphrase, read_confirm, unstake_rate, load_unstake_state, save_unstake_state, UnstakeRefund};
 
 pub struct DmcInfo {
     pub dmc_account: String,
@@ -21,10 +23,12 @@ pub struct App {
     owner_id: ObjectId,
     dmc_sever: String,
     dmc_tracker_server: String,
+    key_store: DMCKeyStore,
+    allow_raw_key: bool,
 }
 
 impl App {
-    pub async fn new(stack: Arc<SharedCyfsStack>, dec_id: ObjectId, dmc_server: String, dmc_tracker_server: String) -> BuckyResult<Self> {
+    pub async fn new(stack: Arc<SharedCyfsStack>, dec_id: ObjectId, dmc_server: String, dmc_tracker_server: String, allow_raw_key: bool) -> BuckyResult<Self> {
         let owner_id = stack.local_device().desc().owner().as_ref().unwrap().clone();
         let ood_id = stack.resolve_ood(owner_id.clone()).await?;
         Ok(Self {
@@ -33,46 +37,270 @@ impl App {
             ood_id,
             owner_id,
             dmc_sever: dmc_server.to_string(),
-            dmc_tracker_server
+            dmc_tracker_server,
+            key_store: DMCKeyStore::open_default()?,
+            allow_raw_key
         })
     }
 
-    pub async fn set_dmc_account(&self, dmc_account: &str, private_key: &str) -> BuckyResult<()> {
-        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::SetDMCAccount as u16, &SetDMCAccount {
-            dmc_account: dmc_account.to_string(),
-            dmc_key: private_key.to_string()
+    // key is an alias in the keystore, raw private keys only with --allow-raw-key
+    pub fn load_private_key(&self, key: &str) -> BuckyResult<DMCPrivateKey> {
+        if self.key_store.contains(key) {
+            let passphrase = read_passphrase()?;
+            self.key_store.load(key, passphrase.as_str())
+        } else {
+            self.parse_raw_key(key)
+        }
+    }
+
+    fn parse_raw_key(&self, key: &str) -> BuckyResult<DMCPrivateKey> {
+        let private_key = DMCPrivateKey::from_str(key).map_err(|_| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find key alias {} in keystore", key)
         })?;
-        let req_id = req.desc().calculate_id();
-        let dec_id = DecApp::generate_id(ObjectId::from_str(DMCDsgConfig::PUB_PEOPLE_ID).unwrap(), "DMC DSG service");
-        let req_path = CyfsPath::new(self.ood_id.clone(), dec_id.clone(), "dmc_account_commands").to_path();
-        let _: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
-        Ok(())
+        if !self.allow_raw_key {
+            return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "raw private key on the command line is refused, import it with key_import and use the alias, or pass --allow-raw-key"));
+        }
+        println!("warning: raw private key on the command line ends up in shell history, import it with key_import and use the alias");
+        Ok(private_key)
+    }
+
+    // key is a remote signer endpoint, an alias in the keystore or a raw private key
+    fn signature_provider_config(&self, key: &str) -> BuckyResult<SignatureProviderConfig> {
+        let provider = if key.starts_with("http://") || key.starts_with("https://") || key.starts_with("unix:") {
+            // the operator asked for this very command, so the signer may sign anything it builds
+            SignatureProviderType::Remote {
+                endpoint: key.to_string(),
+                policy: SignPolicyType::ApproveAll
+            }
+        } else if self.key_store.contains(key) {
+            SignatureProviderType::Keystore {
+                key_files: vec![self.key_store.key_path(key)],
+                passphrase: read_passphrase()?
+            }
+        } else {
+            self.parse_raw_key(key)?;
+            SignatureProviderType::Simple(vec![key.to_string()])
+        };
+        Ok(SignatureProviderConfig {
+            provider,
+            light_only: false
+        })
+    }
+
+    async fn create_dmc_client(&self, dmc_account: &str, key: &str) -> BuckyResult<DMCClient<LocalDMCTxSender<Box<dyn SignatureProvider>>>> {
+        let tx_sender = LocalDMCTxSender::from_config(
+            dmc_account,
+            self.dmc_sever.as_str(),
+            self.signature_provider_config(key)?).await?;
+        Ok(DMCClient::new(
+            dmc_account,
+            self.dmc_sever.as_str(),
+            self.dmc_tracker_server.as_str(),
+            tx_sender
+        ))
     }
 
-    pub async fn create_light_auth(&self, dmc_account: &str, private_key: &str) -> BuckyResult<()> {
-        let light_private_key = DMCPrivateKey::gen_key();
-        let dmc_key = light_private_key.get_public_key().to_legacy_string()?;
+    // reads chain tables only, nothing can be signed with it
+    fn create_query_client(&self, dmc_account: &str) -> BuckyResult<DMCClient<LocalDMCTxSender<SimpleSignatureProvider>>> {
         let tx_sender = LocalDMCTxSender::new(
             dmc_account,
             self.dmc_sever.as_str(),
-            SimpleSignatureProvider::new(vec![private_key.to_string()])?);
-        let dmc_client = DMCClient::new(
+            SimpleSignatureProvider::new(vec![])?);
+        Ok(DMCClient::new(
             dmc_account,
             self.dmc_sever.as_str(),
             self.dmc_tracker_server.as_str(),
             tx_sender
-        );
+        ))
+    }
+
+    async fn get_storage_pst(&self) -> BuckyResult<u64> {
+        Ok(space_to_pst(self.stack.get_free_space().await?))
+    }
+
+    // through the miner, so it records the light key it reports with GetDMCKey
+    pub async fn set_dmc_account(&self, dmc_account: &str, light_key: &str) -> BuckyResult<()> {
+        let light_private_key = self.load_private_key(light_key)?;
+        self.push_light_key(dmc_account, &light_private_key).await
+    }
+
+    // decisions the pricing agent of the local miner recorded, oldest first
+    pub async fn pricing_log(&self, count: u32) -> BuckyResult<()> {
+        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::GetPricingDecisions as u16, &count)?;
+        let req_id = req.desc().calculate_id();
+        let req_path = CyfsPath::new(self.ood_id.clone(), self.dec_id.clone(), "dsg_local_commands").to_path();
+        let resp: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
+        let decisions: Vec<serde_json::Value> = resp.get()?;
+        println!("{:<12} {:>10} {:>6} {:>10} {:>10} {:<7} {:<60} {}", "time", "avg_price", "util", "capacity", "price", "dry_run", "action", "result");
+        for decision in decisions.iter() {
+            println!("{:<12} {:>10} {:>6.2} {:>10} {:>10.4} {:<7} {:<60} {}",
+                     decision["time"].as_u64().unwrap_or(0),
+                     decision["avg_price"].as_f64().unwrap_or(0f64),
+                     decision["utilisation"].as_f64().unwrap_or(0f64),
+                     decision["capacity"].as_u64().unwrap_or(0),
+                     decision["price"].as_f64().unwrap_or(0f64),
+                     decision["dry_run"].as_bool().unwrap_or(false),
+                     decision["action"].to_string(),
+                     decision["result"].as_str().unwrap_or(""));
+        }
+        Ok(())
+    }
+
+    // what the auto mint of the local miner saw and did in its last round
+    pub async fn mint_status(&self) -> BuckyResult<()> {
+        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::GetAutoMintState as u16, &"".to_string())?;
+        let req_id = req.desc().calculate_id();
+        let req_path = CyfsPath::new(self.ood_id.clone(), self.dec_id.clone(), "dsg_local_commands").to_path();
+        let resp: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
+        let state: serde_json::Value = resp.get()?;
+        println!("last check:{}", state["last_check"].as_u64().unwrap_or(0));
+        println!("minted PST:{}", state["minted"].as_u64().unwrap_or(0));
+        println!("storage backed PST:{}", state["storage_pst"].as_u64().unwrap_or(0));
+        println!("stake backed PST:{}", state["stake_pst"].as_u64().unwrap_or(0));
+        println!("minted today:{}", state["minted_today"].as_u64().unwrap_or(0));
+        println!("last amount:{} {}", state["amount"].as_u64().unwrap_or(0), state["result"].as_str().unwrap_or(""));
+        Ok(())
+    }
+
+    // income, stake rewards, penalties and locked pledge of the local miner, written as csv when a file is given
+    pub async fn earnings(&self, period: &str, csv_file: Option<&str>) -> BuckyResult<()> {
+        let period = EarningsPeriod::parse(period)?;
+        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::GetEarningsReport as u16, &period)?;
+        let req_id = req.desc().calculate_id();
+        let req_path = CyfsPath::new(self.ood_id.clone(), self.dec_id.clone(), "dsg_local_commands").to_path();
+        let resp: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
+        let report: EarningsReport = resp.get()?;
+        if let Some(csv_file) = csv_file {
+            std::fs::write(csv_file, report.to_csv()).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::IoError, "write {} failed {}", csv_file, e)
+            })?;
+            println!("write {} rows to {}", report.rows.len(), csv_file);
+            return Ok(());
+        }
+
+        println!("{:<20} {:>12} {:>12} {:>12} {:>14} {:>16} {:>12}", "key", "income", "stake_reward", "penalty", "pledge_locked", "pledge_released", "net");
+        for row in report.rows.iter() {
+            println!("{:<20} {:>12.4} {:>12.4} {:>12.4} {:>14.4} {:>16.4} {:>12.4}",
+                     row.key, row.income, row.stake_reward, row.penalty, row.pledge_locked, row.pledge_released, row.net);
+        }
+        println!("locked DMC:{:.4}", report.locked_dmc);
+        println!("locked PST:{}", report.locked_pst);
+        println!("stake DMC:{:.4} rate {}", report.staked, report.stake_rate);
+        println!("updated at:{}", report.updated_at);
+        Ok(())
+    }
+
+    // the miner answers with the key it handed to its signer
+    async fn get_miner_light_key(&self, dmc_account: &str) -> BuckyResult<String> {
+        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::GetDMCKey as u16, &dmc_account.to_string())?;
+        let req_id = req.desc().calculate_id();
+        let req_path = CyfsPath::new(self.ood_id.clone(), self.dec_id.clone(), "dsg_local_commands").to_path();
+        let resp: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
+        let public_key: String = resp.get()?;
+        DMCPublicKey::from_str(public_key.as_str())?.to_legacy_string()
+    }
+
+    async fn get_light_keys<T: DMCTxSender>(dmc_client: &DMCClient<T>) -> BuckyResult<Vec<String>> {
+        let light = dmc_client.get_permission(LIGHT_PERMISSION).await?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "{} has no light permission, run create_light_auth first", dmc_client.get_account_name())
+        })?;
+        let mut keys = Vec::new();
+        for key in light.required_auth.keys.iter() {
+            keys.push(DMCPublicKey::from_str(key.key.as_str())?.to_legacy_string()?);
+        }
+        Ok(keys)
+    }
+
+    async fn update_light_keys<T: DMCTxSender>(dmc_client: &DMCClient<T>, keys: Vec<String>) -> BuckyResult<()> {
+        // the chain requires the keys of an authority sorted by their binary form
+        let mut sorted = Vec::new();
+        for key in keys.into_iter() {
+            let binary = DMCPublicKey::from_str(key.as_str())?.key.serialize_compressed().to_vec();
+            sorted.push((binary, key));
+        }
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+        dmc_client.update_auth(LIGHT_PERMISSION.to_string(), LIGHT_PERMISSION_PARENT.to_string(), Authority {
+            threshold: 1,
+            keys: sorted.iter().map(|(_, key)| KeyWeight {
+                key: key.clone(),
+                weight: 1
+            }).collect(),
+            accounts: vec![],
+            waits: vec![]}).await?;
+
+        let mut expect: Vec<String> = sorted.into_iter().map(|(_, key)| key).collect();
+        expect.sort();
+        for _ in 0..10 {
+            let mut keys = Self::get_light_keys(dmc_client).await?;
+            keys.sort();
+            if keys == expect {
+                return Ok(());
+            }
+            async_std::task::sleep(Duration::from_secs(3)).await;
+        }
+        Err(cyfs_err!(BuckyErrorCode::Timeout, "light permission of {} isn't updated on chain", dmc_client.get_account_name()))
+    }
+
+    // the miner forwards the key to its signer, then GetDMCKey reports it
+    async fn push_light_key(&self, dmc_account: &str, light_private_key: &DMCPrivateKey) -> BuckyResult<()> {
+        let req = JSONObject::new(self.dec_id.clone(), self.owner_id.clone(), JsonProtocol::SetDMCAccount as u16, &SetDMCAccount {
+            dmc_account: dmc_account.to_string(),
+            dmc_key: light_private_key.to_legacy_string()?
+        })?;
+        let req_id = req.desc().calculate_id();
+        let req_path = CyfsPath::new(self.ood_id.clone(), self.dec_id.clone(), "dsg_local_commands").to_path();
+        let _: JSONObject = self.stack.put_object_with_resp2(req_path.as_str(), req_id, req.to_vec()?).await?;
+        Ok(())
+    }
+
+    // rotate the light key without a window where the miner can't sign, see rotate_light_key in dmc-dsg-base
+    pub async fn rotate_light_key(&self, dmc_account: &str, owner_key: &str, light_alias: &str) -> BuckyResult<()> {
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        let passphrase = read_passphrase()?;
+
+        let chain_keys = Self::get_light_keys(&dmc_client).await?;
+        let current_key = self.key_store.get_public_key(light_alias)?;
+        // a previous interrupted rotation left the new key in keystore, continue with it
+        if chain_keys.len() == 1 && chain_keys[0] == current_key {
+            self.key_store.rotate(light_alias, passphrase.as_str())?;
+        }
+        let new_private_key = self.key_store.load(light_alias, passphrase.as_str())?;
+
+        rotate_light_key(
+            &ChainLightKeys { dmc_client: &dmc_client },
+            &MinerLightKeys { app: self },
+            dmc_account,
+            &new_private_key,
+            RotateProgress {
+                on_step: Box::new(|step| match step {
+                    RotateStep::KeyAdded(keys) => println!("step 1/3: light permission has keys {}", keys.join(",")),
+                    RotateStep::MinerSwitched(key) => println!("step 2/3: miner signs with {}", key),
+                    RotateStep::OldKeysDropped(keys) => println!("step 3/3: light permission has keys {}", keys.join(",")),
+                }),
+                ..RotateProgress::default()
+            }).await
+    }
+
+    pub async fn create_light_auth(&self, dmc_account: &str, owner_key: &str, light_alias: &str) -> BuckyResult<()> {
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        // reuse the light key left by a previous failed run
+        let dmc_key = if self.key_store.contains(light_alias) {
+            self.key_store.get_public_key(light_alias)?
+        } else {
+            let passphrase = read_new_passphrase()?;
+            self.key_store.generate(light_alias, passphrase.as_str())?
+        };
+        let light_alias = light_alias.to_string();
 
         let task: JoinHandle<BuckyResult<()>> = async_std::task::spawn(async move {
-            let _ = dmc_client.unlink_auth("cyfsaddrinfo".to_string(), "bind".to_string(), "active".to_string()).await;
-            let _ = dmc_client.unlink_auth("dmc.token".to_string(), "addmerkle".to_string(), "active".to_string()).await;
-            let _ = dmc_client.unlink_auth("dmc.token".to_string(), "reqchallenge".to_string(), "active".to_string()).await;
-            let _ = dmc_client.unlink_auth("dmc.token".to_string(), "anschallenge".to_string(), "active".to_string()).await;
-            let _ = dmc_client.unlink_auth("dmc.token".to_string(), "arbitration".to_string(), "active".to_string()).await;
-            let _ = dmc_client.unlink_auth("dmc.token".to_string(), "paychallenge".to_string(), "active".to_string()).await;
+            for (code, ty) in LIGHT_LINKED_ACTIONS.iter() {
+                let _ = dmc_client.unlink_auth(code.to_string(), ty.to_string(), "active".to_string()).await;
+            }
             let _ = dmc_client.delete_auth("light".to_string(), "active".to_string()).await;
 
-            dmc_client.update_auth("light".to_string(), "active".to_string(), Authority {
+            let dmc_key_str = dmc_key.clone();
+            dmc_client.update_auth(LIGHT_PERMISSION.to_string(), LIGHT_PERMISSION_PARENT.to_string(), Authority {
                 threshold: 1,
                 keys: vec![KeyWeight {
                     key: dmc_key,
@@ -81,14 +309,11 @@ impl App {
                 accounts: vec![],
                 waits: vec![]}).await?;
 
-            dmc_client.link_auth("cyfsaddrinfo".to_string(), "bind".to_string(), "active".to_string()).await?;
-            dmc_client.link_auth("dmc.token".to_string(), "addmerkle".to_string(), "active".to_string()).await?;
-            dmc_client.link_auth("dmc.token".to_string(), "reqchallenge".to_string(), "active".to_string()).await?;
-            dmc_client.link_auth("dmc.token".to_string(), "anschallenge".to_string(), "active".to_string()).await?;
-            dmc_client.link_auth("dmc.token".to_string(), "arbitration".to_string(), "active".to_string()).await?;
-            dmc_client.link_auth("dmc.token".to_string(), "paychallenge".to_string(), "active".to_string()).await?;
+            for (code, ty) in LIGHT_LINKED_ACTIONS.iter() {
+                dmc_client.link_auth(code.to_string(), ty.to_string(), "active".to_string()).await?;
+            }
 
-            println!("light key {}", light_private_key.to_legacy_string()?);
+            println!("light key {} saved in keystore as {}", dmc_key_str, light_alias);
             Ok(())
         });
 
@@ -98,46 +323,135 @@ impl App {
         Ok(())
     }
 
-    pub async fn stake(&self, dmc_account: &str, private_key: &str, dmc_count: &str) -> BuckyResult<()> {
-        let tx_sender = LocalDMCTxSender::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            SimpleSignatureProvider::new(vec![private_key.to_string()])?);
-        let dmc_client = DMCClient::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            self.dmc_tracker_server.as_str(),
-            tx_sender
-        );
+    // check the light permission of dmc_account, the expected key comes from keystore or from the running DSG
+    pub async fn check_light_auth(&self, dmc_account: &str, light_alias: Option<&str>) -> BuckyResult<()> {
+        let light_key = match light_alias {
+            Some(light_alias) => Some(self.key_store.get_public_key(light_alias)?),
+            None => match self.get_miner_light_key(dmc_account).await {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    println!("can't get light key from DSG: {}, key isn't checked", e);
+                    None
+                }
+            }
+        };
+        let permissions = DMCRpc::new(self.dmc_sever.as_str()).get_permissions(dmc_account).await?;
+        let issues = audit_light_permission(&permissions, light_key.as_ref().map(|v| v.as_str()))?;
+        if issues.is_empty() {
+            println!("light permission of {} is ok", dmc_account);
+            return Ok(());
+        }
+        for issue in issues.iter() {
+            println!("{}", issue);
+            println!("    fix: {}", issue.fix(dmc_account));
+        }
+        Err(cyfs_err!(BuckyErrorCode::PermissionDenied, "light permission of {} has {} issues", dmc_account, issues.len()))
+    }
+
+    pub async fn stake(&self, dmc_account: &str, owner_key: &str, dmc_count: &str) -> BuckyResult<()> {
+        let amount: f64 = dmc_count.parse().map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidInput, "parse {} err {}", dmc_count, e)
+        })?;
+        let query_client = self.create_query_client(dmc_account)?;
+        // the first stake has no row yet
+        let staked = match query_client.get_stake_info(dmc_account).await {
+            Ok(stake_info) => stake_info.get_staked()?,
+            Err(e) if e.code() == BuckyErrorCode::NotFound => 0f64,
+            Err(e) => return Err(e),
+        };
+        let avg_price = query_client.get_pst_trans_info().await?.avg();
+        println!("stake DMC:{:.4} -> {:.4}", staked, staked + amount);
+        println!("PST the stake backs:{} -> {}", stake_to_pst(staked, avg_price), stake_to_pst(staked + amount, avg_price));
+        read_confirm("stake?")?;
+
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
         dmc_client.stake(dmc_count).await?;
         Ok(())
     }
 
-    pub async fn mint(&self, dmc_account: &str, private_key: &str, pst_count: &str) -> BuckyResult<()> {
-        let resp = self.stack.util().get_system_info(UtilGetSystemInfoOutputRequest {
-            common: Default::default()
-        }).await?;
-        let space = resp.info.hdd_disk_avail + resp.info.ssd_disk_avail;
-        let mut max_pst = space / (1024 * 1024 * 1024) * 85 / 100;
+    // amount is DMC of the account's own share or all, the refund is tracked until its transaction is final
+    pub async fn unstake(&self, dmc_account: &str, owner_key: &str, amount: &str) -> BuckyResult<()> {
+        let query_client = self.create_query_client(dmc_account)?;
+        let stake_info = query_client.get_stake_info(dmc_account).await?;
+        let weight = match query_client.get_maker_pool(dmc_account, dmc_account).await? {
+            Some(record) => record.get_weight()?,
+            None => 0f64
+        };
+        let share = stake_info.weight_to_dmc(weight)?;
+        let rate = unstake_rate(amount, share)?;
+        let refund = share * rate;
 
-        let tx_sender = LocalDMCTxSender::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            SimpleSignatureProvider::new(vec![private_key.to_string()])?);
-        let dmc_client = DMCClient::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            self.dmc_tracker_server.as_str(),
-            tx_sender
-            );
+        let staked = stake_info.get_staked()?;
+        let avg_price = query_client.get_pst_trans_info().await?.avg();
+        let minted_pst = query_client.get_pst_amount(dmc_account).await?;
+        let storage_pst = self.get_storage_pst().await?;
+        let max_pst = storage_pst.min(stake_to_pst(staked, avg_price));
+        let new_max_pst = storage_pst.min(stake_to_pst(staked - refund, avg_price));
+        println!("unstake DMC:{:.4}, {:.2}% of the share of {}", refund, rate * 100f64, dmc_account);
+        println!("stake DMC:{:.4} -> {:.4}", staked, staked - refund);
+        println!("max mintable PST:{} -> {}, minted {}", max_pst, new_max_pst, minted_pst);
+        if minted_pst > new_max_pst {
+            println!("warning: the stake left can't back the minted PST, the chain may refuse the unstake");
+        }
+        read_confirm("unstake?")?;
+
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        let ret = dmc_client.unstake(rate).await?;
+        let mut state = load_unstake_state()?;
+        let trx_id = DMCTxTracker::new(self.dmc_sever.as_str(), DMC_TX_EXPIRE_SECONDS).track(&mut state, ret, "unstake", "", UnstakeRefund {
+            dmc_account: dmc_account.to_string(),
+            rate,
+            dmc: refund
+        }, 0).await?;
+        save_unstake_state(&state)?;
+        println!("unstake transaction {} sent, stake_info shows when it is final", trx_id);
+        Ok(())
+    }
+
+    pub async fn stake_info(&self, dmc_account: &str) -> BuckyResult<()> {
+        let dmc_client = self.create_query_client(dmc_account)?;
+        let stake_info = dmc_client.get_stake_info(dmc_account).await?;
+        let weight = match dmc_client.get_maker_pool(dmc_account, dmc_account).await? {
+            Some(record) => record.get_weight()?,
+            None => 0f64
+        };
+        let staked = stake_info.get_staked()?;
+        let avg_price = dmc_client.get_pst_trans_info().await?.avg();
+        let minted_pst = dmc_client.get_pst_amount(dmc_account).await?;
+        let max_pst = self.get_storage_pst().await?.min(stake_to_pst(staked, avg_price));
+        println!("total stake DMC:{:.4}", staked);
+        println!("total weight:{}", stake_info.get_total_weight()?);
+        println!("current rate:{}", stake_info.get_current_rate()?);
+        println!("miner rate:{}", stake_info.get_miner_rate()?);
+        println!("own weight:{} stake DMC:{:.4}", weight, stake_info.weight_to_dmc(weight)?);
+        println!("minted PST:{} max mintable PST:{}", minted_pst, max_pst);
+
+        let mut state = load_unstake_state()?;
+        DMCTxTracker::new(self.dmc_sever.as_str(), DMC_TX_EXPIRE_SECONDS).poll(&mut state).await?;
+        for tx in state.txs.iter().filter(|tx| tx.payload.dmc_account.as_str() == dmc_account) {
+            let status = match &tx.state {
+                TrackedTxState::Pending | TrackedTxState::Included(_) => "pending".to_string(),
+                TrackedTxState::Irreversible(block_num) => format!("refunded in block {}", block_num),
+                TrackedTxState::Expired => "expired".to_string(),
+                TrackedTxState::Failed(e) => format!("failed {}", e),
+            };
+            println!("unstake DMC:{:.4} trx {} {}", tx.payload.dmc, tx.trx_id, status);
+        }
+        // finished requests are shown once
+        state.txs.retain(|tx| tx.payload.dmc_account.as_str() != dmc_account || !tx.is_finished());
+        save_unstake_state(&state)?;
+        Ok(())
+    }
+
+    pub async fn mint(&self, dmc_account: &str, owner_key: &str, pst_count: &str) -> BuckyResult<()> {
+        let mut max_pst = self.get_storage_pst().await?;
+
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
         let minted_pst = dmc_client.get_pst_amount(dmc_account).await?;
         let stake_info = dmc_client.get_stake_info(dmc_account).await?;
         let pst_info = dmc_client.get_pst_trans_info().await?;
-        let quantity: f64 = stake_info.total_staked.quantity.trim_end_matches("DMC").trim().parse().map_err(|e| {
-            cyfs_err!(BuckyErrorCode::Failed, "parse {} err {}", stake_info.total_staked.quantity.as_str(), e)
-        })?;
 
-        let stake_max_pst = (quantity / (2f64 * pst_info.avg())) as u64;
+        let stake_max_pst = stake_to_pst(stake_info.get_staked()?, pst_info.avg());
         if max_pst > stake_max_pst {
             max_pst = stake_max_pst;
         }
@@ -152,45 +466,108 @@ impl App {
         Ok(())
     }
 
-    pub async fn bill(&self, dmc_account: &str, private_key: &str, pst_count: &str, price: f64) -> BuckyResult<()> {
-        let tx_sender = LocalDMCTxSender::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            SimpleSignatureProvider::new(vec![private_key.to_string()])?);
-        let dmc_client = DMCClient::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            self.dmc_tracker_server.as_str(),
-            tx_sender
-        );
+    pub async fn bill(&self, dmc_account: &str, owner_key: &str, pst_count: &str, price: f64) -> BuckyResult<()> {
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
         dmc_client.bill(pst_count.to_string(), price, "".to_string()).await?;
         Ok(())
     }
 
+    pub async fn bill_list(&self, dmc_account: &str) -> BuckyResult<()> {
+        let dmc_client = self.create_query_client(dmc_account)?;
+        let bills = dmc_client.get_bill_list(dmc_account, Some(i32::MAX)).await?;
+        println!("{:<20} {:>10} {:>10} {:>12} {:<20} {:<20}", "bill_id", "matched", "unmatched", "price", "created_at", "updated_at");
+        for bill in bills.iter() {
+            println!("{:<20} {:>10} {:>10} {:>12.4} {:<20} {:<20}",
+                     bill.primary,
+                     bill.matched.get_quantity(),
+                     bill.unmatched.get_quantity(),
+                     bill.price,
+                     bill.created_at.as_str(),
+                     bill.updated_at.as_str());
+        }
+        Ok(())
+    }
+
+    // amount None withdraws all unmatched PST of the bill
+    pub async fn bill_withdraw(&self, dmc_account: &str, owner_key: &str, bill_id: u64, amount: Option<u64>) -> BuckyResult<()> {
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        if let TransResult::TransactResult(ret) = dmc_client.withdraw_bill(bill_id, amount).await? {
+            println!("transaction {} pushed", ret.transaction_id);
+        }
+        Ok(())
+    }
+
+    pub async fn bill_reprice(&self, dmc_account: &str, owner_key: &str, bill_id: u64, price: f64) -> BuckyResult<()> {
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        if let TransResult::TransactResult(ret) = dmc_client.reprice_bill(bill_id, price).await? {
+            println!("transaction {} pushed", ret.transaction_id);
+        }
+        Ok(())
+    }
+
+    // push any contract action, data is encoded with the contract abi on chain
+    pub async fn call_action(&self, dmc_account: &str, owner_key: &str, contract: &str, action: &str, data: &str, permission: &str) -> BuckyResult<()> {
+        let data: serde_json::Value = serde_json::from_str(data).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidInput, "parse action data {} err {}", data, e)
+        })?;
+        let dmc_client = self.create_dmc_client(dmc_account, owner_key).await?;
+        let raw = dmc_client.api().encode_action_data(contract, action, &data).await?;
+        let decoded = dmc_client.api().decode_action_data(contract, action, raw.as_slice()).await?;
+        println!("{}::{} by {}@{} data: {}", contract, action, dmc_account, permission, decoded);
+        if let TransResult::TransactResult(ret) = dmc_client.push_action(contract, action, permission, &data).await? {
+            println!("transaction {} pushed", ret.transaction_id);
+            // the actions it ran, the inline ones after their parent
+            if let Some(processed) = ret.processed {
+                match serde_json::from_value::<TransactionTrace>(processed) {
+                    Ok(trace) => {
+                        for action in dmc_client.api().decode_action_traces(&trace).await?.iter() {
+                            println!("{} <= {}::{} {}", action.receiver, action.account, action.name, action.data);
+                        }
+                    }
+                    Err(e) => println!("parse trace of transaction {} err {}", ret.transaction_id, e)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // build an unsigned stake/mint/bill transaction for tx_sign on an offline machine
+    pub async fn build_offline_tx(&self, kind: &str, dmc_account: &str, amount: &str, price: Option<f64>, out_file: &str) -> BuckyResult<()> {
+        let trans = match kind {
+            "stake" => stake_transaction(dmc_account, amount)?,
+            "mint" => mint_transaction(dmc_account, amount)?,
+            "bill" => {
+                let price = price.ok_or_else(|| cyfs_err!(BuckyErrorCode::InvalidInput, "bill needs a price"))?;
+                bill_transaction(dmc_account, amount.to_string(), price, "".to_string())?
+            }
+            _ => {
+                return Err(cyfs_err!(BuckyErrorCode::InvalidInput, "unknown transaction kind {}", kind));
+            }
+        };
+        let offline_tx = OfflineTransaction::build(self.dmc_sever.as_str(), trans).await?;
+        print_offline_transaction(&offline_tx)?;
+        offline_tx.save(Path::new(out_file))?;
+        println!("unsigned transaction saved to {}", out_file);
+        Ok(())
+    }
+
+    pub async fn push_offline_tx(&self, file: &str) -> BuckyResult<()> {
+        let offline_tx = OfflineTransaction::load(Path::new(file))?;
+        print_offline_transaction(&offline_tx)?;
+        let ret = offline_tx.push(self.dmc_sever.as_str()).await?;
+        println!("transaction {} pushed", ret.transaction_id);
+        Ok(())
+    }
+
     pub async fn get_info(&self, dmc_account: &str) -> BuckyResult<DmcInfo> {
-        let tx_sender = LocalDMCTxSender::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            SimpleSignatureProvider::new(vec![])?);
-        let dmc_client = DMCClient::new(
-            dmc_account,
-            self.dmc_sever.as_str(),
-            self.dmc_tracker_server.as_str(),
-            tx_sender
-        );
+        let dmc_client = self.create_query_client(dmc_account)?;
         let minted_pst = dmc_client.get_pst_amount(dmc_account).await?;
         let stake_info = dmc_client.get_stake_info(dmc_account).await?;
         let pst_info = dmc_client.get_pst_trans_info().await?;
 
-        let resp = self.stack.util().get_system_info(UtilGetSystemInfoOutputRequest {
-            common: Default::default()
-        }).await?;
-        let space = resp.info.hdd_disk_avail + resp.info.ssd_disk_avail;
-        let mut max_pst = space / (1024 * 1024 * 1024) * 85 / 100;
-        let quantity: f64 = stake_info.total_staked.quantity.trim_end_matches("DMC").trim().parse().map_err(|e| {
-            cyfs_err!(BuckyErrorCode::Failed, "parse {} err {}", stake_info.total_staked.quantity.as_str(), e)
-        })?;
-        let stake_max_pst = (quantity / (2f64 * pst_info.avg())) as u64;
+        let mut max_pst = self.get_storage_pst().await?;
+        let quantity = stake_info.get_staked()?;
+        let stake_max_pst = stake_to_pst(quantity, pst_info.avg());
         if max_pst > stake_max_pst {
             max_pst = stake_max_pst;
         }
@@ -204,3 +581,33 @@ impl App {
         })
     }
 }
+
+struct ChainLightKeys<'a, T: DMCTxSender> {
+    dmc_client: &'a DMCClient<T>,
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DMCTxSender> LightKeyChain for ChainLightKeys<'a, T> {
+    async fn get_light_keys(&self) -> BuckyResult<Vec<String>> {
+        App::get_light_keys(self.dmc_client).await
+    }
+
+    async fn set_light_keys(&self, keys: Vec<String>) -> BuckyResult<()> {
+        App::update_light_keys(self.dmc_client, keys).await
+    }
+}
+
+struct MinerLightKeys<'a> {
+    app: &'a App,
+}
+
+#[async_trait::async_trait]
+impl<'a> LightKeyMiner for MinerLightKeys<'a> {
+    async fn set_light_key(&self, dmc_account: &str, light_key: &DMCPrivateKey) -> BuckyResult<()> {
+        self.app.push_light_key(dmc_account, light_key).await
+    }
+
+    async fn get_light_key(&self, dmc_account: &str) -> BuckyResult<String> {
+        self.app.get_miner_light_key(dmc_account).await
+    }
+}
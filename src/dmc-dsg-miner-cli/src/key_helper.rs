@@ -0,0 +1,52 @@
+use std::io::Write;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use dmc_dsg_base::cyfs_err;
+
+pub const KEYSTORE_PASSPHRASE_ENV: &'static str = "DMC_KEYSTORE_PASSPHRASE";
+
+pub fn read_line(prompt: &str) -> BuckyResult<String> {
+    print!("{}", prompt);
+    std::io::stdout().flush().map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "flush stdout err {}", e)
+    })?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "read stdin err {}", e)
+    })?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+// like read_line, but the input isn't echoed
+pub fn read_secret(prompt: &str) -> BuckyResult<String> {
+    rpassword::prompt_password(prompt).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "read stdin err {}", e)
+    })
+}
+
+// anything but y cancels
+pub fn read_confirm(prompt: &str) -> BuckyResult<()> {
+    let confirm = read_line(format!("{} [y/N] ", prompt).as_str())?;
+    if confirm.as_str() != "y" && confirm.as_str() != "Y" {
+        return Err(cyfs_err!(BuckyErrorCode::Reject, "canceled"));
+    }
+    Ok(())
+}
+
+pub fn read_passphrase() -> BuckyResult<String> {
+    if let Ok(passphrase) = std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    read_secret("keystore passphrase: ")
+}
+
+pub fn read_new_passphrase() -> BuckyResult<String> {
+    if let Ok(passphrase) = std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_secret("new keystore passphrase: ")?;
+    let confirm = read_secret("repeat passphrase: ")?;
+    if passphrase != confirm {
+        return Err(cyfs_err!(BuckyErrorCode::InvalidInput, "passphrases don't match"));
+    }
+    Ok(passphrase)
+}
@@ -1,5 +1,13 @@
 mod runtime_launcher;
 mod app;
+mod key_helper;
+mod tx_helper;
+mod stake_helper;
+mod proof_helper;
 
 pub use runtime_launcher::*;
 pub use app::*;
+pub use key_helper::*;
+pub use tx_helper::*;
+pub use stake_helper::*;
+pub use proof_helper::*;
@@ -3,37 +3,209 @@ use std::sync::Arc;
 use clap::{SubCommand};
 use config::builder::DefaultState;
 use config::ConfigBuilder;
-use cyfs_base::{ObjectId};
+use cyfs_base::{BuckyErrorCode, BuckyResult, ObjectId};
 use cyfs_core::{DecApp, DecAppObj};
 use cyfs_lib::SharedCyfsStack;
 use cyfs_util::get_app_data_dir;
-use dmc_dsg_base::DMCDsgConfig;
-use dmc_dsg_miner_cli::{App, RuntimeLauncher};
+use dmc_dsg_base::{cyfs_err, DMCDsgConfig, DMCKeyStore, MerkleHashAlgorithm};
+use dmc_dsg_miner_cli::{App, read_confirm, read_secret, read_new_passphrase, read_passphrase, RuntimeLauncher, show_offline_transaction, sign_offline_transaction, verify_proof_file};
 
 #[async_std::main]
 async fn main() {
     let matches = clap::App::new("dmc-dsg-miner-cli")
+        .arg(clap::Arg::with_name("allow_raw_key").long("allow-raw-key").global(true)
+            .help("accept a raw private key where a keystore alias is expected, it shows up in shell history"))
         .subcommand(SubCommand::with_name("create_light_auth").about("Create the low-privilege private key needed to run the DSG")
             .arg(clap::Arg::with_name("dmc_account").required(true))
-            .arg(clap::Arg::with_name("private_key").required(true)))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
+            .arg(clap::Arg::with_name("light_alias").required(false).help("keystore alias for the light key, default <dmc_account>-light")))
         .subcommand(SubCommand::with_name("set_dmc_account").about("Set the dmc account and low-privilege private key needed to run the DSG")
             .arg(clap::Arg::with_name("dmc_account").required(true))
-            .arg(clap::Arg::with_name("light_private_key").required(true)))
+            .arg(clap::Arg::with_name("light_key").required(true).help("alias of the light key in keystore")))
+        .subcommand(SubCommand::with_name("rotate_light_key").about("Replace the light key of the DSG without stopping the miner")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
+            .arg(clap::Arg::with_name("light_alias").required(false).help("keystore alias for the light key, default <dmc_account>-light")))
+        .subcommand(SubCommand::with_name("check_light_auth").about("Check the light permission on chain and print the fixes")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("light_alias").required(false).help("keystore alias of the light key, default the key used by DSG")))
         .subcommand(SubCommand::with_name("stake").about("Enter the amount of DMC you want to stake")
             .arg(clap::Arg::with_name("dmc_account").required(true))
-            .arg(clap::Arg::with_name("private_key").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
             .arg(clap::Arg::with_name("amount").required(true)))
+        .subcommand(SubCommand::with_name("unstake").about("Redeem DMC from your own stake")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
+            .arg(clap::Arg::with_name("amount").required(true).help("DMC to redeem, or all")))
+        .subcommand(SubCommand::with_name("stake_info").about("Print the stake, its rates and weight, and the pending unstake requests")
+            .arg(clap::Arg::with_name("dmc_account").required(true)))
         .subcommand(SubCommand::with_name("mint").about("Enter the amount of PST you want to mint")
             .arg(clap::Arg::with_name("dmc_account").required(true))
-            .arg(clap::Arg::with_name("private_key").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
             .arg(clap::Arg::with_name("amount").required(true)))
         .subcommand(SubCommand::with_name("bill").about("Enter the amount of PST you want to sell")
             .arg(clap::Arg::with_name("dmc_account").required(true))
-            .arg(clap::Arg::with_name("private_key").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
             .arg(clap::Arg::with_name("amount").required(true))
             .arg(clap::Arg::with_name("price").required(true)))
+        .subcommand(SubCommand::with_name("bill_list").about("List the bills of the account with matched and unmatched PST")
+            .arg(clap::Arg::with_name("dmc_account").required(true)))
+        .subcommand(SubCommand::with_name("bill_withdraw").about("Withdraw the unmatched PST of a bill, the rest keeps selling at the same price")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
+            .arg(clap::Arg::with_name("bill_id").required(true))
+            .arg(clap::Arg::with_name("amount").required(false).help("PST to withdraw, default all unmatched PST which cancels the bill")))
+        .subcommand(SubCommand::with_name("bill_reprice").about("Sell the unmatched PST of a bill at a new price")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the owner key in keystore"))
+            .arg(clap::Arg::with_name("bill_id").required(true))
+            .arg(clap::Arg::with_name("price").required(true)))
+        .subcommand(SubCommand::with_name("call").about("Push any contract action, the json data is encoded with the contract abi")
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("owner_key").required(true).help("alias of the key in keystore"))
+            .arg(clap::Arg::with_name("contract").required(true))
+            .arg(clap::Arg::with_name("action").required(true))
+            .arg(clap::Arg::with_name("data").required(true).help("action data as json, e.g. {\"owner\":\"alice\"}"))
+            .arg(clap::Arg::with_name("permission").required(false).help("permission to sign with, default active")))
+        .subcommand(SubCommand::with_name("pricing_log").about("Print the latest decisions of the pricing agent of the miner")
+            .arg(clap::Arg::with_name("count").required(false).help("default 20")))
+        .subcommand(SubCommand::with_name("mint_status").about("Print the last round of the auto mint of the miner"))
+        .subcommand(SubCommand::with_name("earnings").about("Print the income, penalties and locked pledge of the miner")
+            .arg(clap::Arg::with_name("period").required(true).possible_values(&["daily", "monthly", "order"]))
+            .arg(clap::Arg::with_name("csv_file").required(false).help("write the report to the csv file instead")))
         .subcommand(SubCommand::with_name("info").about("Get info about miner")
-            .arg(clap::Arg::with_name("dmc_account").required(true))).get_matches();
+            .arg(clap::Arg::with_name("dmc_account").required(true)))
+        .subcommand(SubCommand::with_name("key_import").about("Import a private key into the encrypted keystore")
+            .arg(clap::Arg::with_name("alias").required(true)))
+        .subcommand(SubCommand::with_name("key_list").about("List the keys in the keystore"))
+        .subcommand(SubCommand::with_name("key_export").about("Print the private key of an alias")
+            .arg(clap::Arg::with_name("alias").required(true)))
+        .subcommand(SubCommand::with_name("key_rotate").about("Replace the key of an alias with a new one, the old key is kept as backup")
+            .arg(clap::Arg::with_name("alias").required(true)))
+        .subcommand(SubCommand::with_name("tx_build").about("Build an unsigned stake/mint/bill transaction and save it for offline signing")
+            .arg(clap::Arg::with_name("kind").required(true).possible_values(&["stake", "mint", "bill"]))
+            .arg(clap::Arg::with_name("dmc_account").required(true))
+            .arg(clap::Arg::with_name("out_file").required(true))
+            .arg(clap::Arg::with_name("amount").required(true))
+            .arg(clap::Arg::with_name("price").required(false).help("price of bill")))
+        .subcommand(SubCommand::with_name("tx_show").about("Print the actions of a transaction file")
+            .arg(clap::Arg::with_name("file").required(true)))
+        .subcommand(SubCommand::with_name("tx_sign").about("Sign a transaction file with a key in keystore, can run offline")
+            .arg(clap::Arg::with_name("file").required(true))
+            .arg(clap::Arg::with_name("key").required(true).help("alias of the key in keystore")))
+        .subcommand(SubCommand::with_name("tx_push").about("Broadcast a signed transaction file")
+            .arg(clap::Arg::with_name("file").required(true)))
+        .subcommand(SubCommand::with_name("verify_proof").about("Verify a piece proof against the challenge of the order on chain")
+            .arg(clap::Arg::with_name("order_id").required(true))
+            .arg(clap::Arg::with_name("data_id").required(true))
+            .arg(clap::Arg::with_name("file").required(true).help("json of the arbitration action or a raw encoded proof"))
+            .arg(clap::Arg::with_name("hash_algorithm").required(false).help("merkle hash id of the contract, default 0 sha256"))).get_matches();
+
+    let mut builder = ConfigBuilder::<DefaultState>::default();
+    builder = builder.set_default("dmc_server", "http://explorer.dmctech.io").unwrap();
+    builder = builder.set_default("dmc_tracker_server", "http://tracker.dmctech.io").unwrap();
+
+    let data_dir = get_app_data_dir(DMCDsgConfig::APP_NAME);
+    let config_path = data_dir.join("config.toml");
+    if config_path.exists() {
+        builder = builder.add_source(config::File::new(&config_path.display().to_string(), config::FileFormat::Toml));
+    }
+    let config = builder.build().unwrap();
+
+    match matches.subcommand() {
+        ("key_import", matches) => {
+            let alias = matches.as_ref().unwrap().value_of("alias").unwrap();
+            if let Err(e) = key_import(alias) {
+                println!("import key err {}", e);
+            }
+            return;
+        }
+        ("key_list", _) => {
+            match DMCKeyStore::open_default().and_then(|key_store| key_store.list()) {
+                Ok(list) => {
+                    for info in list.iter() {
+                        println!("{} {}", info.alias, info.public_key);
+                    }
+                }
+                Err(e) => {
+                    println!("list key err {}", e);
+                }
+            }
+            return;
+        }
+        ("key_export", matches) => {
+            let alias = matches.as_ref().unwrap().value_of("alias").unwrap();
+            match read_passphrase().and_then(|passphrase| DMCKeyStore::open_default()?.export(alias, passphrase.as_str())) {
+                Ok(private_key) => {
+                    println!("{}", private_key);
+                }
+                Err(e) => {
+                    println!("export key err {}", e);
+                }
+            }
+            return;
+        }
+        ("key_rotate", matches) => {
+            let alias = matches.as_ref().unwrap().value_of("alias").unwrap();
+            println!("the key of {} is replaced here only, the chain and the miner keep the old key until they're updated.", alias);
+            println!("for the light key use rotate_light_key, it updates both.");
+            match read_confirm("rotate?").and_then(|_| read_passphrase()).and_then(|passphrase| DMCKeyStore::open_default()?.rotate(alias, passphrase.as_str())) {
+                Ok(public_key) => {
+                    println!("new public key of {} is {}", alias, public_key);
+                }
+                Err(e) => {
+                    println!("rotate key err {}", e);
+                }
+            }
+            return;
+        }
+        ("tx_show", matches) => {
+            let file = matches.as_ref().unwrap().value_of("file").unwrap();
+            if let Err(e) = show_offline_transaction(file) {
+                println!("show transaction err {}", e);
+            }
+            return;
+        }
+        ("tx_sign", matches) => {
+            let file = matches.as_ref().unwrap().value_of("file").unwrap();
+            let key = matches.as_ref().unwrap().value_of("key").unwrap();
+            if let Err(e) = sign_offline_transaction(file, key).await {
+                println!("sign transaction err {}", e);
+            }
+            return;
+        }
+        ("verify_proof", matches) => {
+            let order_id = matches.as_ref().unwrap().value_of("order_id").unwrap();
+            let data_id = match matches.as_ref().unwrap().value_of("data_id").unwrap().parse::<u64>() {
+                Ok(data_id) => data_id,
+                Err(e) => {
+                    println!("verify proof err data_id must be a piece index, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let file = matches.as_ref().unwrap().value_of("file").unwrap();
+            let hash_algorithm = matches.as_ref().unwrap().value_of("hash_algorithm").unwrap_or("0");
+            let algorithm = match hash_algorithm.parse::<u32>().map_err(|e| {
+                cyfs_err!(BuckyErrorCode::InvalidInput, "parse hash_algorithm {} err {}", hash_algorithm, e)
+            }).and_then(|id| MerkleHashAlgorithm::from_id(id)) {
+                Ok(algorithm) => algorithm,
+                Err(e) => {
+                    println!("verify proof err {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match verify_proof_file(config.get_string("dmc_server").unwrap().as_str(), config.get_string("dmc_tracker_server").unwrap().as_str(), order_id, data_id, file, algorithm).await {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    println!("verify proof err {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
 
     if cfg!(not(debug_assertions)) {
         async_std::task::block_on(RuntimeLauncher::launch());
@@ -53,68 +225,205 @@ async fn main() {
         .build()
         .start();
 
-    let mut builder = ConfigBuilder::<DefaultState>::default();
-    builder = builder.set_default("dmc_server", "http://explorer.dmctech.io").unwrap();
-    builder = builder.set_default("dmc_tracker_server", "http://tracker.dmctech.io").unwrap();
-
-    let data_dir = get_app_data_dir(DMCDsgConfig::APP_NAME);
-    let config_path = data_dir.join("config.toml");
-    if config_path.exists() {
-        builder = builder.add_source(config::File::new(&config_path.display().to_string(), config::FileFormat::Toml));
-    }
-    let config = builder.build().unwrap();
-
     let dec_id = DecApp::generate_id(ObjectId::from_str(DMCDsgConfig::PUB_PEOPLE_ID).unwrap(), DMCDsgConfig::PRODUCT_NAME);
     log::info!("dec_id:{} product_name:{}", dec_id.to_string(), DMCDsgConfig::PRODUCT_NAME);
     let object_stack = Arc::new(SharedCyfsStack::open_runtime(Some(dec_id.clone())).await.unwrap());
     object_stack.wait_online(None).await.unwrap();
 
-    let app = App::new(object_stack, dec_id, config.get_string("dmc_server").unwrap(), config.get_string("dmc_tracker_server").unwrap()).await.unwrap();
+    let app = App::new(object_stack, dec_id, config.get_string("dmc_server").unwrap(), config.get_string("dmc_tracker_server").unwrap(), matches.is_present("allow_raw_key")).await.unwrap();
     match matches.subcommand() {
         ("create_light_auth", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
-            let private_key = matches.as_ref().unwrap().value_of("private_key").unwrap();
-            if let Err(e) = app.create_light_auth(dmc_account, private_key).await {
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let light_alias = matches.as_ref().unwrap().value_of("light_alias").map(|v| v.to_string()).unwrap_or(format!("{}-light", dmc_account));
+            if let Err(e) = app.create_light_auth(dmc_account, owner_key, light_alias.as_str()).await {
                 log::error!("create light auth err {}", e);
                 println!("create light auth err {}", e);
             }
         }
         ("set_dmc_account", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
-            let private_key = matches.as_ref().unwrap().value_of("light_private_key").unwrap();
-            if let Err(e) = app.set_dmc_account(dmc_account, private_key).await {
+            let light_key = matches.as_ref().unwrap().value_of("light_key").unwrap();
+            if let Err(e) = app.set_dmc_account(dmc_account, light_key).await {
                 log::error!("create light auth err {}", e);
                 println!("create light auth err {}", e);
             }
         }
+        ("rotate_light_key", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let light_alias = matches.as_ref().unwrap().value_of("light_alias").map(|v| v.to_string()).unwrap_or(format!("{}-light", dmc_account));
+            if let Err(e) = app.rotate_light_key(dmc_account, owner_key, light_alias.as_str()).await {
+                log::error!("rotate light key err {}", e);
+                println!("rotate light key err {}", e);
+            }
+        }
+        ("check_light_auth", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let light_alias = matches.as_ref().unwrap().value_of("light_alias");
+            if let Err(e) = app.check_light_auth(dmc_account, light_alias).await {
+                log::error!("check light auth err {}", e);
+                println!("check light auth err {}", e);
+            }
+        }
         ("stake", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
-            let private_key = matches.as_ref().unwrap().value_of("private_key").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
             let amount = matches.as_ref().unwrap().value_of("amount").unwrap();
-            if let Err(e) = app.stake(dmc_account, private_key, amount).await {
+            if let Err(e) = app.stake(dmc_account, owner_key, amount).await {
                 log::error!("stack err {}", e);
                 println!("stack err {}", e);
             }
         }
+        ("unstake", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let amount = matches.as_ref().unwrap().value_of("amount").unwrap();
+            if let Err(e) = app.unstake(dmc_account, owner_key, amount).await {
+                log::error!("unstake err {}", e);
+                println!("unstake err {}", e);
+            }
+        }
+        ("stake_info", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            if let Err(e) = app.stake_info(dmc_account).await {
+                log::error!("get stake info err {}", e);
+                println!("get stake info err {}", e);
+            }
+        }
         ("mint", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
-            let private_key = matches.as_ref().unwrap().value_of("private_key").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
             let amount = matches.as_ref().unwrap().value_of("amount").unwrap();
-            if let Err(e) = app.mint(dmc_account, private_key, amount).await {
+            if let Err(e) = app.mint(dmc_account, owner_key, amount).await {
                 log::error!("mint err {}", e);
                 println!("mint err {}", e);
             }
         }
         ("bill", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
-            let private_key = matches.as_ref().unwrap().value_of("private_key").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
             let amount = matches.as_ref().unwrap().value_of("amount").unwrap();
             let price = matches.as_ref().unwrap().value_of("price").unwrap();
-            if let Err(e) = app.bill(dmc_account, private_key, amount, price.parse().unwrap()).await {
+            if let Err(e) = app.bill(dmc_account, owner_key, amount, price.parse().unwrap()).await {
                 log::error!("bill err {}", e);
                 println!("bill err {}", e);
             }
         }
+        ("bill_list", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            if let Err(e) = app.bill_list(dmc_account).await {
+                log::error!("list bill err {}", e);
+                println!("list bill err {}", e);
+            }
+        }
+        ("bill_withdraw", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let bill_id = match matches.as_ref().unwrap().value_of("bill_id").unwrap().parse::<u64>() {
+                Ok(bill_id) => bill_id,
+                Err(e) => {
+                    println!("withdraw bill err bill_id must be a number, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let amount = match matches.as_ref().unwrap().value_of("amount").map(|amount| amount.parse::<u64>()).transpose() {
+                Ok(amount) => amount,
+                Err(e) => {
+                    println!("withdraw bill err amount must be a whole number of PST, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = app.bill_withdraw(dmc_account, owner_key, bill_id, amount).await {
+                log::error!("withdraw bill err {}", e);
+                println!("withdraw bill err {}", e);
+            }
+        }
+        ("bill_reprice", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let bill_id = match matches.as_ref().unwrap().value_of("bill_id").unwrap().parse::<u64>() {
+                Ok(bill_id) => bill_id,
+                Err(e) => {
+                    println!("reprice bill err bill_id must be a number, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let price = match matches.as_ref().unwrap().value_of("price").unwrap().parse::<f64>() {
+                Ok(price) => price,
+                Err(e) => {
+                    println!("reprice bill err price must be a number, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = app.bill_reprice(dmc_account, owner_key, bill_id, price).await {
+                log::error!("reprice bill err {}", e);
+                println!("reprice bill err {}", e);
+            }
+        }
+        ("call", matches) => {
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let owner_key = matches.as_ref().unwrap().value_of("owner_key").unwrap();
+            let contract = matches.as_ref().unwrap().value_of("contract").unwrap();
+            let action = matches.as_ref().unwrap().value_of("action").unwrap();
+            let data = matches.as_ref().unwrap().value_of("data").unwrap();
+            let permission = matches.as_ref().unwrap().value_of("permission").unwrap_or("active");
+            if let Err(e) = app.call_action(dmc_account, owner_key, contract, action, data, permission).await {
+                log::error!("call {}::{} err {}", contract, action, e);
+                println!("call {}::{} err {}", contract, action, e);
+            }
+        }
+        ("tx_build", matches) => {
+            let kind = matches.as_ref().unwrap().value_of("kind").unwrap();
+            let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
+            let out_file = matches.as_ref().unwrap().value_of("out_file").unwrap();
+            let amount = matches.as_ref().unwrap().value_of("amount").unwrap();
+            let price = match matches.as_ref().unwrap().value_of("price").map(|price| price.parse::<f64>()).transpose() {
+                Ok(price) => price,
+                Err(e) => {
+                    println!("build transaction err price must be a number, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = app.build_offline_tx(kind, dmc_account, amount, price, out_file).await {
+                log::error!("build transaction err {}", e);
+                println!("build transaction err {}", e);
+            }
+        }
+        ("tx_push", matches) => {
+            let file = matches.as_ref().unwrap().value_of("file").unwrap();
+            if let Err(e) = app.push_offline_tx(file).await {
+                log::error!("push transaction err {}", e);
+                println!("push transaction err {}", e);
+            }
+        }
+        ("pricing_log", matches) => {
+            let count = match matches.as_ref().and_then(|matches| matches.value_of("count")).map(|count| count.parse::<usize>()).transpose() {
+                Ok(count) => count.unwrap_or(20),
+                Err(e) => {
+                    println!("get pricing log err count must be a whole number, {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = app.pricing_log(count).await {
+                log::error!("get pricing log err {}", e);
+                println!("get pricing log err {}", e);
+            }
+        }
+        ("mint_status", _) => {
+            if let Err(e) = app.mint_status().await {
+                log::error!("get mint status err {}", e);
+                println!("get mint status err {}", e);
+            }
+        }
+        ("earnings", matches) => {
+            let period = matches.as_ref().unwrap().value_of("period").unwrap();
+            let csv_file = matches.as_ref().unwrap().value_of("csv_file");
+            if let Err(e) = app.earnings(period, csv_file).await {
+                log::error!("get earnings err {}", e);
+                println!("get earnings err {}", e);
+            }
+        }
         ("info", matches) => {
             let dmc_account = matches.as_ref().unwrap().value_of("dmc_account").unwrap();
             match app.get_info(dmc_account).await {
@@ -134,3 +443,12 @@ async fn main() {
         _ => {}
     }
 }
+
+fn key_import(alias: &str) -> BuckyResult<()> {
+    let key_store = DMCKeyStore::open_default()?;
+    let private_key = read_secret("private key: ")?;
+    let passphrase = read_new_passphrase()?;
+    let public_key = key_store.import(alias, private_key.as_str(), passphrase.as_str())?;
+    println!("imported {} {}", alias, public_key);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use dmc_dsg_base::{cyfs_err, DMCClient, LocalDMCTxSender, MerkleHashAlgorithm, PieceProof, SimpleSignatureProvider, verify_challenge_proof};
+
+// only reads the challenge row on chain, consumers and arbitrators can run it without a cyfs runtime
+pub async fn verify_proof_file(dmc_server: &str, dmc_tracker_server: &str, order_id: &str, data_id: u64, path: &str, algorithm: MerkleHashAlgorithm) -> BuckyResult<bool> {
+    let data = std::fs::read(Path::new(path)).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "read {} err {}", path, e)
+    })?;
+    let proof = PieceProof::decode(data_id, data.as_slice())?;
+
+    let tx_sender = LocalDMCTxSender::new(
+        "",
+        dmc_server,
+        SimpleSignatureProvider::new(vec![])?);
+    let dmc_client = DMCClient::new("", dmc_server, dmc_tracker_server, tx_sender);
+    let challenge_ret = dmc_client.get_challenge_info(order_id, Some(1)).await?;
+    let challenge = challenge_ret.rows.get(0).ok_or_else(|| {
+        cyfs_err!(BuckyErrorCode::NotFound, "order {} has no challenge on chain", order_id)
+    })?;
+    if challenge.data_id != data_id {
+        println!("warning: the challenge {} of order {} is for data_id {}", challenge.challenge_times, order_id, challenge.data_id);
+    }
+
+    let report = verify_challenge_proof(challenge, &proof, algorithm)?;
+    println!("piece:{} path:{}", proof.piece_index, proof.path_list.len());
+    println!("root:{} chain merkle_root:{} matched:{}", report.root.to_string(), challenge.merkle_root, report.root_matched);
+    println!("data_id:{} data_block_count:{} matched:{}", challenge.data_id, challenge.data_block_count, report.index_matched);
+    println!("path len matched:{}", report.depth_matched);
+    println!("piece answers the challenge hash:{}", report.challenge_matched);
+    println!("proof is {}", if report.is_valid() { "valid" } else { "invalid" });
+    Ok(report.is_valid())
+}
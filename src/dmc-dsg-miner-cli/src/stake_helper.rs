@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+use cyfs_base::{BuckyErrorCode, BuckyResult};
+use dmc_dsg_base::{cyfs_err, DMCDsgConfig, DMCTxTrackerState};
+use serde::{Serialize, Deserialize};
+
+// what an unstake request is expected to give back, kept until its transaction is final
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UnstakeRefund {
+    pub dmc_account: String,
+    pub rate: f64,
+    pub dmc: f64,
+}
+
+pub type UnstakeState = DMCTxTrackerState<UnstakeRefund>;
+
+fn unstake_state_path() -> PathBuf {
+    cyfs_util::get_app_data_dir(DMCDsgConfig::APP_NAME).join("unstake.json")
+}
+
+pub fn load_unstake_state() -> BuckyResult<UnstakeState> {
+    let path = unstake_state_path();
+    if !path.exists() {
+        return Ok(UnstakeState::default());
+    }
+    let content = std::fs::read_to_string(path.as_path()).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", path.display(), e)
+    })?;
+    serde_json::from_str(content.as_str()).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse {} failed {}", path.display(), e)
+    })
+}
+
+pub fn save_unstake_state(state: &UnstakeState) -> BuckyResult<()> {
+    let path = unstake_state_path();
+    let content = serde_json::to_string_pretty(state).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::Failed, "encode unstake state failed {}", e)
+    })?;
+    std::fs::write(path.as_path(), content).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::IoError, "write {} failed {}", path.display(), e)
+    })
+}
+
+// the rate of the owner's share an unstake of amount DMC takes, "all" takes the whole share
+pub fn unstake_rate(amount: &str, share: f64) -> BuckyResult<f64> {
+    if share <= 0f64 {
+        return Err(cyfs_err!(BuckyErrorCode::NotFound, "nothing staked"));
+    }
+    if amount == "all" {
+        return Ok(1f64);
+    }
+    let amount: f64 = amount.parse().map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidInput, "parse {} err {}", amount, e)
+    })?;
+    if amount <= 0f64 {
+        return Err(cyfs_err!(BuckyErrorCode::InvalidInput, "unstake amount {} must be positive", amount));
+    }
+    if amount > share {
+        return Err(cyfs_err!(BuckyErrorCode::InvalidInput, "only {:.4} DMC staked", share));
+    }
+    Ok(amount / share)
+}
+
+#[cfg(test)]
+mod test_stake_helper {
+    use cyfs_base::BuckyErrorCode;
+    use crate::*;
+
+    #[test]
+    fn test_unstake_rate() {
+        assert_eq!(unstake_rate("0", 0f64).unwrap_err().code(), BuckyErrorCode::NotFound);
+        assert_eq!(unstake_rate("all", 0f64).unwrap_err().code(), BuckyErrorCode::NotFound);
+        assert_eq!(unstake_rate("0", 8f64).unwrap_err().code(), BuckyErrorCode::InvalidInput);
+        assert_eq!(unstake_rate("-1", 8f64).unwrap_err().code(), BuckyErrorCode::InvalidInput);
+        assert_eq!(unstake_rate("ten", 8f64).unwrap_err().code(), BuckyErrorCode::InvalidInput);
+
+        assert_eq!(unstake_rate("2", 8f64).unwrap(), 0.25);
+        // the full stake and "all" both take the whole share
+        assert_eq!(unstake_rate("8", 8f64).unwrap(), 1f64);
+        assert_eq!(unstake_rate("all", 8f64).unwrap(), 1f64);
+        assert_eq!(unstake_rate("8.0001", 8f64).unwrap_err().code(), BuckyErrorCode::InvalidInput);
+    }
+}
@@ -0,0 +1,31 @@
+use std::path::Path;
+use cyfs_base::BuckyResult;
+use dmc_dsg_base::{DMCKeyStore, KeystoreSignatureProvider, OfflineTransaction};
+use crate::{read_confirm, read_passphrase};
+
+pub fn print_offline_transaction(trans: &OfflineTransaction) -> BuckyResult<()> {
+    for line in trans.decode()?.iter() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+pub fn show_offline_transaction(path: &str) -> BuckyResult<()> {
+    print_offline_transaction(&OfflineTransaction::load(Path::new(path))?)
+}
+
+// runs on the offline machine, only the keystore is touched
+pub async fn sign_offline_transaction(path: &str, key_alias: &str) -> BuckyResult<()> {
+    let path = Path::new(path);
+    let mut trans = OfflineTransaction::load(path)?;
+    print_offline_transaction(&trans)?;
+    read_confirm("sign this transaction?")?;
+
+    let key_store = DMCKeyStore::open_default()?;
+    let passphrase = read_passphrase()?;
+    let provider = KeystoreSignatureProvider::from_key_store(&key_store, vec![key_alias.to_string()], passphrase.as_str())?;
+    let count = trans.sign(&provider).await?;
+    trans.save(path)?;
+    println!("added {} signatures to {}", count, path.display());
+    Ok(())
+}
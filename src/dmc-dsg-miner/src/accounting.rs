@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use cyfs_base::*;
+use crate::*;
+use dmc_dsg_base::*;
+
+const LEDGER_SYNC_INTERVAL: u64 = 3600;
+const ORDER_PAGE_SIZE: u32 = 100;
+
+pub async fn load_ledger_state<CONN: ContractMetaStore>(meta_store: &dyn MetaStore<CONN>) -> BuckyResult<LedgerState> {
+    let state = meta_store.get_setting("dmc_ledger_state", "").await?;
+    if state.is_empty() {
+        return Ok(LedgerState::default());
+    }
+    serde_json::from_str(state.as_str()).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse ledger state err {}", e)
+    })
+}
+
+// keeps a local ledger of what the miner earned, lost and has locked in its orders
+pub struct Accounting<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> {
+    dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+    meta_store: Arc<dyn MetaStore<CONN>>,
+}
+pub type AccountingRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> = Arc<Accounting<STACK, CONN, CHUNKSTORE, DMCTXSENDER>>;
+
+impl<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> Accounting<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+    pub fn new(
+        dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+        meta_store: Arc<dyn MetaStore<CONN>>,
+    ) -> AccountingRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+        AccountingRef::new(Self {
+            dmc,
+            meta_store
+        })
+    }
+
+    pub fn start(self: &AccountingRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>) {
+        let this = self.clone();
+        #[cfg(not(feature = "no_dmc"))]
+        async_std::task::spawn(async move {
+            loop {
+                if let Err(e) = this.sync().await {
+                    log::error!("sync ledger err {}", e);
+                }
+                async_std::task::sleep(std::time::Duration::from_secs(LEDGER_SYNC_INTERVAL)).await;
+            }
+        });
+    }
+
+    async fn get_order_ids(&self) -> BuckyResult<Vec<String>> {
+        let mut order_ids = Vec::new();
+        let mut skip = 0;
+        loop {
+            let orders = self.dmc.get_miner_orders(skip, ORDER_PAGE_SIZE).await?;
+            let count = orders.len() as u32;
+            order_ids.extend(orders.into_iter().map(|order| order.id));
+            if count < ORDER_PAGE_SIZE {
+                break;
+            }
+            skip += count;
+        }
+        Ok(order_ids)
+    }
+
+    pub async fn sync(&self) -> BuckyResult<LedgerState> {
+        let mut state = load_ledger_state(self.meta_store.as_ref()).await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        // orders the tracker no longer lists are checked too, their pledge may still be locked
+        let mut order_ids: HashSet<String> = self.get_order_ids().await?.into_iter().collect();
+        order_ids.extend(state.orders.iter().filter(|(_, order)| !order.is_end()).map(|(order_id, _)| order_id.clone()));
+
+        for order_id in order_ids.iter() {
+            let cur = match self.dmc.get_chain_order(order_id.as_str()).await? {
+                Some(order) => {
+                    let challenge = self.dmc.get_challenge(order_id.as_str()).await?;
+                    OrderSnapshot::new(&order, challenge.as_ref())?
+                }
+                None => match state.orders.get(order_id) {
+                    Some(prev) if !prev.is_end() => prev.closed(),
+                    _ => continue,
+                }
+            };
+            state.update_order(order_id.as_str(), cur, now);
+        }
+
+        // a miner without stake still gets its order ledger
+        match self.dmc.get_stake_info().await {
+            Ok(stake_info) => {
+                let staked = stake_info.get_staked()?;
+                let total_weight = stake_info.get_total_weight()?;
+                state.update_stake(staked, total_weight, stake_info.current_rate, now);
+            }
+            Err(e) => log::error!("get stake info err {}", e),
+        }
+        state.compact(now);
+        state.updated_at = now;
+        self.meta_store.set_setting("dmc_ledger_state".to_string(), serde_json::to_string(&state).unwrap()).await?;
+        Ok(state)
+    }
+}
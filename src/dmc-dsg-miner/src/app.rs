@@ -5,13 +5,33 @@ use cyfs_base::*;
 use cyfs_core::{DecApp, DecAppObj};
 use cyfs_dsg_client::{DsgContractState, DsgContractStateObjectRef};
 use cyfs_lib::SharedCyfsStack;
-use dmc_dsg_base::{Setting, SettingRef, DMCDsgConfig, CyfsPath, JSONObject, DSGJSON, CyfsClient, CyfsNOC};
-use crate::{ContractMetaStore, CyfsStackFileDownloader, CyfsStackMetaStore, DMC, DmcDsgMiner, MetaStore, MinerStat, NocChunkStore, OodMiner, RemoteDMCTxSender, RemoteProtocol};
+use dmc_dsg_base::{Setting, SettingRef, DMCDsgConfig, CyfsPath, JSONObject, DSGJSON, CyfsClient, CyfsNOC, DMCRpc, audit_light_permission, EarningsPeriod, EarningsReport, JsonProtocol, SetDMCAccount, LightKeyHandler, LightKeySigner};
+use crate::{ContractMetaStore, CyfsStackFileDownloader, CyfsStackMetaStore, DMC, DmcDsgMiner, MetaStore, MinerStat, OodChunkStore, OodMiner, MinerTxSender, SignerConfig, RemoteProtocol, PricingAgent, PricingConfig, PricingDecision, load_pricing_decisions, AutoMint, AutoMintConfig, AutoMintState, load_auto_mint_state, Accounting, load_ledger_state, SelfAudit, SelfAuditConfig};
+
+// hands the light key to the DMC DSG service, which signs the miner's transactions
+pub struct RemoteLightKeySigner {
+    stack: Arc<SharedCyfsStack>,
+    dec_id: ObjectId,
+    dmc_dsg_dec_id: ObjectId,
+}
+
+#[async_trait::async_trait]
+impl LightKeySigner for RemoteLightKeySigner {
+    async fn set_dmc_account(&self, req: &SetDMCAccount) -> BuckyResult<()> {
+        let device = self.stack.local_device();
+        let local_id = device.desc().object_id();
+        let owner_id = device.desc().owner().as_ref().unwrap().clone();
+        let req_path = CyfsPath::new(local_id, self.dmc_dsg_dec_id.clone(), "dmc_account_commands").to_path();
+        let req = JSONObject::new(self.dec_id.clone(), owner_id, JsonProtocol::SetDMCAccount as u16, req)?;
+        self.stack.put_object_with_resp2::<JSONObject>(req_path.as_str(), req.desc().object_id(), req.to_vec()?).await?;
+        Ok(())
+    }
+}
 
 pub struct App {
     setting: SettingRef,
     chunk_meta: Arc<CyfsStackMetaStore>,
-    raw_data_store: Arc<NocChunkStore>,
+    raw_data_store: Arc<OodChunkStore>,
     stack: Arc<SharedCyfsStack>,
     miner: Mutex<Option<OodMiner>>,
     dmc_server: String,
@@ -19,6 +39,11 @@ pub struct App {
     dec_id: ObjectId,
     dmc_dsg_dec_id: ObjectId,
     challenge_check_interval: u64,
+    pricing: PricingConfig,
+    auto_mint: AutoMintConfig,
+    self_audit: SelfAuditConfig,
+    signer: SignerConfig,
+    light_key: LightKeyHandler<RemoteLightKeySigner>,
 }
 pub type AppRef = Arc<App>;
 
@@ -26,16 +51,31 @@ impl App {
     pub async fn new(
         stack: Arc<SharedCyfsStack>,
         chunk_meta: Arc<CyfsStackMetaStore>,
-        raw_data_store: Arc<NocChunkStore>,
+        raw_data_store: Arc<OodChunkStore>,
         dmc_server: String,
         dmc_tracker_server: String,
         dec_id: ObjectId,
         challenge_check_interval: u64,
+        pricing: PricingConfig,
+        auto_mint: AutoMintConfig,
+        self_audit: SelfAuditConfig,
+        signer: SignerConfig,
     ) -> BuckyResult<AppRef> {
         let setting = Setting::new(stack.clone());
         setting.load().await?;
 
         let dmc_dsg_dec_id = DecApp::generate_id(ObjectId::from_str(DMCDsgConfig::PUB_PEOPLE_ID).unwrap(), "DMC DSG service");
+        let light_key_account = setting.get_setting("light_key_account", "");
+        let current_light_key = if light_key_account.is_empty() {
+            None
+        } else {
+            Some((light_key_account, setting.get_setting("light_key", "")))
+        };
+        let light_key = LightKeyHandler::new(RemoteLightKeySigner {
+            stack: stack.clone(),
+            dec_id: dec_id.clone(),
+            dmc_dsg_dec_id: dmc_dsg_dec_id.clone(),
+        }, current_light_key);
         Ok(AppRef::new(Self {
             setting,
             chunk_meta,
@@ -46,7 +86,12 @@ impl App {
             dmc_tracker_server,
             dec_id,
             dmc_dsg_dec_id,
-            challenge_check_interval
+            challenge_check_interval,
+            pricing,
+            auto_mint,
+            self_audit,
+            signer,
+            light_key
         }))
     }
 
@@ -103,7 +148,13 @@ impl App {
         loop {
             let dmc_account = self.get_dmc_account().await?;
             if dmc_account.is_some() {
-                let dmc_sender = RemoteDMCTxSender::new(self.stack.clone(), self.dmc_dsg_dec_id.clone());
+                self.check_light_permission(dmc_account.as_ref().unwrap().as_str()).await;
+                let dmc_sender = MinerTxSender::new(
+                    &self.signer,
+                    self.stack.clone(),
+                    self.dmc_dsg_dec_id.clone(),
+                    dmc_account.as_ref().unwrap().as_str(),
+                    self.dmc_server.as_str()).await?;
                 let dmc = DMC::new(
                     self.stack.clone(),
                     self.dec_id.clone(),
@@ -138,6 +189,20 @@ impl App {
                 miner.start_chunk_sync().await?;
                 miner.start_proof_resp().await;
                 miner.start_contract_end_check().await;
+                PricingAgent::new(
+                    self.pricing.clone(),
+                    dmc.clone(),
+                    self.chunk_meta.clone(),
+                    self.dmc_server.as_str(),
+                    dmc_account.as_ref().unwrap().as_str()).start();
+                AutoMint::new(
+                    self.auto_mint.clone(),
+                    dmc.clone(),
+                    self.chunk_meta.clone(),
+                    self.dmc_server.as_str(),
+                    dmc_account.as_ref().unwrap().as_str()).start();
+                Accounting::new(dmc.clone(), self.chunk_meta.clone()).start();
+                SelfAudit::new(self.self_audit.clone(), dmc.clone(), self.chunk_meta.clone()).start();
 
                 let service = OodMiner::new(self.stack.clone(), miner.clone()).await?;
                 *self.miner.lock().unwrap() = Some(service);
@@ -148,6 +213,36 @@ impl App {
         Ok(())
     }
 
+    // misconfigured light permission only shows up as failed transactions when a challenge comes,
+    // so report it with the fixes at startup, the miner keeps running either way
+    pub async fn check_light_permission(&self, dmc_account: &str) -> bool {
+        let permissions = match DMCRpc::new(self.dmc_server.as_str()).get_permissions(dmc_account).await {
+            Ok(permissions) => permissions,
+            Err(e) => {
+                log::error!("get permissions of {} err {}", dmc_account, e);
+                return false;
+            }
+        };
+        // the key last pushed to the signer, checked against the keys on chain too
+        let light_key = if self.setting.get_setting("light_key_account", "") == dmc_account {
+            Some(self.setting.get_setting("light_key", ""))
+        } else {
+            None
+        };
+        match audit_light_permission(&permissions, light_key.as_ref().map(|key| key.as_str()).filter(|key| !key.is_empty())) {
+            Ok(issues) => {
+                for issue in issues.iter() {
+                    log::error!("light permission of {}: {}, fix: {}", dmc_account, issue, issue.fix(dmc_account));
+                }
+                issues.is_empty()
+            }
+            Err(e) => {
+                log::error!("audit light permission of {} err {}", dmc_account, e);
+                false
+            }
+        }
+    }
+
     async fn get_dmc_account(&self) -> BuckyResult<Option<String>> {
         let device = self.stack.local_device();
         let local_id = device.desc().object_id();
@@ -185,6 +280,36 @@ impl App {
         }
     }
 
+    // the latest decisions of the pricing agent, oldest first
+    pub async fn get_pricing_decisions(&self, count: usize) -> BuckyResult<Vec<PricingDecision>> {
+        let mut decisions = load_pricing_decisions(self.chunk_meta.as_ref()).await?;
+        if decisions.len() > count {
+            decisions.drain(..decisions.len() - count);
+        }
+        Ok(decisions)
+    }
+
+    pub async fn get_auto_mint_state(&self) -> BuckyResult<AutoMintState> {
+        load_auto_mint_state(self.chunk_meta.as_ref()).await
+    }
+
+    pub async fn get_earnings_report(&self, period: EarningsPeriod) -> BuckyResult<EarningsReport> {
+        Ok(load_ledger_state(self.chunk_meta.as_ref()).await?.report(period))
+    }
+
+    // the light key goes to the signer through the miner, so the miner knows which key it signs with
+    pub async fn set_dmc_account(&self, req: SetDMCAccount) -> BuckyResult<()> {
+        let (account, public_key) = self.light_key.on_set_dmc_account(&req).await?;
+        self.setting.set_setting("light_key_account".to_string(), account);
+        self.setting.set_setting("light_key".to_string(), public_key);
+        self.setting.save().await?;
+        Ok(())
+    }
+
+    pub fn get_dmc_public_key(&self, dmc_account: &str) -> BuckyResult<String> {
+        self.light_key.on_get_dmc_key(dmc_account)
+    }
+
     async fn set_miner_dec_id(&self) -> BuckyResult<()> {
         let device = self.stack.local_device();
         let local_id = device.desc().object_id();
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use crate::*;
+use dmc_dsg_base::*;
+
+const DAY_SECONDS: u64 = 24 * 3600;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoMintConfig {
+    pub enable: bool,
+    // only log and record what could be minted
+    pub recommend_only: bool,
+    // remote signer holding the owner key, http(s)://host:port or unix:/path
+    pub signer: String,
+    // most bytes the chunk store offers to orders, 0 for the whole disk
+    pub store_capacity: u64,
+    // 0 for no limit
+    pub max_pst_per_day: u64,
+    pub max_total_pst: u64,
+    // smaller growths wait for the next round
+    pub min_mint_pst: u64,
+    pub check_interval: u64,
+}
+
+impl Default for AutoMintConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            recommend_only: true,
+            signer: "".to_string(),
+            store_capacity: 0,
+            max_pst_per_day: 100,
+            max_total_pst: 0,
+            min_mint_pst: 1,
+            check_interval: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct AutoMintState {
+    pub day_start: u64,
+    pub minted_today: u64,
+    // what the last round saw and did
+    pub last_check: u64,
+    pub minted: u64,
+    pub storage_pst: u64,
+    pub stake_pst: u64,
+    pub amount: u64,
+    pub result: String,
+}
+
+pub struct MintInput {
+    pub minted: u64,
+    // PST the stored data and the free disk can back
+    pub storage_pst: u64,
+    pub stake_pst: u64,
+}
+
+// bytes the store can back orders with, the free disk is shared with the rest of the device so a capacity caps it
+pub fn backing_space(free_space: u64, used_space: u64, store_capacity: u64) -> u64 {
+    let space = free_space.saturating_add(used_space);
+    if store_capacity > 0 {
+        space.min(store_capacity)
+    } else {
+        space
+    }
+}
+
+// PST to mint this round, never more than storage and stake can back
+pub fn plan_mint(config: &AutoMintConfig, state: &mut AutoMintState, input: &MintInput, now: u64) -> u64 {
+    if now >= state.day_start + DAY_SECONDS {
+        state.day_start = now;
+        state.minted_today = 0;
+    }
+    let mut limit = input.storage_pst.min(input.stake_pst);
+    if config.max_total_pst > 0 {
+        limit = limit.min(config.max_total_pst);
+    }
+    let mut amount = limit.saturating_sub(input.minted);
+    if config.max_pst_per_day > 0 {
+        amount = amount.min(config.max_pst_per_day.saturating_sub(state.minted_today));
+    }
+    if amount < config.min_mint_pst.max(1) {
+        0
+    } else {
+        amount
+    }
+}
+
+pub async fn load_auto_mint_state<CONN: ContractMetaStore>(meta_store: &dyn MetaStore<CONN>) -> BuckyResult<AutoMintState> {
+    let state = meta_store.get_setting("dmc_auto_mint_state", "").await?;
+    if state.is_empty() {
+        return Ok(AutoMintState::default());
+    }
+    serde_json::from_str(state.as_str()).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse auto mint state err {}", e)
+    })
+}
+
+pub struct AutoMint<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> {
+    config: AutoMintConfig,
+    dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+    meta_store: Arc<dyn MetaStore<CONN>>,
+    dmc_server: String,
+    dmc_account: String,
+}
+pub type AutoMintRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> = Arc<AutoMint<STACK, CONN, CHUNKSTORE, DMCTXSENDER>>;
+
+impl<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> AutoMint<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+    pub fn new(
+        config: AutoMintConfig,
+        dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+        meta_store: Arc<dyn MetaStore<CONN>>,
+        dmc_server: &str,
+        dmc_account: &str,
+    ) -> AutoMintRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+        AutoMintRef::new(Self {
+            config,
+            dmc,
+            meta_store,
+            dmc_server: dmc_server.to_string(),
+            dmc_account: dmc_account.to_string()
+        })
+    }
+
+    pub fn start(self: &AutoMintRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>) {
+        if !self.config.enable {
+            return;
+        }
+        log::info!("start auto mint, recommend only {}", self.config.recommend_only);
+
+        let this = self.clone();
+        #[cfg(not(feature = "no_dmc"))]
+        async_std::task::spawn(async move {
+            loop {
+                if let Err(e) = this.run_once().await {
+                    log::error!("auto mint err {}", e);
+                }
+                async_std::task::sleep(std::time::Duration::from_secs(this.config.check_interval)).await;
+            }
+        });
+    }
+
+    // the light key can't mint, the owner key stays in the signer which signs nothing but mint
+    async fn mint(&self, amount: u64) -> BuckyResult<()> {
+        if self.config.signer.is_empty() {
+            return Err(cyfs_err!(BuckyErrorCode::NotFound, "no signer configured for the owner key"));
+        }
+        let provider = RemoteSignatureProvider::open(
+            self.config.signer.as_str(),
+            ActionWhitelistPolicy::new(vec![("dmc.token".to_string(), "mint".to_string())])).await?;
+        let sender = LocalDMCTxSender::new(self.dmc_account.as_str(), self.dmc_server.as_str(), provider);
+        sender.mint(amount.to_string().as_str()).await?;
+        Ok(())
+    }
+
+    pub async fn run_once(&self) -> BuckyResult<AutoMintState> {
+        let minted = self.dmc.get_pst_amount().await?;
+        let free_space = self.dmc.get_free_space().await?;
+        let used_space: u64 = self.meta_store.get_setting("used_space", "0").await?.parse().unwrap_or(0);
+        let storage_pst = space_to_pst(backing_space(free_space, used_space, self.config.store_capacity));
+        let stake_pst = stake_to_pst(self.dmc.get_stake_info().await?.get_staked()?, self.dmc.get_avg_price().await?);
+
+        let mut state = load_auto_mint_state(self.meta_store.as_ref()).await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let amount = plan_mint(&self.config, &mut state, &MintInput {
+            minted,
+            storage_pst,
+            stake_pst
+        }, now);
+
+        let result = if amount == 0 {
+            String::new()
+        } else if self.config.recommend_only {
+            "recommended".to_string()
+        } else {
+            match self.mint(amount).await {
+                Ok(_) => {
+                    state.minted_today += amount;
+                    "minted".to_string()
+                }
+                Err(e) => format!("err {}", e),
+            }
+        };
+        log::info!("auto mint minted {} storage {} stake {} amount {} result {}", minted, storage_pst, stake_pst, amount, result.as_str());
+
+        state.last_check = now;
+        state.minted = minted;
+        state.storage_pst = storage_pst;
+        state.stake_pst = stake_pst;
+        state.amount = amount;
+        state.result = result;
+        self.meta_store.set_setting("dmc_auto_mint_state".to_string(), serde_json::to_string(&state).unwrap()).await?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test_auto_mint {
+    use crate::{backing_space, plan_mint, AutoMintConfig, AutoMintState, MintInput};
+
+    fn config() -> AutoMintConfig {
+        AutoMintConfig {
+            enable: true,
+            recommend_only: false,
+            signer: "".to_string(),
+            store_capacity: 0,
+            max_pst_per_day: 50,
+            max_total_pst: 0,
+            min_mint_pst: 5,
+            check_interval: 3600
+        }
+    }
+
+    #[test]
+    fn test_plan_mint() {
+        let mut state = AutoMintState::default();
+        // storage is the tighter limit
+        assert_eq!(plan_mint(&config(), &mut state, &MintInput { minted: 10, storage_pst: 40, stake_pst: 100 }, 1000), 30);
+        // stake is the tighter limit
+        assert_eq!(plan_mint(&config(), &mut state, &MintInput { minted: 10, storage_pst: 100, stake_pst: 20 }, 1000), 10);
+        // already beyond what storage backs
+        assert_eq!(plan_mint(&config(), &mut state, &MintInput { minted: 50, storage_pst: 40, stake_pst: 100 }, 1000), 0);
+        // too small a growth
+        assert_eq!(plan_mint(&config(), &mut state, &MintInput { minted: 38, storage_pst: 40, stake_pst: 100 }, 1000), 0);
+    }
+
+    #[test]
+    fn test_backing_space() {
+        assert_eq!(backing_space(100, 50, 0), 150);
+        // the capacity caps the disk
+        assert_eq!(backing_space(100, 50, 120), 120);
+        assert_eq!(backing_space(100, 50, 1000), 150);
+    }
+
+    #[test]
+    fn test_daily_limit() {
+        let mut state = AutoMintState::default();
+        let input = MintInput { minted: 0, storage_pst: 1000, stake_pst: 1000 };
+        assert_eq!(plan_mint(&config(), &mut state, &input, 1000), 50);
+        state.minted_today += 30;
+        assert_eq!(plan_mint(&config(), &mut state, &input, 2000), 20);
+        state.minted_today += 20;
+        assert_eq!(plan_mint(&config(), &mut state, &input, 3000), 0);
+        assert_eq!(plan_mint(&config(), &mut state, &input, 1000 + 24 * 3600), 50);
+        assert_eq!(state.minted_today, 0);
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use async_std::io::ReadExt;
+use async_std::task::JoinHandle;
+use cyfs_base::*;
+use crate::ContractChunkStore;
+
+// reads the logical bytes of a contract: the meta chunks, then the data chunks,
+// each chunk is padded with zeros to its length in the layout.
+// chunks are fetched by spawned tasks, the next ones are fetched ahead and the lately read ones are cached.
+// with no cache and no prefetch, read_range only reads the bytes of the range from the store
+
+const DEFAULT_CACHE_CHUNKS: usize = 4;
+const DEFAULT_PREFETCH_CHUNKS: usize = 2;
+
+enum SegmentSource {
+    Memory(Arc<Vec<u8>>),
+    Chunk(ChunkId),
+}
+
+struct Segment {
+    start: u64,
+    len: u64,
+    source: SegmentSource,
+}
+
+// chunk data dropped least recently used first, a capacity of 0 keeps nothing
+pub struct ChunkLruCache {
+    capacity: usize,
+    chunks: HashMap<ChunkId, Arc<Vec<u8>>>,
+    order: VecDeque<ChunkId>,
+}
+
+impl ChunkLruCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn contains(&self, chunk_id: &ChunkId) -> bool {
+        self.chunks.contains_key(chunk_id)
+    }
+
+    pub fn get(&mut self, chunk_id: &ChunkId) -> Option<Arc<Vec<u8>>> {
+        let data = self.chunks.get(chunk_id)?.clone();
+        self.touch(chunk_id);
+        Some(data)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn insert(&mut self, chunk_id: ChunkId, data: Arc<Vec<u8>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.chunks.insert(chunk_id.clone(), data).is_some() {
+            self.touch(&chunk_id);
+            return;
+        }
+        self.order.push_back(chunk_id);
+        while self.order.len() > self.capacity {
+            if let Some(chunk_id) = self.order.pop_front() {
+                self.chunks.remove(&chunk_id);
+            }
+        }
+    }
+
+    fn touch(&mut self, chunk_id: &ChunkId) {
+        if let Some(pos) = self.order.iter().position(|v| v == chunk_id) {
+            if let Some(chunk_id) = self.order.remove(pos) {
+                self.order.push_back(chunk_id);
+            }
+        }
+    }
+}
+
+pub struct ContractDataReader<CHUNKSTORE: ContractChunkStore> {
+    chunk_store: Arc<CHUNKSTORE>,
+    segments: Vec<Segment>,
+    size: u64,
+    pos: u64,
+    cache: ChunkLruCache,
+    prefetch: usize,
+    pending: HashMap<ChunkId, JoinHandle<BuckyResult<Vec<u8>>>>,
+}
+
+impl<CHUNKSTORE: ContractChunkStore> ContractDataReader<CHUNKSTORE> {
+    fn from_segments(chunk_store: Arc<CHUNKSTORE>, segments: Vec<Segment>) -> Self {
+        let size = segments.last().map(|v| v.start + v.len).unwrap_or(0);
+        Self {
+            chunk_store,
+            segments,
+            size,
+            pos: 0,
+            cache: ChunkLruCache::new(DEFAULT_CACHE_CHUNKS),
+            prefetch: DEFAULT_PREFETCH_CHUNKS,
+            pending: HashMap::new(),
+        }
+    }
+
+    // data chunks padded to chunk_size, the layout the merkle tree and the challenges of a contract use
+    pub fn new(chunk_store: Arc<CHUNKSTORE>, chunk_list: Vec<ChunkId>, chunk_size: u32) -> BuckyResult<Self> {
+        let mut segments = Vec::with_capacity(chunk_list.len());
+        for (index, chunk_id) in chunk_list.into_iter().enumerate() {
+            if chunk_id.len() as u64 > chunk_size as u64 {
+                let msg = format!("chunk {} len {} big than {}", chunk_id.to_string(), chunk_id.len(), chunk_size);
+                log::error!("{}", msg);
+                return Err(BuckyError::new(BuckyErrorCode::Failed, msg));
+            }
+            segments.push(Segment {
+                start: index as u64 * chunk_size as u64,
+                len: chunk_size as u64,
+                source: SegmentSource::Chunk(chunk_id),
+            });
+        }
+        Ok(Self::from_segments(chunk_store, segments))
+    }
+
+    // chunks one after another without padding, the bytes of a file
+    pub fn unpadded(chunk_store: Arc<CHUNKSTORE>, chunk_list: Vec<ChunkId>) -> Self {
+        let mut start = 0;
+        let segments = chunk_list.into_iter().map(|chunk_id| {
+            let segment = Segment {
+                start,
+                len: chunk_id.len() as u64,
+                source: SegmentSource::Chunk(chunk_id),
+            };
+            start += segment.len;
+            segment
+        }).collect();
+        Self::from_segments(chunk_store, segments)
+    }
+
+    // the meta data takes meta_len bytes before the data chunks, padded or cut to it
+    pub fn with_meta(mut self, mut meta_data: Vec<u8>, meta_len: u64) -> Self {
+        meta_data.resize(meta_len as usize, 0);
+        for segment in self.segments.iter_mut() {
+            segment.start += meta_len;
+        }
+        self.segments.insert(0, Segment {
+            start: 0,
+            len: meta_len,
+            source: SegmentSource::Memory(Arc::new(meta_data)),
+        });
+        self.size += meta_len;
+        self
+    }
+
+    // 0 caches nothing, meant for read_range, a chunk read in pieces through Read is fetched again for each piece
+    pub fn with_cache(mut self, chunks: usize) -> Self {
+        self.cache = ChunkLruCache::new(chunks);
+        self
+    }
+
+    pub fn with_prefetch(mut self, chunks: usize) -> Self {
+        self.prefetch = chunks;
+        self
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // the bytes of range in the layout, a range out of the layout is cut at its end
+    pub async fn read_range(&mut self, range: Range<u64>) -> BuckyResult<Vec<u8>> {
+        let end = std::cmp::min(range.end, self.size);
+        let start = std::cmp::min(range.start, end);
+        if self.cache.capacity() == 0 && self.prefetch == 0 {
+            let data = self.read_segments(start..end).await?;
+            self.pos = end;
+            return Ok(data);
+        }
+        self.pos = start;
+        let mut data = vec![0u8; (end - start) as usize];
+        self.read_exact(data.as_mut_slice()).await?;
+        Ok(data)
+    }
+
+    // reads only the bytes of range from each chunk it covers
+    async fn read_segments(&self, range: Range<u64>) -> BuckyResult<Vec<u8>> {
+        let mut data = Vec::with_capacity((range.end - range.start) as usize);
+        let mut index = self.segments.partition_point(|v| v.start + v.len <= range.start);
+        while index < self.segments.len() && self.segments[index].start < range.end {
+            let segment = &self.segments[index];
+            let from = std::cmp::max(range.start, segment.start) - segment.start;
+            let to = std::cmp::min(range.end, segment.start + segment.len) - segment.start;
+            let padded_len = data.len() + (to - from) as usize;
+            match &segment.source {
+                SegmentSource::Memory(buf) => {
+                    let copy_to = std::cmp::min(to, buf.len() as u64);
+                    if from < copy_to {
+                        data.extend_from_slice(&buf[from as usize..copy_to as usize]);
+                    }
+                },
+                SegmentSource::Chunk(chunk_id) => {
+                    let copy_to = std::cmp::min(to, chunk_id.len() as u64);
+                    if from < copy_to {
+                        data.extend_from_slice(self.chunk_store.get_chunk_by_range(chunk_id, from..copy_to).await?.as_slice());
+                    }
+                }
+            }
+            data.resize(padded_len, 0);
+            index += 1;
+        }
+        Ok(data)
+    }
+
+    #[tracing::instrument(skip(chunk_store), err)]
+    pub async fn get_contract_data(chunk_store: Arc<CHUNKSTORE>, chunk_list: Vec<ChunkId>, range: Range<u64>, chunk_size: u32) -> BuckyResult<Vec<u8>> {
+        Self::new(chunk_store, chunk_list, chunk_size)?.with_cache(0).with_prefetch(0).read_range(range).await
+    }
+
+    // a dropped JoinHandle leaves its task running, the fetches given up are cancelled
+    fn cancel(handle: JoinHandle<BuckyResult<Vec<u8>>>) {
+        async_std::task::spawn(async move {
+            handle.cancel().await;
+        });
+    }
+
+    fn fetch(&mut self, chunk_id: &ChunkId) {
+        if self.pending.contains_key(chunk_id) || self.cache.contains(chunk_id) {
+            return;
+        }
+        let chunk_store = self.chunk_store.clone();
+        let id = chunk_id.clone();
+        let handle = async_std::task::spawn(async move {
+            chunk_store.get_chunk_by_range(&id, 0..id.len() as u64).await
+        });
+        self.pending.insert(chunk_id.clone(), handle);
+    }
+
+    // fetches the chunk at index and the ones after it, the fetches out of the window are cancelled after a seek,
+    // so at most prefetch + 1 fetches run
+    fn prefetch_after(&mut self, index: usize) {
+        let end = std::cmp::min(self.segments.len(), index + 1 + self.prefetch);
+        let window: Vec<ChunkId> = self.segments[index..end].iter().filter_map(|v| match &v.source {
+            SegmentSource::Chunk(chunk_id) => Some(chunk_id.clone()),
+            SegmentSource::Memory(_) => None,
+        }).collect();
+        let keep: HashSet<&ChunkId> = window.iter().collect();
+        let given_up: Vec<ChunkId> = self.pending.keys().filter(|chunk_id| !keep.contains(chunk_id)).cloned().collect();
+        for chunk_id in given_up.iter() {
+            if let Some(handle) = self.pending.remove(chunk_id) {
+                Self::cancel(handle);
+            }
+        }
+        for chunk_id in window.iter() {
+            self.fetch(chunk_id);
+        }
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>, chunk_id: &ChunkId) -> Poll<BuckyResult<Arc<Vec<u8>>>> {
+        if let Some(data) = self.cache.get(chunk_id) {
+            return Poll::Ready(Ok(data));
+        }
+        self.fetch(chunk_id);
+        let handle = self.pending.get_mut(chunk_id).unwrap();
+        match Pin::new(handle).poll(cx) {
+            Poll::Ready(ret) => {
+                self.pending.remove(chunk_id);
+                let data = Arc::new(ret?);
+                self.cache.insert(chunk_id.clone(), data.clone());
+                Poll::Ready(Ok(data))
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<CHUNKSTORE: ContractChunkStore> Drop for ContractDataReader<CHUNKSTORE> {
+    fn drop(&mut self) {
+        for (_, handle) in self.pending.drain() {
+            Self::cancel(handle);
+        }
+    }
+}
+
+impl<CHUNKSTORE: ContractChunkStore> async_std::io::Read for ContractDataReader<CHUNKSTORE> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() || this.pos >= this.size {
+            return Poll::Ready(Ok(0));
+        }
+        let pos = this.pos;
+        let index = this.segments.partition_point(|v| v.start + v.len <= pos);
+        this.prefetch_after(index);
+
+        let data = match &this.segments[index].source {
+            SegmentSource::Memory(data) => data.clone(),
+            SegmentSource::Chunk(chunk_id) => {
+                let chunk_id = chunk_id.clone();
+                match this.poll_chunk(cx, &chunk_id) {
+                    Poll::Ready(Ok(data)) => data,
+                    Poll::Ready(Err(e)) => {
+                        let msg = format!("read chunk {} at {} failed.err {}", chunk_id, pos, e);
+                        log::error!("{}", msg.as_str());
+                        return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::Other, msg)));
+                    },
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        };
+
+        // one segment at most each time, the bytes after the chunk data are padding
+        let segment = &this.segments[index];
+        let offset = (pos - segment.start) as usize;
+        let len = std::cmp::min(buf.len() as u64, segment.len - offset as u64) as usize;
+        let copied = if offset < data.len() { std::cmp::min(len, data.len() - offset) } else { 0 };
+        buf[..copied].copy_from_slice(&data[offset..offset + copied]);
+        buf[copied..len].fill(0);
+        this.pos += len as u64;
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<CHUNKSTORE: ContractChunkStore> async_std::io::Seek for ContractDataReader<CHUNKSTORE> {
+    fn poll_seek(self: Pin<&mut Self>, _cx: &mut Context<'_>, pos: SeekFrom) -> Poll<std::io::Result<u64>> {
+        let this = self.get_mut();
+        let seek = pos;
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => (pos, 0),
+            SeekFrom::End(offset) => (this.size, offset),
+            SeekFrom::Current(offset) => (this.pos, offset),
+        };
+        let pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        match pos {
+            Some(pos) => {
+                this.pos = pos;
+                Poll::Ready(Ok(pos))
+            },
+            None => Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("seek {:?} failed", seek))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_contract_data_reader {
+    use std::io::SeekFrom;
+    use std::sync::Arc;
+    use async_std::io::{ReadExt, SeekExt};
+    use crate::*;
+
+    #[test]
+    fn test_read() {
+        async_std::task::block_on(async {
+            let chunk_store = Arc::new(MemChunkStore::new());
+            let full = chunk_store.put(vec![1u8; 4096]).unwrap();
+            let short = chunk_store.put(vec![2u8; 1000]).unwrap();
+            let last = chunk_store.put(vec![3u8; 10]).unwrap();
+            let chunk_list = vec![full.clone(), short.clone(), full.clone(), last.clone()];
+
+            let mut expect = vec![7u8; 100];
+            expect.resize(2048, 0);
+            for (value, len) in [(1u8, 4096usize), (2, 1000), (1, 4096), (3, 10)] {
+                let start = expect.len();
+                expect.resize(start + len, value);
+                expect.resize(start + 4096, 0);
+            }
+
+            let mut reader = ContractDataReader::new(chunk_store.clone(), chunk_list.clone(), 4096).unwrap()
+                .with_meta(vec![7u8; 100], 2048)
+                .with_cache(1)
+                .with_prefetch(1);
+            assert_eq!(reader.size(), expect.len() as u64);
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await.unwrap();
+            assert_eq!(data, expect);
+
+            assert_eq!(reader.read_range(5000..7000).await.unwrap(), expect[5000..7000].to_vec());
+            assert_eq!(reader.read_range(expect.len() as u64 - 5..expect.len() as u64 + 100).await.unwrap(), expect[expect.len() - 5..].to_vec());
+            reader.seek(SeekFrom::End(-4096)).await.unwrap();
+            let mut piece = vec![0u8; 20];
+            reader.read_exact(piece.as_mut_slice()).await.unwrap();
+            assert_eq!(piece, expect[expect.len() - 4096..expect.len() - 4076].to_vec());
+
+            // the same bytes as get_contract_data, which starts after the meta chunks
+            let data = ContractDataReader::get_contract_data(chunk_store.clone(), chunk_list.clone(), 4000..6000, 4096).await.unwrap();
+            assert_eq!(data, expect[2048 + 4000..2048 + 6000].to_vec());
+
+            // without cache and prefetch only the ranges are read, padding and meta included
+            let mut direct = ContractDataReader::new(chunk_store.clone(), chunk_list.clone(), 4096).unwrap()
+                .with_meta(vec![7u8; 100], 2048)
+                .with_cache(0)
+                .with_prefetch(0);
+            assert_eq!(direct.read_range(0..expect.len() as u64).await.unwrap(), expect);
+            assert_eq!(direct.read_range(50..3000).await.unwrap(), expect[50..3000].to_vec());
+            assert_eq!(direct.read_range(2048 + 4096 + 990..2048 + 4096 * 2 + 10).await.unwrap(), expect[2048 + 4096 + 990..2048 + 4096 * 2 + 10].to_vec());
+            assert_eq!(direct.read_range(expect.len() as u64 - 5..expect.len() as u64 + 100).await.unwrap(), expect[expect.len() - 5..].to_vec());
+
+            let mut file = Vec::new();
+            ContractDataReader::unpadded(chunk_store.clone(), vec![short.clone(), last.clone()]).read_to_end(&mut file).await.unwrap();
+            assert_eq!(file, vec![vec![2u8; 1000], vec![3u8; 10]].concat());
+
+            assert!(ContractDataReader::new(chunk_store.clone(), chunk_list, 1024).is_err());
+        })
+    }
+}
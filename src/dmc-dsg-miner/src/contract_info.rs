@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use crate::{ContractStatus};
 use cyfs_base::*;
+use dmc_dsg_base::MerkleHashAlgorithm;
 
 #[derive(Clone, ProtobufEncode, ProtobufDecode, ProtobufTransformType)]
 #[cyfs_protobuf_type(crate::protos::ContractInfo)]
@@ -9,7 +10,8 @@ pub struct ContractInfo {
     pub latest_check_time: u64,
     pub meta_merkle: Vec<HashValue>,
     pub stored_size: Option<u64>,
-    pub sum_size: Option<u64>
+    pub sum_size: Option<u64>,
+    pub merkle_hash: MerkleHashAlgorithm,
 }
 
 impl ProtobufTransform<crate::protos::ContractInfo> for ContractInfo {
@@ -20,6 +22,7 @@ impl ProtobufTransform<crate::protos::ContractInfo> for ContractInfo {
             meta_merkle: value.meta_merkle.iter().map(|v| HashValue::try_from(v.as_slice()).unwrap()).collect(),
             stored_size: value.stored_size,
             sum_size: value.sum_size,
+            merkle_hash: MerkleHashAlgorithm::from_id(value.merkle_hash.unwrap_or(0))?,
         })
     }
 }
@@ -32,7 +35,8 @@ impl ProtobufTransform<&ContractInfo> for crate::protos::ContractInfo {
             latest_check_time: value.latest_check_time,
             meta_merkle: value.meta_merkle.iter().map(|v|v.as_slice().to_vec()).collect(),
             stored_size: value.stored_size.clone(),
-            sum_size: value.sum_size.clone()
+            sum_size: value.sum_size.clone(),
+            merkle_hash: Some(value.merkle_hash.id()),
         })
     }
 }
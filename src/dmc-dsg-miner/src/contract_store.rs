@@ -60,6 +60,9 @@ pub trait ContractMetaStore: Send + Sync + MetaConnection + 'static {
     async fn get_chunks_by_path(&mut self, url_path: String) -> BuckyResult<Vec<ChunkId>>;
     async fn get_chunk_list(&mut self, contract_id: &ObjectId) -> BuckyResult<Vec<ChunkId>>;
     async fn save_chunk_list(&mut self, contract_id: &ObjectId, chunk_list: Vec<ChunkId>) -> BuckyResult<()>;
+    // the layers of the contract merkle tree from the chunk roots up
+    async fn get_contract_merkle_tree(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<Vec<u8>>>;
+    async fn save_contract_merkle_tree(&mut self, contract_id: &ObjectId, tree_data: Vec<u8>) -> BuckyResult<()>;
     async fn get_challenge(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgChallengeObject>>;
     async fn save_challenge(&mut self, contract_id: &ObjectId, challenge: &DsgChallengeObject) -> BuckyResult<()>;
     async fn chunk_ref_add(&mut self, contract_id: &ObjectId, chunk_list: &Vec<ChunkId>) -> BuckyResult<()>;
@@ -109,81 +112,14 @@ pub trait ContractChunkStore: Send + Sync + 'static {
     async fn get_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Chunk>>;
     async fn get_chunk_by_range(&self, chunk_id: &ChunkId, range: Range<u64>) -> BuckyResult<Vec<u8>>;
     async fn get_chunk_reader(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Unpin + Read + Send + Sync>>;
-    #[tracing::instrument(skip(self), err)]
-    async fn get_contract_data(&self, chunk_list: Vec<ChunkId>, range: Range<u64>, chunk_size: u32) -> BuckyResult<Vec<u8>> {
-        let start = range.start / chunk_size as u64;
-        let mut end = range.end / chunk_size as u64 + 1;
-        if end > chunk_list.len() as u64{
-            end = chunk_list.len() as u64;
-        }
-        let mut read_len = range.end - range.start;
-        let mut data = Vec::with_capacity(read_len as usize);
-
-        let mut cpos = range.start % chunk_size as u64;
-        for idx in start..end {
-            let chunk_id = &chunk_list[idx as usize];
-            let csize = chunk_id.len() as u64;
-            if csize == chunk_size as u64 {
-                if csize - cpos > read_len {
-                    let mut buf = self.get_chunk_by_range(chunk_id, cpos..cpos+read_len).await?;
-                    read_len -= buf.len() as u64;
-                    cpos = 0;
-                    data.append(&mut buf);
-                } else {
-                    let mut buf = self.get_chunk_by_range(chunk_id, cpos..csize).await?;
-                    read_len -= buf.len() as u64;
-                    cpos = 0;
-                    data.append(&mut buf);
-                }
-            } else if csize < chunk_size as u64 {
-                if csize > cpos && csize - cpos >= read_len {
-                    let mut buf = self.get_chunk_by_range(chunk_id, cpos..cpos+read_len).await?;
-                    read_len -= buf.len() as u64;
-                    cpos = 0;
-                    data.append(&mut buf);
-                } else if csize > cpos && csize - cpos < read_len {
-                    let mut buf = self.get_chunk_by_range(chunk_id, cpos..csize).await?;
-                    read_len -= buf.len() as u64;
-                    cpos += buf.len() as u64;
-                    data.append(&mut buf);
-                    if cpos < chunk_size as u64 && read_len > 0 {
-                        let mut padding = if read_len > chunk_size as u64 - cpos {
-                            let mut padding = Vec::<u8>::new();
-                            padding.resize(chunk_size as usize - cpos as usize, 0);
-                            padding
-                        } else {
-                            let mut padding = Vec::<u8>::new();
-                            padding.resize(read_len as usize, 0);
-                            padding
-                        };
-                        read_len -= padding.len() as u64;
-                        cpos = 0;
-                        data.append(&mut padding);
-                    }
-                } else {
-                    let mut padding = if read_len > chunk_size as u64 - cpos {
-                        let mut padding = Vec::<u8>::new();
-                        padding.resize(chunk_size as usize - cpos as usize, 0);
-                        padding
-                    } else {
-                        let mut padding = Vec::<u8>::new();
-                        padding.resize(read_len as usize, 0);
-                        padding
-                    };
-                    read_len -= padding.len() as u64;
-                    cpos = 0;
-                    data.append(&mut padding);
-                }
-            } else {
-                let msg = format!("chunk {} len {} big than {}", chunk_id.to_string(), csize, chunk_size);
-                log::error!("{}", msg);
-                return Err(BuckyError::new(BuckyErrorCode::Failed, msg));
-            }
-        }
-
-        Ok(data)
-    }
     async fn chunk_exists(&self, chunk_id: &ChunkId) -> bool;
+    // the redundancy the chunks are kept with, none for the default of the store
+    async fn set_storage_class(&self, _chunk_list: &[ChunkId], _class: Option<&str>) -> BuckyResult<()> {
+        Ok(())
+    }
+    async fn delete_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<()> {
+        Err(crate::cyfs_err!(BuckyErrorCode::NotSupport, "delete chunk {} not supported", chunk_id))
+    }
 }
 
 #[derive(Debug,Clone, Copy, Eq, PartialEq)]
@@ -1,12 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 use std::ops::Range;
-use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::sync::Arc;
 use cyfs_base::*;
-use cyfs_chunk_lib::{Chunk, CHUNK_SIZE, MemChunk};
+use cyfs_chunk_lib::CHUNK_SIZE;
 use cyfs_dsg_client::{DsgContractObject, DsgContractObjectRef, DsgContractState, DsgContractStateObjectRef};
 use crate::*;
 use dmc_dsg_base::*;
+use serde::{Serialize, Deserialize};
 
 #[derive(ProtobufEncode, ProtobufDecode, Clone, ProtobufTransform, Debug)]
 #[cyfs_protobuf_type(crate::protos::DmcContractData)]
@@ -15,6 +17,10 @@ pub struct DMCContractData {
     pub miner_dmc_account: String,
     pub merkle_root: Option<HashValue>,
     pub chunk_size: Option<u32>,
+    // id of MerkleHashAlgorithm, none is sha256
+    pub merkle_hash: Option<u32>,
+    // the redundancy class the miner keeps the data with, none for the miner's default
+    pub storage_class: Option<String>,
 }
 
 pub enum DMCCommand {
@@ -22,12 +28,47 @@ pub enum DMCCommand {
     GetOrderInfoResp
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ChallengeState {
     RespChallenge,
     Arbitration,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+struct OrderChallengeState {
+    challenge_times: u64,
+    state: ChallengeState,
+}
+
+// chain writes kept in the tx tracker, enough to send them again when they expire
+#[derive(Clone, Serialize, Deserialize)]
+pub enum DMCTrackedAction {
+    AddMerkle { merkle_root: String, data_block_count: u64 },
+    ChallengeResp { reply_hash: String },
+    Arbitration { data: String, cut_merkle: Vec<String> },
+}
+
+impl DMCTrackedAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AddMerkle { .. } => "addmerkle",
+            Self::ChallengeResp { .. } => "anschallenge",
+            Self::Arbitration { .. } => "arbitration",
+        }
+    }
+}
+
+const CHALLENGE_STATE_LOCKER: &str = "dmc_challenge_state_locker";
+const TX_TRACKER_LOCKER: &str = "dmc_tx_tracker_locker";
+const MAX_TX_RETRIES: u32 = 3;
+
+// a corrupt setting would otherwise be taken as empty and overwritten with the next save
+fn parse_setting<T: serde::de::DeserializeOwned>(name: &str, value: &str) -> BuckyResult<T> {
+    serde_json::from_str(value).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse setting {} err {}", name, e)
+    })
+}
+
 pub struct DMC<
     STACK: CyfsClient,
     CONN: ContractMetaStore,
@@ -39,7 +80,7 @@ pub struct DMC<
     http_domain: String,
     contract_store: Arc<dyn MetaStore<CONN>>,
     raw_data_store: Arc<CHUNKSTORE>,
-    challenge_state: Mutex<HashMap<String, ChallengeState>>,
+    tx_tracker: DMCTxTracker,
     dmc_account: String,
     _marker: PhantomData<CONN>,
 }
@@ -70,7 +111,7 @@ impl<
             http_domain,
             contract_store,
             raw_data_store,
-            challenge_state: Mutex::new(Default::default()),
+            tx_tracker: DMCTxTracker::new(dmc_server, DMC_TX_EXPIRE_SECONDS),
             dmc_account: dmc_account.to_string(),
             _marker: Default::default(),
         });
@@ -93,6 +134,17 @@ impl<
             }
         });
 
+        let tmp_dmc = dmc.clone();
+        #[cfg(not(feature = "no_dmc"))]
+        async_std::task::spawn(async move {
+            loop {
+                if let Err(e) = tmp_dmc.check_txs().await {
+                    log::error!("check txs err {}", e);
+                }
+                async_std::task::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        });
+
         Ok(dmc)
     }
 
@@ -110,6 +162,143 @@ impl<
         Ok(())
     }
 
+    async fn get_challenge_state(&self, order_id: &str, challenge_times: u64) -> BuckyResult<Option<ChallengeState>> {
+        let states = self.contract_store.get_setting("dmc_challenge_state", "{}").await?;
+        let states: HashMap<String, OrderChallengeState> = parse_setting("dmc_challenge_state", states.as_str())?;
+        // the state of an earlier challenge of the order doesn't count
+        Ok(states.get(order_id).filter(|v| v.challenge_times == challenge_times).map(|v| v.state))
+    }
+
+    async fn set_challenge_state(&self, order_id: &str, challenge_times: u64, state: Option<ChallengeState>) -> BuckyResult<()> {
+        let _locker = Locker::get_locker(CHALLENGE_STATE_LOCKER.to_string()).await;
+        let states = self.contract_store.get_setting("dmc_challenge_state", "{}").await?;
+        let mut states: HashMap<String, OrderChallengeState> = parse_setting("dmc_challenge_state", states.as_str())?;
+        match state {
+            Some(state) => {
+                states.insert(order_id.to_string(), OrderChallengeState {
+                    challenge_times,
+                    state
+                });
+            }
+            None => {
+                states.remove(order_id);
+            }
+        }
+        self.contract_store.set_setting("dmc_challenge_state".to_string(), serde_json::to_string(&states).unwrap()).await
+    }
+
+    async fn take_failed_merkle(&self, order_id: &str) -> BuckyResult<Option<DMCTrackedAction>> {
+        let _locker = Locker::get_locker(CHALLENGE_STATE_LOCKER.to_string()).await;
+        let failed = self.contract_store.get_setting("dmc_failed_merkle", "{}").await?;
+        let mut failed: HashMap<String, DMCTrackedAction> = parse_setting("dmc_failed_merkle", failed.as_str())?;
+        let action = failed.remove(order_id);
+        if action.is_some() {
+            self.contract_store.set_setting("dmc_failed_merkle".to_string(), serde_json::to_string(&failed).unwrap()).await?;
+        }
+        Ok(action)
+    }
+
+    async fn save_failed_merkle(&self, order_id: &str, action: DMCTrackedAction) -> BuckyResult<()> {
+        let _locker = Locker::get_locker(CHALLENGE_STATE_LOCKER.to_string()).await;
+        let failed = self.contract_store.get_setting("dmc_failed_merkle", "{}").await?;
+        let mut failed: HashMap<String, DMCTrackedAction> = parse_setting("dmc_failed_merkle", failed.as_str())?;
+        failed.insert(order_id.to_string(), action);
+        self.contract_store.set_setting("dmc_failed_merkle".to_string(), serde_json::to_string(&failed).unwrap()).await
+    }
+
+    async fn load_tx_state(&self) -> BuckyResult<DMCTxTrackerState<DMCTrackedAction>> {
+        let state = self.contract_store.get_setting("dmc_tracked_txs", "").await?;
+        if state.is_empty() {
+            return Ok(DMCTxTrackerState::default());
+        }
+        parse_setting("dmc_tracked_txs", state.as_str())
+    }
+
+    async fn save_tx_state(&self, state: &DMCTxTrackerState<DMCTrackedAction>) -> BuckyResult<()> {
+        self.contract_store.set_setting("dmc_tracked_txs".to_string(), serde_json::to_string(state).unwrap()).await
+    }
+
+    async fn send_action(&self, order_id: &str, action: &DMCTrackedAction) -> BuckyResult<TransResult> {
+        match action {
+            DMCTrackedAction::AddMerkle { merkle_root, data_block_count } => {
+                self.dmc_client.add_merkle(order_id, HashValue::from_str(merkle_root.as_str())?, *data_block_count).await
+            }
+            DMCTrackedAction::ChallengeResp { reply_hash } => {
+                self.dmc_client.add_challenge_resp(order_id, HashValue::from_str(reply_hash.as_str())?).await
+            }
+            DMCTrackedAction::Arbitration { data, cut_merkle } => {
+                let data = hex::decode(data.as_str()).map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::InvalidData, "decode arbitration data of order {} err {}", order_id, e)
+                })?;
+                let mut cut_merkle_list = Vec::new();
+                for hash in cut_merkle.iter() {
+                    cut_merkle_list.push(HashValue::from_str(hash.as_str())?);
+                }
+                self.dmc_client.arbitration(order_id, data, cut_merkle_list).await
+            }
+        }
+    }
+
+    // push the action and record its transaction, check_txs follows it until irreversible.
+    // an action that can't be followed is an error too, the caller sends it again rather than count on it
+    async fn send_tracked(&self, order_id: &str, action: DMCTrackedAction, retries: u32) -> BuckyResult<()> {
+        let ret = self.send_action(order_id, &action).await?;
+        let _locker = Locker::get_locker(TX_TRACKER_LOCKER.to_string()).await;
+        let mut state = self.load_tx_state().await?;
+        let name = action.name();
+        self.tx_tracker.track(&mut state, ret, name, order_id, action, retries).await.map_err(|e| {
+            log::error!("track {} of order {} err {}", name, order_id, e);
+            e
+        })?;
+        self.save_tx_state(&state).await
+    }
+
+    async fn on_tx_failed(&self, tx: TrackedTx<DMCTrackedAction>) -> BuckyResult<()> {
+        log::error!("{} of order {} trx {} failed, state {:?} retries {}", tx.action, tx.order_id, tx.trx_id, tx.state, tx.retries);
+        let challenge_info = self.dmc_client.get_challenge_info(tx.order_id.as_str(), None).await?;
+        let challenge_times = challenge_info.rows.get(0).map(|v| v.challenge_times).unwrap_or(0);
+        match &tx.payload {
+            // check_challenge sends them again on its next round
+            DMCTrackedAction::AddMerkle { .. } => {
+                self.save_failed_merkle(tx.order_id.as_str(), tx.payload.clone()).await
+            }
+            DMCTrackedAction::ChallengeResp { .. } => {
+                self.set_challenge_state(tx.order_id.as_str(), challenge_times, None).await
+            }
+            DMCTrackedAction::Arbitration { .. } => {
+                self.set_challenge_state(tx.order_id.as_str(), challenge_times, Some(ChallengeState::RespChallenge)).await
+            }
+        }
+    }
+
+    async fn check_txs(&self) -> BuckyResult<()> {
+        let finished = {
+            let _locker = Locker::get_locker(TX_TRACKER_LOCKER.to_string()).await;
+            let mut state = self.load_tx_state().await?;
+            self.tx_tracker.poll(&mut state).await?;
+            let finished = state.take_finished();
+            self.save_tx_state(&state).await?;
+            finished
+        };
+
+        for tx in finished.into_iter() {
+            match tx.state {
+                TrackedTxState::Irreversible(_) => {}
+                TrackedTxState::Expired if tx.retries < MAX_TX_RETRIES => {
+                    log::info!("{} of order {} trx {} expired, send again", tx.action, tx.order_id, tx.trx_id);
+                    if let Err(e) = self.send_tracked(tx.order_id.as_str(), tx.payload.clone(), tx.retries + 1).await {
+                        log::error!("send {} of order {} again err {}", tx.action, tx.order_id, e);
+                        self.on_tx_failed(tx).await?;
+                    }
+                }
+                _ => {
+                    self.on_tx_failed(tx).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn check_challenge(&self) -> BuckyResult<()> {
         let mut conn = self.contract_store.create_meta_connection().await?;
         let contract_list = conn.contract_set().await?;
@@ -126,11 +315,16 @@ impl<
 
                 log::info!("challenge ret {}", serde_json::to_string(&challenge_ret).unwrap());
                 let challenge = &challenge_ret.rows[0];
-                if challenge.state == DMCChallengeState::ChallengeRequest as u32 {
-                    let state = {
-                        let state = self.challenge_state.lock().unwrap();
-                        state.get(challenge.order_id.to_string().as_str()).map(|state| state.clone())
-                    };
+                if challenge.state == DMCChallengeState::ChallengePrepare as u32 {
+                    if let Some(action) = self.take_failed_merkle(witness.order_id.as_str()).await? {
+                        log::info!("send addmerkle of order {} again", witness.order_id.as_str());
+                        if let Err(e) = self.send_tracked(witness.order_id.as_str(), action.clone(), 0).await {
+                            log::error!("add_merkle order_id {} failed {}", witness.order_id.as_str(), e);
+                            self.save_failed_merkle(witness.order_id.as_str(), action).await?;
+                        }
+                    }
+                } else if challenge.state == DMCChallengeState::ChallengeRequest as u32 {
+                    let state = self.get_challenge_state(challenge.order_id.to_string().as_str(), challenge.challenge_times).await?;
                     let contract_info = conn.get_contract_info(contract_id).await?;
                     let meta_max_id = contract_info.meta_merkle.len() as u64 * chunk_size as u64 / DSG_CHUNK_PIECE_SIZE;
                     if state.is_none() {
@@ -144,13 +338,13 @@ impl<
                             data
                         } else {
                             let chunk_list = conn.get_chunk_list(contract_id).await?;
-                            let data = self.raw_data_store.get_contract_data(chunk_list,
+                            let data = ContractDataReader::get_contract_data(self.raw_data_store.clone(), chunk_list,
                                                                              Range { start: (challenge.data_id - meta_max_id) * DSG_CHUNK_PIECE_SIZE as u64, end: ((challenge.data_id - meta_max_id) + 1) * DSG_CHUNK_PIECE_SIZE as u64 },
                                                                              chunk_size).await?;
                             data
                         };
-                        let mut hash = hash_data(vec![data.as_slice(), challenge.nonce.as_bytes()].concat().as_slice());
-                        let check_hash = hash_data(hash.as_slice());
+                        let mut hash = contract_info.merkle_hash.challenge_hash(data.as_slice(), challenge.nonce.as_str());
+                        let check_hash = HashValue::from(&contract_info.merkle_hash.hash_data(hash.as_slice()));
                         if check_hash.to_string() != challenge.hash_data {
                             let contract_state = conn.get_contract_state(contract_id).await?;
                             if contract_state.is_none() {
@@ -171,48 +365,54 @@ impl<
                                     cur_state = state.unwrap();
                                 }
                             };
-                            let data = self.raw_data_store.get_contract_data(chunk_list,
+                            let data = ContractDataReader::get_contract_data(self.raw_data_store.clone(), chunk_list,
                                                                              Range { start: (challenge.data_id - meta_max_id) * DSG_CHUNK_PIECE_SIZE as u64, end: ((challenge.data_id - meta_max_id) + 1) * DSG_CHUNK_PIECE_SIZE as u64 },
                                                                              chunk_size).await?;
-                            hash = hash_data(vec![data.as_slice(), challenge.nonce.as_bytes()].concat().as_slice());
+                            hash = contract_info.merkle_hash.challenge_hash(data.as_slice(), challenge.nonce.as_str());
                         }
-                        if let Err(e) = self.dmc_client.add_challenge_resp(witness.order_id.as_str(), hash).await {
+                        if let Err(e) = self.send_tracked(witness.order_id.as_str(), DMCTrackedAction::ChallengeResp {
+                            reply_hash: hash.to_string()
+                        }, 0).await {
                             if e.code() != BuckyErrorCode::InvalidData {
                                 log::error!("add_challenge_resp order_id {} failed {}", witness.order_id.as_str(), e);
                                 continue;
                             }
                         }
-                        let mut state = self.challenge_state.lock().unwrap();
-                        state.insert(challenge.order_id.to_string().clone(), ChallengeState::RespChallenge);
+                        self.set_challenge_state(challenge.order_id.to_string().as_str(), challenge.challenge_times, Some(ChallengeState::RespChallenge)).await?;
                     } else if state.unwrap() == ChallengeState::RespChallenge {
-                        let chunk_map = if challenge.data_id < meta_max_id {
-                            let meta_data = conn.get_contract_meta_data(contract_id).await?.to_vec()?;
-                            let mut chunk_list: HashMap<ChunkId, Box<dyn Chunk<Target=[u8]>>> = HashMap::new();
-                            let mut meta_ptr = meta_data.as_slice();
-                            while meta_ptr.len() > chunk_size as usize {
-                                let chunk = Box::new(MemChunk::from(meta_ptr[..chunk_size as usize].to_vec()));
-                                chunk_list.insert(chunk.calculate_id(), chunk);
-                                meta_ptr = &meta_ptr[chunk_size as usize..];
-                            }
-                            let chunk = Box::new(MemChunk::from(meta_ptr.to_vec()));
-                            chunk_list.insert(chunk.calculate_id(), chunk);
-                            Some(chunk_list)
+                        let stored_proof = match conn.get_contract_merkle_tree(contract_id).await? {
+                            Some(tree_data) => match self.gen_stored_tree_proof(contract_id, &contract_info, tree_data, challenge.data_id, chunk_size).await {
+                                Ok(proof) => Some(proof),
+                                Err(e) => {
+                                    log::error!("contract {} prove {} by the saved tree err {}, rebuild the tree", contract_id.to_string(), challenge.data_id, e);
+                                    None
+                                }
+                            },
+                            None => None
+                        };
+                        let proof = if let Some(proof) = stored_proof {
+                            proof
                         } else {
-                            None
+                            // the meta bytes are read only by a proof of them
+                            let meta_data = if challenge.data_id < meta_max_id {
+                                conn.get_contract_meta_data(contract_id).await?.to_vec()?
+                            } else {
+                                Vec::new()
+                            };
+                            let chunk_list = conn.get_chunk_list(contract_id).await?;
+                            let mut chunk_hash_list = conn.get_chunk_merkle_root(&chunk_list, chunk_size).await?;
+                            let mut hash_list: Vec<(ChunkId, HashValue)> = contract_info.meta_merkle.iter().map(|v| (ChunkId::default(), v.clone())).collect();
+                            hash_list.append(&mut chunk_hash_list);
+                            let reader = ContractDataReader::new(self.raw_data_store.clone(), chunk_list, chunk_size)?
+                                .with_meta(meta_data, contract_info.meta_merkle.len() as u64 * chunk_size as u64);
+                            let mut merkle_tree = self.build_merkle_tree(reader, hash_list, chunk_size).await?;
+                            merkle_tree.gen_proof(challenge.data_id).await?
                         };
-                        let chunk_list = conn.get_chunk_list(contract_id).await?;
-                        let mut chunk_hash_list = conn.get_chunk_merkle_root(&chunk_list, chunk_size).await?;
-                        let mut hash_list: Vec<(ChunkId, HashValue)> = contract_info.meta_merkle.iter().map(|v| (ChunkId::default(), v.clone())).collect();
-                        hash_list.append(&mut chunk_hash_list);
-                        let chunk_list: Vec<ChunkId> = hash_list.iter().map(|v| v.0.clone()).collect();
-                        let reader = AsyncMerkleChunkReader::new(MerkleChunkReader::new(
-                            self.raw_data_store.clone(), chunk_list, chunk_size, chunk_map));
-                        let mut merkle_tree = self.build_merkle_tree(reader, hash_list, chunk_size).await?;
-                        let proof = merkle_tree.gen_proof(challenge.data_id).await?;
-                        self.dmc_client.arbitration(challenge.order_id.to_string().as_str(), proof.piece,
-                                                    proof.path_list.iter().map(|item|  HashValue::from(item)).collect()).await?;
-                        let mut state = self.challenge_state.lock().unwrap();
-                        state.insert(challenge.order_id.to_string().clone(), ChallengeState::Arbitration);
+                        self.send_tracked(challenge.order_id.to_string().as_str(), DMCTrackedAction::Arbitration {
+                            data: hex::encode(proof.piece.as_slice()),
+                            cut_merkle: proof.path_list.iter().map(|item| HashValue::from(item).to_string()).collect()
+                        }, 0).await?;
+                        self.set_challenge_state(challenge.order_id.to_string().as_str(), challenge.challenge_times, Some(ChallengeState::Arbitration)).await?;
                     }
                 }
             }
@@ -220,8 +420,137 @@ impl<
         Ok(())
     }
 
+    // a single round of the challenge loop, the in-memory harness steps through it
+    #[cfg(any(test, feature = "simulator"))]
+    pub async fn check_challenge_once(&self) -> BuckyResult<()> {
+        self.check_challenge().await
+    }
+
+    #[cfg(any(test, feature = "simulator"))]
+    pub async fn check_txs_once(&self) -> BuckyResult<()> {
+        self.check_txs().await
+    }
+
+    // the bytes of a chunk of the contract tree, the meta chunks come first
+    async fn read_tree_chunk(&self, conn: &mut CONN, contract_id: &ObjectId, meta_count: u64, chunk_list: &Vec<ChunkId>, chunk_index: u64, chunk_size: u32) -> BuckyResult<Vec<u8>> {
+        if chunk_index < meta_count {
+            let meta_data = conn.get_contract_meta_data(contract_id).await?.to_vec()?;
+            // the last meta chunk takes the rest, the same as at sync
+            let start = std::cmp::min(chunk_index as usize * chunk_size as usize, meta_data.len());
+            let end = if chunk_index + 1 == meta_count { meta_data.len() } else { std::cmp::min(start + chunk_size as usize, meta_data.len()) };
+            Ok(meta_data[start..end].to_vec())
+        } else {
+            let chunk_id = chunk_list.get((chunk_index - meta_count) as usize).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::OutOfLimit, "chunk {} is out of contract {}", chunk_index, contract_id)
+            })?;
+            ContractDataReader::get_contract_data(self.raw_data_store.clone(), vec![chunk_id.clone()], 0..chunk_size as u64, chunk_size).await
+        }
+    }
+
+    // proves the piece by the upper tree saved at sync, only the chunk holding it is hashed
+    async fn gen_stored_tree_proof(&self, contract_id: &ObjectId, contract_info: &ContractInfo, tree_data: Vec<u8>, data_id: u64, chunk_size: u32) -> BuckyResult<SinglePieceProof> {
+        let mut conn = self.contract_store.create_meta_connection().await?;
+        let chunk_leafs = chunk_size as u64 / DSG_CHUNK_PIECE_SIZE;
+        let chunk_index = data_id / chunk_leafs;
+        let piece_index = data_id % chunk_leafs;
+        let meta_count = contract_info.meta_merkle.len() as u64;
+        let chunk_list = conn.get_chunk_list(contract_id).await?;
+        let upper = HashVecStore::<Vec<u8>>::load(meta_count + chunk_list.len() as u64, tree_data)?;
+
+        let data = self.read_tree_chunk(&mut conn, contract_id, meta_count, &chunk_list, chunk_index, chunk_size).await?;
+        let mut chunk_tree = MerkleTree::create_from_raw(
+            MerkleMemoryChunkReader::new(data.as_slice(), chunk_size),
+            HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_leafs)?).await?;
+        let proof = chunk_tree.gen_proof(piece_index).await?;
+        proof.extend(chunk_index, chunk_leafs, &upper).await
+    }
+
+    // proves pieces of the whole contract sampled by the seed against the merkle root of the order,
+    // None if the tree wasn't saved at sync
+    pub async fn gen_sampled_proof(&self, contract_id: &ObjectId, sample_count: usize, seed: &[u8]) -> BuckyResult<Option<MultiPieceProof>> {
+        let mut conn = self.contract_store.create_meta_connection().await?;
+        let contract_info = conn.get_contract_info(contract_id).await?;
+        let tree_data = match conn.get_contract_merkle_tree(contract_id).await? {
+            Some(tree_data) => tree_data,
+            None => return Ok(None)
+        };
+        let contract = conn.get_contract(contract_id).await?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find contract {}", contract_id)
+        })?;
+        let chunk_size = DsgContractObjectRef::from(&contract).witness().chunk_size.unwrap_or(CHUNK_SIZE as u32);
+        let chunk_leafs = chunk_size as u64 / DSG_CHUNK_PIECE_SIZE;
+        let meta_count = contract_info.meta_merkle.len() as u64;
+        let chunk_list = conn.get_chunk_list(contract_id).await?;
+        let chunk_count = meta_count + chunk_list.len() as u64;
+        let upper = HashVecStore::<Vec<u8>>::load(chunk_count, tree_data)?;
+
+        let mut chunk_pieces: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for index in sample_piece_indices(chunk_count * chunk_leafs, sample_count, seed) {
+            chunk_pieces.entry(index / chunk_leafs).or_default().push(index % chunk_leafs);
+        }
+        let mut proofs = BTreeMap::new();
+        for (chunk_index, piece_indices) in chunk_pieces.iter() {
+            let data = self.read_tree_chunk(&mut conn, contract_id, meta_count, &chunk_list, *chunk_index, chunk_size).await?;
+            let mut chunk_tree = MerkleTree::create_from_raw(
+                MerkleMemoryChunkReader::new(data.as_slice(), chunk_size),
+                HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_leafs)?).await?;
+            for piece_index in piece_indices.iter() {
+                let proof = chunk_tree.gen_proof(*piece_index).await?.extend(*chunk_index, chunk_leafs, &upper).await?;
+                proofs.insert(proof.piece_index, proof);
+            }
+        }
+        Ok(Some(MultiPieceProof::from_proofs(chunk_count * chunk_leafs, proofs)?))
+    }
+
+    // proves pieces sampled by the seed from the stored data and checks them with the chunk roots saved at sync,
+    // returns false if any chunk doesn't match, so the data is lost before a challenge finds it
+    pub async fn audit_contract(&self, contract_id: &ObjectId, sample_count: usize, seed: &[u8]) -> BuckyResult<bool> {
+        let mut conn = self.contract_store.create_meta_connection().await?;
+        let contract_info = conn.get_contract_info(contract_id).await?;
+        if contract_info.contract_status != ContractStatus::Storing {
+            return Ok(true);
+        }
+        let tree_data = match conn.get_contract_merkle_tree(contract_id).await? {
+            Some(tree_data) => tree_data,
+            None => {
+                log::info!("contract {} has no saved tree, skip the audit", contract_id.to_string());
+                return Ok(true);
+            }
+        };
+        let contract = conn.get_contract(contract_id).await?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find contract {}", contract_id)
+        })?;
+        let chunk_size = DsgContractObjectRef::from(&contract).witness().chunk_size.unwrap_or(CHUNK_SIZE as u32);
+        let chunk_leafs = chunk_size as u64 / DSG_CHUNK_PIECE_SIZE;
+        let meta_count = contract_info.meta_merkle.len() as u64;
+        let chunk_list = conn.get_chunk_list(contract_id).await?;
+        let upper = MerkleAccumulator::<Sha256Hasher>::load(meta_count + chunk_list.len() as u64, tree_data.as_slice())?;
+
+        // pieces of the whole contract are sampled, then proved chunk by chunk
+        let mut chunk_pieces: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for index in sample_piece_indices(upper.leaf_count() * chunk_leafs, sample_count, seed) {
+            chunk_pieces.entry(index / chunk_leafs).or_default().push(index % chunk_leafs);
+        }
+        let mut passed = true;
+        for (chunk_index, piece_indices) in chunk_pieces.iter() {
+            let chunk_root = upper.get_leaf(*chunk_index).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::OutOfLimit, "chunk {} is out of contract {}", chunk_index, contract_id)
+            })?;
+            let data = self.read_tree_chunk(&mut conn, contract_id, meta_count, &chunk_list, *chunk_index, chunk_size).await?;
+            let mut chunk_tree = MerkleTree::create_from_raw(
+                MerkleMemoryChunkReader::new(data.as_slice(), chunk_size),
+                HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_leafs)?).await?;
+            let proof = chunk_tree.gen_multi_proof(piece_indices.as_slice()).await?;
+            if !proof.verify(chunk_root) {
+                log::error!("contract {} chunk {} pieces {:?} don't match the saved root", contract_id.to_string(), chunk_index, piece_indices);
+                passed = false;
+            }
+        }
+        Ok(passed)
+    }
+
     async fn build_merkle_tree<
-        READ: async_std::io::Read + async_std::io::Seek + Send + Unpin>(&self, reader: READ, hash_list: Vec<(ChunkId, HashValue)>, chunk_size: u32) -> BuckyResult<MerkleTree<READ, MinerHashStore<Vec<u8>, CONN>>> {
+        READ: async_std::io::Read + async_std::io::Seek + Send + Unpin>(&self, reader: READ, hash_list: Vec<(ChunkId, HashValue)>, chunk_size: u32) -> BuckyResult<MerkleTree<READ, MinerHashStore<SpillVec, CONN>>> {
         let leafs = chunk_size as u64 / DSG_CHUNK_PIECE_SIZE;
         let mut layer = 0;
         let mut count = 1;
@@ -238,7 +567,7 @@ impl<
             layer += 1;
         }
 
-        let hash_store = MinerHashStore::<Vec<u8>, _>::new::<MemVecCache>(
+        let hash_store = MinerHashStore::<SpillVec, _>::new::<SpillVecCache>(
             layer,
             chunk_size,
             hash_list,
@@ -315,7 +644,10 @@ impl<
                 dmc_data.order_id.as_str(), info.pre_merkle_root, info.pre_data_block_count, merkle_root.to_string(), piece_count));
             }
 
-            self.dmc_client.add_merkle(dmc_data.order_id.as_str(), merkle_root, piece_count).await?;
+            self.send_tracked(dmc_data.order_id.as_str(), DMCTrackedAction::AddMerkle {
+                merkle_root: merkle_root.to_string(),
+                data_block_count: piece_count
+            }, 0).await?;
 
         }
         Ok(())
@@ -325,7 +657,48 @@ impl<
         self.dmc_client.get_order_by_id(order_id).await
     }
 
+    pub async fn get_miner_orders(&self, skip: u32, limit: u32) -> BuckyResult<Vec<TrackerDMCOrder>> {
+        self.dmc_client.get_miner_orders(Some((skip, limit))).await
+    }
+
+    pub async fn get_chain_order(&self, order_id: &str) -> BuckyResult<Option<DMCOrder>> {
+        self.dmc_client.get_chain_order(order_id).await
+    }
+
+    pub async fn get_challenge(&self, order_id: &str) -> BuckyResult<Option<DMCChallengeInfo>> {
+        let mut challenge_info = self.dmc_client.get_challenge_info(order_id, None).await?;
+        Ok(challenge_info.rows.pop())
+    }
+
     pub async fn get_bill_list(&self) -> BuckyResult<Vec<BillRecord>> {
         self.dmc_client.get_bill_list(self.dmc_account.as_str(), Some(i32::MAX)).await
     }
+
+    pub async fn get_avg_price(&self) -> BuckyResult<f64> {
+        Ok(self.dmc_client.get_pst_trans_info().await?.avg())
+    }
+
+    pub async fn get_pst_amount(&self) -> BuckyResult<u64> {
+        self.dmc_client.get_pst_amount(self.dmc_account.as_str()).await
+    }
+
+    pub async fn get_stake_info(&self) -> BuckyResult<StakeInfo> {
+        self.dmc_client.get_stake_info(self.dmc_account.as_str()).await
+    }
+
+    pub async fn get_free_space(&self) -> BuckyResult<u64> {
+        self.stack.get_free_space().await
+    }
+
+    pub async fn bill(&self, amount: u64, price: f64, memo: &str) -> BuckyResult<TransResult> {
+        self.dmc_client.bill(amount.to_string(), price, memo.to_string()).await
+    }
+
+    pub async fn unbill(&self, bill_id: u64, memo: &str) -> BuckyResult<TransResult> {
+        self.dmc_client.unbill(bill_id, memo.to_string()).await
+    }
+
+    pub async fn rebill(&self, bill_id: u64, amount: u64, price: f64, memo: &str) -> BuckyResult<TransResult> {
+        self.dmc_client.rebill(bill_id, amount.to_string(), price, memo.to_string()).await
+    }
 }
@@ -0,0 +1,671 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::convert::TryInto;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_std::io::{Cursor, Read};
+use async_std::stream::StreamExt;
+use cyfs_base::*;
+use cyfs_chunk_lib::{Chunk, MemChunk};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use crate::*;
+
+const ERASURE_DIR: &str = "erasure";
+const MANIFEST_FILE: &str = "manifest.json";
+
+// a chunk is striped into data_shards pieces and parity_shards more,
+// any data_shards of them give the chunk back
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageClass {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+}
+
+impl Default for StorageClass {
+    fn default() -> Self {
+        Self {
+            data_shards: 4,
+            parity_shards: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErasureConfig {
+    // chunks are only kept by the inner store if false
+    pub enable: bool,
+    // a directory on each local disk
+    pub volumes: Vec<PathBuf>,
+    // used by contracts naming no class or a class not configured
+    pub default_class: String,
+    pub classes: BTreeMap<String, StorageClass>,
+    // seconds between the scans rebuilding missing or truncated shards, 0 for never
+    pub rebuild_interval: u64,
+    // seconds between the scans reading every shard to find corrupted ones, 0 for never
+    pub scrub_interval: u64,
+    // bytes a scrub reads per second at most
+    pub scrub_rate: u64,
+    // manifests of how many chunks are kept in memory
+    pub manifest_cache: usize,
+    // keep the inner copy of a chunk once it is encoded, for readers going to the noc directly
+    pub keep_inner_copy: bool,
+}
+
+impl Default for ErasureConfig {
+    fn default() -> Self {
+        let mut classes = BTreeMap::new();
+        classes.insert("standard".to_string(), StorageClass::default());
+        Self {
+            enable: false,
+            volumes: vec![],
+            default_class: "standard".to_string(),
+            classes,
+            rebuild_interval: 3600,
+            scrub_interval: 0,
+            scrub_rate: 16 * 1024 * 1024,
+            manifest_cache: 65536,
+            keep_inner_copy: false,
+        }
+    }
+}
+
+// replicated on every volume, the shards are checked with their hashes
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct ShardManifest {
+    class: StorageClass,
+    len: u64,
+    shard_len: u64,
+    shard_hashes: Vec<String>,
+}
+
+impl ShardManifest {
+    fn shard_count(&self) -> usize {
+        self.class.data_shards + self.class.parity_shards
+    }
+}
+
+fn codec(class: &StorageClass) -> BuckyResult<ReedSolomon> {
+    ReedSolomon::new(class.data_shards, class.parity_shards).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidParam, "invalid storage class {:?} {}", class, e)
+    })
+}
+
+fn shard_hash(shard: &[u8]) -> String {
+    hex::encode(Sha256::digest(shard))
+}
+
+// the manifest of a chunk, none if it isn't encoded, dropped first in first out
+struct ManifestCache {
+    capacity: usize,
+    manifests: HashMap<ChunkId, Option<ShardManifest>>,
+    order: VecDeque<ChunkId>,
+}
+
+impl ManifestCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: std::cmp::max(capacity, 1),
+            manifests: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, chunk_id: &ChunkId) -> Option<Option<ShardManifest>> {
+        self.manifests.get(chunk_id).cloned()
+    }
+
+    fn insert(&mut self, chunk_id: &ChunkId, manifest: Option<ShardManifest>) {
+        if self.manifests.insert(chunk_id.clone(), manifest).is_some() {
+            return;
+        }
+        self.order.push_back(chunk_id.clone());
+        while self.order.len() > self.capacity {
+            if let Some(chunk_id) = self.order.pop_front() {
+                self.manifests.remove(&chunk_id);
+            }
+        }
+    }
+}
+
+// written beside and renamed, a reader never sees half a file
+async fn write_file(path: &Path, data: &[u8]) -> BuckyResult<()> {
+    if let Some(dir) = path.parent() {
+        async_std::fs::create_dir_all(dir).await?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    async_std::fs::write(&tmp_path, data).await?;
+    async_std::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+// keeps the chunks erasure coded across local volumes, so losing a disk loses no chunk.
+// chunks not encoded yet, such as the ones just downloaded into the inner store, are read from the inner store
+pub struct ErasureChunkStore<INNER: ContractChunkStore> {
+    inner: INNER,
+    config: ErasureConfig,
+    // only this store writes the manifests, so the cache follows the volumes
+    manifests: Mutex<ManifestCache>,
+}
+
+impl<INNER: ContractChunkStore> ErasureChunkStore<INNER> {
+    pub fn new(inner: INNER, config: ErasureConfig) -> Self {
+        if config.enable {
+            for (name, class) in config.classes.iter() {
+                let shards_per_volume = (class.data_shards + class.parity_shards + config.volumes.len().max(1) - 1) / config.volumes.len().max(1);
+                if shards_per_volume > class.parity_shards {
+                    log::warn!("storage class {} puts {} shards on one of {} volumes, losing it loses chunks", name, shards_per_volume, config.volumes.len());
+                }
+            }
+        }
+        let manifests = Mutex::new(ManifestCache::new(config.manifest_cache));
+        Self {
+            inner,
+            config,
+            manifests
+        }
+    }
+
+    pub fn inner(&self) -> &INNER {
+        &self.inner
+    }
+
+    fn enabled(&self) -> bool {
+        self.config.enable && !self.config.volumes.is_empty()
+    }
+
+    fn storage_class(&self, name: Option<&str>) -> BuckyResult<StorageClass> {
+        let default_class = || {
+            self.config.classes.get(self.config.default_class.as_str()).cloned().ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::NotFound, "default storage class {} isn't configured", self.config.default_class)
+            })
+        };
+        match name {
+            Some(name) => match self.config.classes.get(name) {
+                Some(class) => Ok(class.clone()),
+                None => {
+                    log::warn!("storage class {} isn't configured, use {}", name, self.config.default_class);
+                    default_class()
+                }
+            },
+            None => default_class()
+        }
+    }
+
+    // the shards of a chunk go round the volumes from a place picked by the chunk id
+    fn shard_path(&self, chunk_id: &ChunkId, class: &StorageClass, index: usize) -> PathBuf {
+        let hash = Sha256::digest(chunk_id.to_string().as_bytes());
+        let start = u64::from_le_bytes(hash[..8].try_into().unwrap()) % self.config.volumes.len() as u64;
+        let volume = &self.config.volumes[(start as usize + index) % self.config.volumes.len()];
+        volume.join(ERASURE_DIR).join(chunk_id.to_string()).join(format!("{}_{}_{}.shard", class.data_shards, class.parity_shards, index))
+    }
+
+    fn manifest_path(volume: &Path, chunk_id: &ChunkId) -> PathBuf {
+        volume.join(ERASURE_DIR).join(chunk_id.to_string()).join(MANIFEST_FILE)
+    }
+
+    // the manifest of each volume, none if it's lost
+    async fn read_manifests(&self, chunk_id: &ChunkId) -> Vec<Option<ShardManifest>> {
+        let mut manifests = Vec::with_capacity(self.config.volumes.len());
+        for volume in self.config.volumes.iter() {
+            let manifest = match async_std::fs::read(Self::manifest_path(volume, chunk_id)).await {
+                Ok(data) => serde_json::from_slice::<ShardManifest>(data.as_slice()).ok(),
+                Err(_) => None
+            };
+            manifests.push(manifest);
+        }
+        manifests
+    }
+
+    // a chunk is only encoded again with more parity, the manifest with the most is the latest
+    fn latest_manifest(manifests: &[Option<ShardManifest>]) -> Option<ShardManifest> {
+        manifests.iter().flatten().max_by_key(|v| v.class.parity_shards).cloned()
+    }
+
+    async fn read_manifest(&self, chunk_id: &ChunkId) -> Option<ShardManifest> {
+        if !self.enabled() {
+            return None;
+        }
+        if let Some(manifest) = self.manifests.lock().unwrap().get(chunk_id) {
+            return manifest;
+        }
+        let manifest = Self::latest_manifest(self.read_manifests(chunk_id).await.as_slice());
+        self.manifests.lock().unwrap().insert(chunk_id, manifest.clone());
+        manifest
+    }
+
+    // a shard is taken as there if its file has the length of a shard, the content is only checked by a scrub
+    async fn shard_exists(&self, chunk_id: &ChunkId, manifest: &ShardManifest, index: usize) -> bool {
+        match async_std::fs::metadata(self.shard_path(chunk_id, &manifest.class, index)).await {
+            Ok(meta) => meta.len() == manifest.shard_len,
+            Err(_) => false
+        }
+    }
+
+    async fn read_shard(&self, chunk_id: &ChunkId, manifest: &ShardManifest, index: usize) -> Option<Vec<u8>> {
+        let path = self.shard_path(chunk_id, &manifest.class, index);
+        let shard = async_std::fs::read(&path).await.ok()?;
+        if shard.len() as u64 != manifest.shard_len || shard_hash(shard.as_slice()) != manifest.shard_hashes[index] {
+            log::error!("shard {} of chunk {} is corrupted", path.display(), chunk_id);
+            return None;
+        }
+        Some(shard)
+    }
+
+    async fn write_chunk(&self, chunk_id: &ChunkId, class: &StorageClass, data: &[u8]) -> BuckyResult<()> {
+        let rs = codec(class)?;
+        let shard_len = std::cmp::max((data.len() + class.data_shards - 1) / class.data_shards, 1);
+        let mut shards: Vec<Vec<u8>> = (0..class.data_shards + class.parity_shards).map(|index| {
+            let mut shard = vec![0u8; shard_len];
+            let start = std::cmp::min(index * shard_len, data.len());
+            let end = std::cmp::min(start + shard_len, data.len());
+            shard[..end - start].copy_from_slice(&data[start..end]);
+            shard
+        }).collect();
+        rs.encode(&mut shards).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode chunk {} err {}", chunk_id, e)
+        })?;
+
+        for (index, shard) in shards.iter().enumerate() {
+            write_file(self.shard_path(chunk_id, class, index).as_path(), shard.as_slice()).await?;
+        }
+        // the manifests go last, the chunk is read as before until they're written
+        let manifest = ShardManifest {
+            class: class.clone(),
+            len: data.len() as u64,
+            shard_len: shard_len as u64,
+            shard_hashes: shards.iter().map(|v| shard_hash(v.as_slice())).collect(),
+        };
+        let manifest_data = serde_json::to_vec(&manifest).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode manifest of chunk {} err {}", chunk_id, e)
+        })?;
+        let mut written = 0;
+        for volume in self.config.volumes.iter() {
+            match write_file(Self::manifest_path(volume, chunk_id).as_path(), manifest_data.as_slice()).await {
+                Ok(_) => written += 1,
+                Err(e) => log::error!("write manifest of chunk {} to {} err {}", chunk_id, volume.display(), e)
+            }
+        }
+        if written == 0 {
+            return Err(cyfs_err!(BuckyErrorCode::IoError, "no volume takes the manifest of chunk {}", chunk_id));
+        }
+        self.manifests.lock().unwrap().insert(chunk_id, Some(manifest));
+        Ok(())
+    }
+
+    async fn remove_shards(&self, chunk_id: &ChunkId, class: &StorageClass) {
+        for index in 0..class.data_shards + class.parity_shards {
+            let _ = async_std::fs::remove_file(self.shard_path(chunk_id, class, index)).await;
+        }
+    }
+
+    // reads the data shards covering the range, the lost ones are reconstructed from the others
+    async fn read_range(&self, chunk_id: &ChunkId, manifest: &ShardManifest, range: Range<u64>) -> BuckyResult<Vec<u8>> {
+        let end = std::cmp::min(range.end, manifest.len);
+        let start = std::cmp::min(range.start, end);
+        if start == end {
+            return Ok(vec![]);
+        }
+        let first = (start / manifest.shard_len) as usize;
+        let last = ((end - 1) / manifest.shard_len) as usize;
+
+        let mut shards: Vec<Option<Vec<u8>>> = vec![None; manifest.shard_count()];
+        let mut lost = false;
+        for index in first..=last {
+            shards[index] = self.read_shard(chunk_id, manifest, index).await;
+            lost |= shards[index].is_none();
+        }
+        if lost {
+            for index in (0..first).chain(last + 1..manifest.shard_count()) {
+                shards[index] = self.read_shard(chunk_id, manifest, index).await;
+            }
+            codec(&manifest.class)?.reconstruct_data(&mut shards).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::NotFound, "chunk {} lost too many shards {}", chunk_id, e)
+            })?;
+        }
+
+        let mut data = Vec::with_capacity((end - start) as usize);
+        for index in first..=last {
+            let shard_start = index as u64 * manifest.shard_len;
+            let from = std::cmp::max(start, shard_start) - shard_start;
+            let to = std::cmp::min(end, shard_start + manifest.shard_len) - shard_start;
+            data.extend_from_slice(&shards[index].as_ref().unwrap()[from as usize..to as usize]);
+        }
+        Ok(data)
+    }
+
+    // rewrites the lost shards and the stale manifests of a chunk, returns false if nothing was lost.
+    // without verify a shard is lost if its file is missing or truncated, with verify also if its hash doesn't match
+    async fn repair(&self, chunk_id: &ChunkId, verify: bool) -> BuckyResult<bool> {
+        let manifests = self.read_manifests(chunk_id).await;
+        let manifest = Self::latest_manifest(manifests.as_slice()).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "no manifest of chunk {}", chunk_id)
+        })?;
+        self.manifests.lock().unwrap().insert(chunk_id, Some(manifest.clone()));
+
+        let mut shards: Vec<Option<Vec<u8>>> = vec![None; manifest.shard_count()];
+        let mut lost = vec![];
+        for index in 0..manifest.shard_count() {
+            let exists = if verify {
+                shards[index] = self.read_shard(chunk_id, &manifest, index).await;
+                self.throttle(manifest.shard_len).await;
+                shards[index].is_some()
+            } else {
+                self.shard_exists(chunk_id, &manifest, index).await
+            };
+            if !exists {
+                lost.push(index);
+            }
+        }
+        let stale: Vec<&PathBuf> = self.config.volumes.iter().zip(manifests.iter())
+            .filter(|(_, v)| v.as_ref() != Some(&manifest)).map(|(volume, _)| volume).collect();
+        if lost.is_empty() && stale.is_empty() {
+            return Ok(false);
+        }
+
+        if !lost.is_empty() {
+            // only now the shards left are read, and checked with their hashes before they rebuild the others
+            for index in 0..manifest.shard_count() {
+                if shards[index].is_none() && !lost.contains(&index) {
+                    shards[index] = self.read_shard(chunk_id, &manifest, index).await;
+                }
+            }
+            codec(&manifest.class)?.reconstruct(&mut shards).map_err(|e| {
+                cyfs_err!(BuckyErrorCode::NotFound, "chunk {} lost too many shards {}", chunk_id, e)
+            })?;
+            for index in lost {
+                let path = self.shard_path(chunk_id, &manifest.class, index);
+                if let Err(e) = write_file(path.as_path(), shards[index].as_ref().unwrap().as_slice()).await {
+                    log::error!("rebuild shard {} err {}", path.display(), e);
+                }
+            }
+        }
+        let manifest_data = serde_json::to_vec(&manifest).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::Failed, "encode manifest of chunk {} err {}", chunk_id, e)
+        })?;
+        for volume in stale {
+            if let Err(e) = write_file(Self::manifest_path(volume, chunk_id).as_path(), manifest_data.as_slice()).await {
+                log::error!("rebuild manifest of chunk {} on {} err {}", chunk_id, volume.display(), e);
+            }
+        }
+        Ok(true)
+    }
+
+    // keeps a scrub under scrub_rate bytes per second
+    async fn throttle(&self, bytes: u64) {
+        if self.config.scrub_rate > 0 {
+            async_std::task::sleep(Duration::from_secs_f64(bytes as f64 / self.config.scrub_rate as f64)).await;
+        }
+    }
+
+    // rebuilds the missing or truncated shards of a chunk, returns false if nothing was lost
+    pub async fn repair_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<bool> {
+        self.repair(chunk_id, false).await
+    }
+
+    async fn encoded_chunks(&self) -> BuckyResult<BTreeSet<ChunkId>> {
+        let mut chunk_list = BTreeSet::new();
+        for volume in self.config.volumes.iter() {
+            let dir = volume.join(ERASURE_DIR);
+            let mut entries = match async_std::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("read volume {} err {}", dir.display(), e);
+                    continue;
+                }
+            };
+            while let Some(entry) = entries.next().await {
+                if let Ok(chunk_id) = ChunkId::from_str(entry?.file_name().to_string_lossy().as_ref()) {
+                    chunk_list.insert(chunk_id);
+                }
+            }
+        }
+        Ok(chunk_list)
+    }
+
+    async fn repair_all(&self, verify: bool) -> BuckyResult<usize> {
+        let mut repaired = 0;
+        for chunk_id in self.encoded_chunks().await?.iter() {
+            match self.repair(chunk_id, verify).await {
+                Ok(true) => repaired += 1,
+                Ok(false) => {},
+                Err(e) => log::error!("repair chunk {} err {}", chunk_id, e)
+            }
+        }
+        Ok(repaired)
+    }
+
+    // scans every volume for encoded chunks and rebuilds their missing shards, returns how many were repaired
+    pub async fn rebuild(&self) -> BuckyResult<usize> {
+        self.repair_all(false).await
+    }
+
+    // like rebuild, but reads and hashes every shard at scrub_rate, so corrupted shards are rebuilt too
+    pub async fn scrub(&self) -> BuckyResult<usize> {
+        self.repair_all(true).await
+    }
+
+    pub fn start_rebuild(self: &Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+
+        if self.config.rebuild_interval > 0 {
+            log::info!("start rebuilding shards on {} volumes", self.config.volumes.len());
+            let this = self.clone();
+            async_std::task::spawn(async move {
+                loop {
+                    async_std::task::sleep(Duration::from_secs(this.config.rebuild_interval)).await;
+                    match this.rebuild().await {
+                        Ok(repaired) => if repaired > 0 {
+                            log::info!("rebuilt lost shards of {} chunks", repaired);
+                        },
+                        Err(e) => log::error!("rebuild shards err {}", e)
+                    }
+                }
+            });
+        }
+
+        if self.config.scrub_interval > 0 {
+            log::info!("start scrubbing shards on {} volumes at {} bytes/s", self.config.volumes.len(), self.config.scrub_rate);
+            let this = self.clone();
+            async_std::task::spawn(async move {
+                loop {
+                    async_std::task::sleep(Duration::from_secs(this.config.scrub_interval)).await;
+                    match this.scrub().await {
+                        Ok(repaired) => if repaired > 0 {
+                            log::info!("scrub rebuilt corrupted shards of {} chunks", repaired);
+                        },
+                        Err(e) => log::error!("scrub shards err {}", e)
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<INNER: ContractChunkStore> ContractChunkStore for ErasureChunkStore<INNER> {
+    async fn save_chunk(&self, chunk_id: &ChunkId, buf: &[u8]) -> BuckyResult<()> {
+        if !self.enabled() {
+            return self.inner.save_chunk(chunk_id, buf).await;
+        }
+        if self.read_manifest(chunk_id).await.is_some() {
+            return Ok(());
+        }
+        let class = self.storage_class(None)?;
+        self.write_chunk(chunk_id, &class, buf).await
+    }
+
+    async fn get_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Chunk>> {
+        if self.read_manifest(chunk_id).await.is_none() {
+            return self.inner.get_chunk(chunk_id).await;
+        }
+        let data = self.get_chunk_by_range(chunk_id, 0..chunk_id.len() as u64).await?;
+        Ok(Box::new(MemChunk::from(data)))
+    }
+
+    async fn get_chunk_by_range(&self, chunk_id: &ChunkId, range: Range<u64>) -> BuckyResult<Vec<u8>> {
+        if let Some(manifest) = self.read_manifest(chunk_id).await {
+            match self.read_range(chunk_id, &manifest, range.clone()).await {
+                Ok(data) => return Ok(data),
+                Err(e) => {
+                    if !self.inner.chunk_exists(chunk_id).await {
+                        return Err(e);
+                    }
+                    log::error!("read chunk {} from shards err {}, read the inner store", chunk_id, e);
+                }
+            }
+        }
+        self.inner.get_chunk_by_range(chunk_id, range).await
+    }
+
+    async fn get_chunk_reader(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Unpin + Read + Send + Sync>> {
+        if self.read_manifest(chunk_id).await.is_none() {
+            return self.inner.get_chunk_reader(chunk_id).await;
+        }
+        let data = self.get_chunk_by_range(chunk_id, 0..chunk_id.len() as u64).await?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    async fn chunk_exists(&self, chunk_id: &ChunkId) -> bool {
+        self.read_manifest(chunk_id).await.is_some() || self.inner.chunk_exists(chunk_id).await
+    }
+
+    // encodes the chunks from the inner store, a chunk already encoded is only encoded again with more parity,
+    // the inner copy is dropped once the manifests are written unless keep_inner_copy
+    async fn set_storage_class(&self, chunk_list: &[ChunkId], class: Option<&str>) -> BuckyResult<()> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let class = self.storage_class(class)?;
+        for chunk_id in chunk_list.iter() {
+            let manifest = self.read_manifest(chunk_id).await;
+            if let Some(manifest) = manifest.as_ref() {
+                if manifest.class.parity_shards >= class.parity_shards {
+                    continue;
+                }
+            }
+            let data = self.get_chunk_by_range(chunk_id, 0..chunk_id.len() as u64).await?;
+            self.write_chunk(chunk_id, &class, data.as_slice()).await?;
+            if let Some(manifest) = manifest {
+                if manifest.class != class {
+                    self.remove_shards(chunk_id, &manifest.class).await;
+                }
+            }
+            if !self.config.keep_inner_copy && self.inner.chunk_exists(chunk_id).await {
+                if let Err(e) = self.inner.delete_chunk(chunk_id).await {
+                    log::error!("drop the inner copy of chunk {} err {}", chunk_id, e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_erasure_store {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use crate::*;
+
+    #[test]
+    fn test_lost_volume() {
+        async_std::task::block_on(async {
+            let dirs: Vec<tempfile::TempDir> = (0..4).map(|_| tempfile::tempdir().unwrap()).collect();
+            let mut classes = BTreeMap::new();
+            classes.insert("standard".to_string(), StorageClass { data_shards: 2, parity_shards: 1 });
+            classes.insert("archive".to_string(), StorageClass { data_shards: 2, parity_shards: 2 });
+            let config = ErasureConfig {
+                enable: true,
+                volumes: dirs.iter().map(|v| v.path().to_path_buf()).collect(),
+                default_class: "standard".to_string(),
+                classes,
+                rebuild_interval: 0,
+                scrub_interval: 0,
+                scrub_rate: 0,
+                manifest_cache: 16,
+                keep_inner_copy: false,
+            };
+            let inner = MemChunkStore::new();
+            let data: Vec<u8> = (0..3001u32).map(|v| v as u8).collect();
+            let chunk_id = ChunkId::calculate_sync(data.as_slice()).unwrap();
+            let raw = inner.put(vec![7u8; 100]).unwrap();
+            let store = Arc::new(ErasureChunkStore::new(inner, config));
+
+            // a chunk not encoded is read from the inner store
+            assert_eq!(store.get_chunk_by_range(&raw, 10..20).await.unwrap(), vec![7u8; 10]);
+            store.set_storage_class(&[raw.clone()], Some("archive")).await.unwrap();
+            assert_eq!(store.read_manifest(&raw).await.unwrap().class.parity_shards, 2);
+            // once encoded the inner copy is dropped
+            assert!(!store.inner().chunk_exists(&raw).await);
+            assert_eq!(store.get_chunk_by_range(&raw, 10..20).await.unwrap(), vec![7u8; 10]);
+
+            // the inner store doesn't have it, every read below comes from the shards
+            store.save_chunk(&chunk_id, data.as_slice()).await.unwrap();
+            assert!(!store.inner().chunk_exists(&chunk_id).await);
+            assert_eq!(store.read_manifest(&chunk_id).await.unwrap().class.parity_shards, 1);
+            store.set_storage_class(&[chunk_id.clone()], Some("archive")).await.unwrap();
+            assert_eq!(store.read_manifest(&chunk_id).await.unwrap().class.parity_shards, 2);
+
+            // two volumes lost, any two shards give the data back
+            for dir in dirs.iter().take(2) {
+                std::fs::remove_dir_all(dir.path().join("erasure")).unwrap();
+            }
+            assert_eq!(store.get_chunk_by_range(&chunk_id, 1400..1600).await.unwrap(), data[1400..1600].to_vec());
+            assert_eq!(store.get_chunk_by_range(&chunk_id, 0..chunk_id.len() as u64).await.unwrap(), data);
+
+            assert_eq!(store.rebuild().await.unwrap(), 2);
+            assert_eq!(store.rebuild().await.unwrap(), 0);
+            for dir in dirs.iter().skip(2) {
+                std::fs::remove_dir_all(dir.path().join("erasure")).unwrap();
+            }
+            assert_eq!(store.get_chunk_by_range(&chunk_id, 0..chunk_id.len() as u64).await.unwrap(), data);
+        })
+    }
+
+    #[test]
+    fn test_scrub() {
+        async_std::task::block_on(async {
+            let dirs: Vec<tempfile::TempDir> = (0..3).map(|_| tempfile::tempdir().unwrap()).collect();
+            let mut classes = BTreeMap::new();
+            classes.insert("standard".to_string(), StorageClass { data_shards: 2, parity_shards: 1 });
+            let config = ErasureConfig {
+                enable: true,
+                volumes: dirs.iter().map(|v| v.path().to_path_buf()).collect(),
+                default_class: "standard".to_string(),
+                classes,
+                rebuild_interval: 0,
+                scrub_interval: 0,
+                scrub_rate: 0,
+                manifest_cache: 16,
+                keep_inner_copy: false,
+            };
+            let data: Vec<u8> = (0..3001u32).map(|v| (v * 7) as u8).collect();
+            let chunk_id = ChunkId::calculate_sync(data.as_slice()).unwrap();
+            let store = Arc::new(ErasureChunkStore::new(MemChunkStore::new(), config));
+            store.save_chunk(&chunk_id, data.as_slice()).await.unwrap();
+
+            // a flipped byte keeps the length, only a scrub finds it
+            let shard = dirs.iter().map(|v| v.path().join("erasure").join(chunk_id.to_string()))
+                .flat_map(|dir| std::fs::read_dir(dir).unwrap().map(|v| v.unwrap().path()))
+                .find(|v| v.extension().map(|v| v == "shard").unwrap_or(false)).unwrap();
+            let mut content = std::fs::read(&shard).unwrap();
+            content[0] ^= 0xff;
+            std::fs::write(&shard, content.as_slice()).unwrap();
+            assert_eq!(store.rebuild().await.unwrap(), 0);
+            assert_eq!(store.scrub().await.unwrap(), 1);
+            assert_eq!(store.scrub().await.unwrap(), 0);
+
+            // a truncated one is found by the rebuild
+            std::fs::write(&shard, &content[..10]).unwrap();
+            assert_eq!(store.rebuild().await.unwrap(), 1);
+            assert_eq!(store.get_chunk_by_range(&chunk_id, 0..chunk_id.len() as u64).await.unwrap(), data);
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use super::*;
 use anyhow::Result;
-use async_std::{io::BufReader, task::block_on};
+use async_std::io::BufReader;
 use tide::{Body, Error, Request, StatusCode};
 
 struct StateMiner<CONN: ContractMetaStore, CHUNKSTORE: ContractChunkStore> {
@@ -38,13 +38,9 @@ impl CacheHttp {
             len += chunk_id.len()
         }
 
-        let list = chunks_list.iter().map(|chunk_id| {
-            block_on(state.raw_data_store.get_chunk_reader(chunk_id)).unwrap()
-        }).collect::<Vec<_>>();
+        let reader = ContractDataReader::unpadded(state.raw_data_store.clone(), chunks_list);
 
-        let merge_reader = ReaderTool::merge(list).await;
-
-        Ok(Body::from_reader(BufReader::new(merge_reader), Some(len)))
+        Ok(Body::from_reader(BufReader::new(reader), Some(len)))
     }
 
     async fn get_slice<CONN: ContractMetaStore, CHUNKSTORE: ContractChunkStore>(req: Request<Arc<StateMiner<CONN, CHUNKSTORE>>>) -> tide::Result<Body> {
@@ -67,13 +63,9 @@ impl CacheHttp {
                 len += chunk_id.len()
             }
 
-            let list = chunks_list.iter().map(|chunk_id| {
-                block_on(state.raw_data_store.get_chunk_reader(chunk_id)).unwrap()
-            }).collect::<Vec<_>>();
-
-            let merge_reader = ReaderTool::merge(list).await;
+            let reader = ContractDataReader::unpadded(state.raw_data_store.clone(), file_chunks.to_vec());
 
-            return Ok(Body::from_reader(BufReader::new(merge_reader), Some(len)))
+            return Ok(Body::from_reader(BufReader::new(reader), Some(len)))
         }
         Err(Error::from_str(StatusCode::BadRequest, "params err"))
     }
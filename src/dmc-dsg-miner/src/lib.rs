@@ -20,10 +20,19 @@ mod raw_obj;
 mod service;
 mod app;
 mod merkle_chunk_reader;
+mod contract_data_reader;
+mod erasure_store;
 mod contract_info;
 mod meta_store;
 mod file_downloader;
 mod remote_dmc_tx_sender;
+mod miner_tx_sender;
+mod pricing_agent;
+mod auto_mint;
+mod accounting;
+mod self_audit;
+#[cfg(any(test, feature = "simulator"))]
+mod mem_harness;
 
 pub use miner_config::*;
 pub use miner::*;
@@ -40,8 +49,17 @@ pub(crate) use dmc_dsg_base::*;
 pub use service::*;
 pub use app::*;
 pub use merkle_chunk_reader::*;
+pub use contract_data_reader::*;
+pub use erasure_store::*;
 pub use contract_info::*;
 pub use meta_store::*;
 pub use file_downloader::*;
 pub use remote_dmc_tx_sender::*;
+pub use miner_tx_sender::*;
+pub use pricing_agent::*;
+pub use auto_mint::*;
+pub use accounting::*;
+pub use self_audit::*;
+#[cfg(any(test, feature = "simulator"))]
+pub use mem_harness::*;
 
@@ -11,6 +11,18 @@ use config::builder::DefaultState;
 use config::ConfigBuilder;
 use dmc_dsg_base::*;
 
+// a missing section keeps the feature off, a broken one is reported and ignored
+fn get_config_section<T: Default + serde::de::DeserializeOwned>(config: &config::Config, key: &str) -> T {
+    match config.get(key) {
+        Ok(section) => section,
+        Err(config::ConfigError::NotFound(_)) => T::default(),
+        Err(e) => {
+            log::error!("parse {} config err {}", key, e);
+            T::default()
+        }
+    }
+}
+
 #[async_std::main]
 async fn main() -> Result<()> {
     let status = cyfs_util::process::check_cmd_and_exec(DMCDsgConfig::APP_NAME);
@@ -41,6 +53,21 @@ async fn main() -> Result<()> {
         builder = builder.add_source(config::File::new(&config_path.display().to_string(), config::FileFormat::Toml));
     }
     let config = builder.build().unwrap();
+    let pricing: PricingConfig = get_config_section(&config, "pricing");
+    let auto_mint: AutoMintConfig = get_config_section(&config, "auto_mint");
+    let mut merkle_cache: MerkleCacheConfig = get_config_section(&config, "merkle_cache");
+    if let Some(spill_dir) = merkle_cache.spill_dir.as_ref() {
+        // keep every tree in memory rather than refuse to start
+        if let Err(e) = std::fs::create_dir_all(spill_dir) {
+            log::error!("create merkle spill dir {} err {}, keep the trees in memory", spill_dir.display(), e);
+            merkle_cache.spill_dir = None;
+            merkle_cache.spill_threshold = u64::MAX;
+        }
+    }
+    set_merkle_cache_config(merkle_cache);
+    let erasure: ErasureConfig = get_config_section(&config, "erasure");
+    let self_audit: SelfAuditConfig = get_config_section(&config, "self_audit");
+    let signer: SignerConfig = get_config_section(&config, "signer");
 
     let dec_id = DecApp::generate_id(ObjectId::from_str(DMCDsgConfig::PUB_PEOPLE_ID).unwrap(), DMCDsgConfig::PRODUCT_NAME);
     log::info!("----> dec_id: {}", &dec_id);
@@ -49,9 +76,10 @@ async fn main() -> Result<()> {
     let stack = Arc::new(SharedCyfsStack::open(stack_params).await.unwrap());
     stack.wait_online(None).await.unwrap();
 
-    let meta_store = CyfsStackMetaStore::create(stack.clone()).await.unwrap();
+    let raw_data_store = Arc::new(ErasureChunkStore::new(NocChunkStore::new(stack.clone()), erasure));
+    raw_data_store.start_rebuild();
 
-    let raw_data_store = Arc::new(NocChunkStore::new(stack.clone()));
+    let meta_store = CyfsStackMetaStore::create(stack.clone(), raw_data_store.clone()).await.unwrap();
 
     let app = App::new(
         stack.clone(),
@@ -60,7 +88,11 @@ async fn main() -> Result<()> {
         config.get_string("dmc_server").unwrap(),
         config.get_string("dmc_tracker_server").unwrap(),
         dec_id.clone(),
-        config.get_int("challenge_check_interval").unwrap() as u64).await?;
+        config.get_int("challenge_check_interval").unwrap() as u64,
+        pricing,
+        auto_mint,
+        self_audit,
+        signer).await?;
     if let Err(e) = app.init().await {
         if get_app_err_code(&e) != DMC_DSG_ERROR_REPORT_FAILED {
             BuckyResult::<()>::Err(e).unwrap();
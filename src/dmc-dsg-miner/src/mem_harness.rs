@@ -0,0 +1,1161 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryInto;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use async_std::io::{Cursor, Read};
+use cyfs_base::*;
+use cyfs_chunk_lib::{Chunk, MemChunk};
+use cyfs_dsg_client::*;
+use crate::*;
+use dmc_dsg_base::*;
+
+// in-memory implementations of the traits DmcDsgMiner is generic over, the chain side runs on DMCSimulator
+
+pub const MEM_MINER_ACCOUNT: &str = "miner";
+pub const MEM_PROOF_PATH: &str = "/dsg/service/proof/";
+
+pub type MemDMCTxSender = LocalDMCTxSender<SimpleSignatureProvider>;
+pub type MemDMC = DMCRef<MemCyfsClient, MemMetaConnection, MemChunkStore, MemDMCTxSender>;
+pub type MemMiner = DmcDsgMiner<MemCyfsClient, MemMetaConnection, MemChunkStore, MemFileDownloader, MemDMCTxSender>;
+
+fn new_device(owner: Option<ObjectId>) -> BuckyResult<Device> {
+    let private_key = PrivateKey::generate_rsa(1024)?;
+    Ok(Device::new(
+        owner,
+        UniqueId::default(),
+        vec![],
+        vec![],
+        vec![],
+        private_key.public(),
+        Area::new(0, 0, 0, 0),
+        DeviceCategory::OOD).build())
+}
+
+fn new_people() -> BuckyResult<People> {
+    let private_key = PrivateKey::generate_rsa(1024)?;
+    Ok(People::new(None, vec![], private_key.public(), None, None, None).build())
+}
+
+fn merkle_leafs(merkle_chunk_size: u32) -> u64 {
+    if merkle_chunk_size % DSG_CHUNK_PIECE_SIZE as u32 == 0 {
+        (merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32) as u64
+    } else {
+        (merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32 + 1) as u64
+    }
+}
+
+async fn chunk_merkle_root(chunk_store: Arc<MemChunkStore>, chunk_id: &ChunkId, merkle_chunk_size: u32) -> BuckyResult<MerkleTree<ContractDataReader<MemChunkStore>, HashVecStore<Vec<u8>>>> {
+    MerkleTree::create_from_raw(
+        ContractDataReader::new(chunk_store, vec![chunk_id.clone()], merkle_chunk_size)?,
+        HashVecStore::<Vec<u8>>::new::<MemVecCache>(merkle_leafs(merkle_chunk_size))?).await
+}
+
+// answers a post_object sent to it through MemCyfsClient
+#[async_trait::async_trait]
+pub trait MemObjectHandler: Send + Sync + 'static {
+    async fn on_post(&self, object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<Vec<u8>>;
+}
+
+pub struct MemCyfsClient {
+    device: Device,
+    free_space: Mutex<u64>,
+    objects: Mutex<HashMap<ObjectId, Vec<u8>>>,
+    oods: Mutex<HashMap<ObjectId, ObjectId>>,
+    handlers: Mutex<HashMap<String, Arc<dyn MemObjectHandler>>>,
+}
+
+impl MemCyfsClient {
+    pub fn new(device: Device) -> Self {
+        Self {
+            device,
+            free_space: Mutex::new(u64::MAX),
+            objects: Mutex::new(HashMap::new()),
+            oods: Mutex::new(HashMap::new()),
+            handlers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_free_space(&self, free_space: u64) {
+        *self.free_space.lock().unwrap() = free_space;
+    }
+
+    pub fn put_object<T: RawEncode>(&self, object_id: ObjectId, object: &T) -> BuckyResult<()> {
+        self.objects.lock().unwrap().insert(object_id, object.to_vec()?);
+        Ok(())
+    }
+
+    pub fn set_ood(&self, owner_id: ObjectId, ood_id: ObjectId) {
+        self.oods.lock().unwrap().insert(owner_id, ood_id);
+    }
+
+    pub fn add_handler(&self, target: ObjectId, dec_id: ObjectId, req_path: &str, handler: Arc<dyn MemObjectHandler>) {
+        let path = CyfsPath::new(target, dec_id, req_path).to_path();
+        self.handlers.lock().unwrap().insert(path, handler);
+    }
+}
+
+#[async_trait::async_trait]
+impl CyfsClient for MemCyfsClient {
+    fn local_device(&self) -> Device {
+        self.device.clone()
+    }
+
+    async fn resolve_ood(&self, object_id: ObjectId) -> BuckyResult<ObjectId> {
+        self.oods.lock().unwrap().get(&object_id).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find {}'s ood", object_id)
+        })
+    }
+
+    async fn sign_object(&self, _object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<Vec<u8>> {
+        Ok(object_raw)
+    }
+
+    async fn sign_object2<T: ObjectType + Sync + Send, O: for <'a> RawDecode<'a>>(&self, obj: &NamedObjectBase<T>) -> BuckyResult<O>
+        where <T as ObjectType>::ContentType: BodyContent + RawEncode, <T as ObjectType>::DescType: RawEncodeWithContext<NamedObjectContext> {
+        O::clone_from_slice(obj.to_vec()?.as_slice())
+    }
+
+    async fn get_object<T: for <'a> RawDecode<'a>>(&self, _target: Option<ObjectId>, object_id: ObjectId) -> BuckyResult<T> {
+        let object_raw = self.objects.lock().unwrap().get(&object_id).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find object {}", object_id)
+        })?;
+        T::clone_from_slice(object_raw.as_slice())
+    }
+
+    async fn put_object_with_resp(&self, req_path: &str, object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<Vec<u8>> {
+        let handler = self.handlers.lock().unwrap().get(req_path).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "no handler for {}", req_path)
+        })?;
+        handler.on_post(object_id, object_raw).await
+    }
+
+    async fn put_object_with_resp2<T: RawEncode + for <'a> RawDecode<'a>>(&self, req_path: &str, object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<T> {
+        let resp = self.put_object_with_resp(req_path, object_id, object_raw).await?;
+        T::clone_from_slice(resp.as_slice())
+    }
+
+    async fn get_free_space(&self) -> BuckyResult<u64> {
+        Ok(*self.free_space.lock().unwrap())
+    }
+}
+
+pub struct MemChunkStore {
+    chunks: Mutex<HashMap<ChunkId, Vec<u8>>>,
+}
+
+impl MemChunkStore {
+    pub fn new() -> Self {
+        Self {
+            chunks: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn put(&self, data: Vec<u8>) -> BuckyResult<ChunkId> {
+        let chunk_id = ChunkId::calculate_sync(data.as_slice())?;
+        self.chunks.lock().unwrap().insert(chunk_id.clone(), data);
+        Ok(chunk_id)
+    }
+
+    fn get_data(&self, chunk_id: &ChunkId) -> BuckyResult<Vec<u8>> {
+        self.chunks.lock().unwrap().get(chunk_id).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find chunk {}", chunk_id)
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ContractChunkStore for MemChunkStore {
+    async fn save_chunk(&self, chunk_id: &ChunkId, buf: &[u8]) -> BuckyResult<()> {
+        self.chunks.lock().unwrap().insert(chunk_id.clone(), buf.to_vec());
+        Ok(())
+    }
+
+    async fn get_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Chunk>> {
+        Ok(Box::new(MemChunk::from(self.get_data(chunk_id)?)))
+    }
+
+    async fn get_chunk_by_range(&self, chunk_id: &ChunkId, range: Range<u64>) -> BuckyResult<Vec<u8>> {
+        let data = self.get_data(chunk_id)?;
+        let end = std::cmp::min(range.end as usize, data.len());
+        let start = std::cmp::min(range.start as usize, end);
+        Ok(data[start..end].to_vec())
+    }
+
+    async fn get_chunk_reader(&self, chunk_id: &ChunkId) -> BuckyResult<Box<dyn Unpin + Read + Send + Sync>> {
+        Ok(Box::new(Cursor::new(self.get_data(chunk_id)?)))
+    }
+
+    async fn chunk_exists(&self, chunk_id: &ChunkId) -> bool {
+        self.chunks.lock().unwrap().contains_key(chunk_id)
+    }
+
+    async fn delete_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<()> {
+        self.chunks.lock().unwrap().remove(chunk_id);
+        Ok(())
+    }
+}
+
+// copies the chunks from the stores registered for the source devices
+#[derive(Clone)]
+pub struct MemFileDownloader {
+    target: Arc<MemChunkStore>,
+    sources: Arc<Mutex<HashMap<DeviceId, Arc<MemChunkStore>>>>,
+}
+
+impl MemFileDownloader {
+    pub fn new(target: Arc<MemChunkStore>) -> Self {
+        Self {
+            target,
+            sources: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    pub fn add_source(&self, device_id: DeviceId, chunk_store: Arc<MemChunkStore>) {
+        self.sources.lock().unwrap().insert(device_id, chunk_store);
+    }
+}
+
+#[async_trait::async_trait]
+impl FileDownloader for MemFileDownloader {
+    async fn download(&self, chunk_list: Vec<ChunkId>, source_list: Vec<DeviceId>, _params: DownloadParams, timeout: u64) -> BuckyResult<()> {
+        let sources: Vec<Arc<MemChunkStore>> = {
+            let sources = self.sources.lock().unwrap();
+            source_list.iter().filter_map(|device_id| sources.get(device_id).cloned()).collect()
+        };
+        for chunk_id in chunk_list.iter() {
+            if bucky_time_now() > timeout {
+                return Err(BuckyError::new(BuckyErrorCode::Timeout, ""));
+            }
+            if self.target.chunk_exists(chunk_id).await {
+                continue;
+            }
+            let data = sources.iter().find_map(|source| source.get_data(chunk_id).ok()).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::NotFound, "can't download chunk {} from {:?}", chunk_id, source_list)
+            })?;
+            self.target.save_chunk(chunk_id, data.as_slice()).await?;
+        }
+        Ok(())
+    }
+}
+
+pub struct MemMetaStore {
+    settings: Mutex<HashMap<String, String>>,
+    data: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+    chunk_store: Arc<MemChunkStore>,
+}
+
+impl MemMetaStore {
+    pub fn new(chunk_store: Arc<MemChunkStore>) -> Self {
+        Self {
+            settings: Mutex::new(HashMap::new()),
+            data: Arc::new(Mutex::new(BTreeMap::new())),
+            chunk_store
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MetaStore<MemMetaConnection> for MemMetaStore {
+    async fn get_setting(&self, key: &str, default: &str) -> BuckyResult<String> {
+        Ok(self.settings.lock().unwrap().get(key).cloned().unwrap_or(default.to_string()))
+    }
+
+    async fn set_setting(&self, key: String, value: String) -> BuckyResult<()> {
+        self.settings.lock().unwrap().insert(key, value);
+        Ok(())
+    }
+
+    async fn create_meta_connection(&self) -> BuckyResult<MetaConnectionProxy<MemMetaConnection>> {
+        Ok(MetaConnectionProxy::new(MemMetaConnection {
+            data: self.data.clone(),
+            changes: BTreeMap::new(),
+            chunk_store: self.chunk_store.clone()
+        }))
+    }
+}
+
+// like the op env of the stack, writes are only seen by other connections after commit
+pub struct MemMetaConnection {
+    data: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+    changes: BTreeMap<String, Option<Vec<u8>>>,
+    chunk_store: Arc<MemChunkStore>,
+}
+
+impl MemMetaConnection {
+    fn get<T: for <'de> RawDecode<'de>>(&self, key: &str) -> BuckyResult<Option<T>> {
+        let value = match self.changes.get(key) {
+            Some(value) => value.clone(),
+            None => self.data.lock().unwrap().get(key).cloned(),
+        };
+        match value {
+            Some(value) => Ok(Some(T::clone_from_slice(value.as_slice())?)),
+            None => Ok(None)
+        }
+    }
+
+    fn set<T: RawEncode>(&mut self, key: String, value: &T) -> BuckyResult<()> {
+        self.changes.insert(key, Some(value.to_vec()?));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: String) {
+        self.changes.insert(key, None);
+    }
+
+    fn get_set(&self, key: &str) -> BuckyResult<BTreeSet<ObjectId>> {
+        Ok(self.get::<ContractList>(key)?.map(|v| v.0).unwrap_or_default())
+    }
+
+    fn update_set(&mut self, key: &str, contract_list: &Vec<ObjectId>, add: bool) -> BuckyResult<()> {
+        let mut set = self.get_set(key)?;
+        for contract_id in contract_list.iter() {
+            if add {
+                set.insert(contract_id.clone());
+            } else {
+                set.remove(contract_id);
+            }
+        }
+        self.set(key.to_string(), &ContractList(set))
+    }
+
+    fn contract_key(contract_id: &ObjectId, name: &str) -> String {
+        format!("contracts/{}/{}", contract_id, name)
+    }
+
+    fn state_key(state_id: &ObjectId) -> String {
+        format!("states/{}", state_id)
+    }
+}
+
+#[async_trait::async_trait]
+impl MetaConnection for MemMetaConnection {
+    async fn begin_trans(&mut self) -> BuckyResult<()> {
+        Ok(())
+    }
+
+    async fn commit_trans(&mut self) -> BuckyResult<()> {
+        let mut data = self.data.lock().unwrap();
+        for (key, value) in std::mem::take(&mut self.changes).into_iter() {
+            match value {
+                Some(value) => {
+                    data.insert(key, value);
+                }
+                None => {
+                    data.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback_trans(&mut self) -> BuckyResult<()> {
+        self.changes.clear();
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ContractMetaStore for MemMetaConnection {
+    async fn get_contract(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgContractObject<DMCContractData>>> {
+        self.get(Self::contract_key(contract_id, "contract").as_str())
+    }
+
+    async fn get_contract_id_by_dmc_order(&mut self, dmc_order: &str) -> BuckyResult<Option<ObjectId>> {
+        self.get(format!("dmc_orders/{}", dmc_order).as_str())
+    }
+
+    async fn save_contract(&mut self, contract: &DsgContractObject<DMCContractData>) -> BuckyResult<()> {
+        let contract_id = contract.desc().calculate_id();
+        self.set(Self::contract_key(&contract_id, "contract"), contract)?;
+        let contract_ref = DsgContractObjectRef::from(contract);
+        self.set(format!("dmc_orders/{}", contract_ref.witness().order_id.as_str()), &contract_id)
+    }
+
+    async fn contract_sync_set(&mut self) -> BuckyResult<Vec<ObjectId>> {
+        Ok(self.get_set("list/contract_sync_set")?.into_iter().collect())
+    }
+
+    async fn contract_sync_set_add(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_sync_set", contract_list, true)
+    }
+
+    async fn contract_sync_set_remove(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_sync_set", contract_list, false)
+    }
+
+    async fn contract_set(&mut self) -> BuckyResult<Vec<ObjectId>> {
+        Ok(self.get_set("list/contract_set")?.into_iter().collect())
+    }
+
+    async fn contract_set_add(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_set", contract_list, true)
+    }
+
+    async fn contract_set_remove(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_set", contract_list, false)
+    }
+
+    async fn get_contract_info(&mut self, contract_id: &ObjectId) -> BuckyResult<ContractInfo> {
+        self.get(Self::contract_key(contract_id, "info").as_str())?.ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find contract {}'s info", contract_id)
+        })
+    }
+
+    async fn set_contract_info(&mut self, contract_id: &ObjectId, contract_info: &ContractInfo) -> BuckyResult<()> {
+        self.set(Self::contract_key(contract_id, "info"), contract_info)
+    }
+
+    async fn contract_proof_set(&mut self) -> BuckyResult<Vec<ObjectId>> {
+        Ok(self.get_set("list/contract_proof_set")?.into_iter().collect())
+    }
+
+    async fn contract_proof_set_add(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_proof_set", contract_list, true)
+    }
+
+    async fn contract_proof_set_remove(&mut self, contract_list: &Vec<ObjectId>) -> BuckyResult<()> {
+        self.update_set("list/contract_proof_set", contract_list, false)
+    }
+
+    async fn get_contract_state(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgContractStateObject>> {
+        match self.get_contract_state_id(contract_id).await? {
+            Some(state_id) => self.get_state(state_id).await,
+            None => Ok(None)
+        }
+    }
+
+    async fn get_contract_state_id(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<ObjectId>> {
+        self.get(Self::contract_key(contract_id, "state").as_str())
+    }
+
+    async fn get_syncing_contract_state(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgContractStateObject>> {
+        match self.get::<ObjectId>(Self::contract_key(contract_id, "syncing_state").as_str())? {
+            Some(state_id) => self.get_state(state_id).await,
+            None => Ok(None)
+        }
+    }
+
+    async fn save_need_sync_contract_state(&mut self, contract_id: &ObjectId, state: &DsgContractStateObject) -> BuckyResult<()> {
+        let state_id = DsgContractStateObjectRef::from(state).id();
+        self.set(Self::state_key(&state_id), state)?;
+        self.set(Self::contract_key(contract_id, "syncing_state"), &state_id)
+    }
+
+    async fn set_contract_state_sync_complete(&mut self, contract_id: &ObjectId, state_id: &ObjectId) -> BuckyResult<()> {
+        let syncing_id = self.get::<ObjectId>(Self::contract_key(contract_id, "syncing_state").as_str())?;
+        if syncing_id.as_ref() != Some(state_id) {
+            return Err(cyfs_err!(BuckyErrorCode::ErrorState, "contract {} is syncing {:?}, not {}", contract_id, syncing_id, state_id));
+        }
+        self.remove(Self::contract_key(contract_id, "syncing_state"));
+        self.set(Self::contract_key(contract_id, "state"), state_id)
+    }
+
+    async fn save_state_id_by_path(&mut self, path: String, object_id: &ObjectId) -> BuckyResult<()> {
+        self.set(format!("http_path/{}", path), object_id)
+    }
+
+    async fn get_state_id_by_path(&mut self, path: String) -> BuckyResult<Option<ObjectId>> {
+        self.get(format!("http_path/{}", path).as_str())
+    }
+
+    async fn save_state(&mut self, state: &DsgContractStateObject) -> BuckyResult<()> {
+        let state_id = DsgContractStateObjectRef::from(state).id();
+        self.set(Self::state_key(&state_id), state)
+    }
+
+    async fn get_state(&mut self, state_id: ObjectId) -> BuckyResult<Option<DsgContractStateObject>> {
+        self.get(Self::state_key(&state_id).as_str())
+    }
+
+    async fn get_chunks_by_path(&mut self, url_path: String) -> BuckyResult<Vec<ChunkId>> {
+        if let Some(state_id) = self.get_state_id_by_path(url_path).await? {
+            if let Some(state) = self.get_state(state_id).await? {
+                let state_ref = DsgContractStateObjectRef::from(&state);
+                if let DsgContractState::DataSourceChanged(change) = state_ref.state() {
+                    return Ok(change.chunks.clone());
+                }
+            }
+        }
+        Ok(vec![])
+    }
+
+    async fn get_chunk_list(&mut self, contract_id: &ObjectId) -> BuckyResult<Vec<ChunkId>> {
+        Ok(self.get::<ContractChunkList>(Self::contract_key(contract_id, "chunk_list").as_str())?.map(|v| v.0).unwrap_or_default())
+    }
+
+    async fn save_chunk_list(&mut self, contract_id: &ObjectId, chunk_list: Vec<ChunkId>) -> BuckyResult<()> {
+        self.set(Self::contract_key(contract_id, "chunk_list"), &ContractChunkList(chunk_list))
+    }
+
+    async fn get_contract_merkle_tree(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<Vec<u8>>> {
+        Ok(self.get::<ContractMerkleTree>(Self::contract_key(contract_id, "merkle_tree").as_str())?.map(|v| v.0))
+    }
+
+    async fn save_contract_merkle_tree(&mut self, contract_id: &ObjectId, tree_data: Vec<u8>) -> BuckyResult<()> {
+        self.set(Self::contract_key(contract_id, "merkle_tree"), &ContractMerkleTree(tree_data))
+    }
+
+    async fn get_challenge(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgChallengeObject>> {
+        self.get(Self::contract_key(contract_id, "challenge").as_str())
+    }
+
+    async fn save_challenge(&mut self, contract_id: &ObjectId, challenge: &DsgChallengeObject) -> BuckyResult<()> {
+        self.set(Self::contract_key(contract_id, "challenge"), challenge)
+    }
+
+    async fn chunk_ref_add(&mut self, contract_id: &ObjectId, chunk_list: &Vec<ChunkId>) -> BuckyResult<()> {
+        let mut map = self.get::<ChunkRefMap>("chunk_ref")?.map(|v| v.0).unwrap_or_default();
+        for chunk_id in chunk_list.iter() {
+            map.entry(chunk_id.clone()).or_insert(BTreeSet::new()).insert(contract_id.clone());
+        }
+        self.set("chunk_ref".to_string(), &ChunkRefMap(map))
+    }
+
+    async fn chunk_ref_del(&mut self, contract_id: &ObjectId, chunk_list: &Vec<ChunkId>) -> BuckyResult<()> {
+        let mut map = self.get::<ChunkRefMap>("chunk_ref")?.map(|v| v.0).unwrap_or_default();
+        for chunk_id in chunk_list.iter() {
+            if let Some(set) = map.get_mut(chunk_id) {
+                set.remove(contract_id);
+            }
+        }
+        self.set("chunk_ref".to_string(), &ChunkRefMap(map))
+    }
+
+    async fn chunk_del_list_del(&mut self, chunk_list: &Vec<ChunkId>) -> BuckyResult<()> {
+        let mut set = self.get::<DelSet>("del_list")?.map(|v| v.0).unwrap_or_default();
+        for chunk_id in chunk_list.iter() {
+            set.remove(chunk_id);
+        }
+        self.set("del_list".to_string(), &DelSet(set))
+    }
+
+    async fn get_del_chunk_list(&mut self) -> BuckyResult<Vec<ChunkId>> {
+        Ok(self.get::<DelSet>("del_list")?.map(|v| v.0.into_iter().collect()).unwrap_or_default())
+    }
+
+    async fn get_chunk_merkle_root(&mut self, chunk_list: &Vec<ChunkId>, merkle_chunk_size: u32) -> BuckyResult<Vec<(ChunkId, HashValue)>> {
+        let mut hash_list = Vec::new();
+        for chunk_id in chunk_list.iter() {
+            let merkle = chunk_merkle_root(self.chunk_store.clone(), chunk_id, merkle_chunk_size).await?;
+            hash_list.push((chunk_id.clone(), HashValue::from(merkle.root())));
+        }
+        Ok(hash_list)
+    }
+
+    async fn get_chunk_merkle_data(&mut self, chunk_id: &ChunkId, merkle_chunk_size: u32) -> BuckyResult<(HashValue, Vec<u8>)> {
+        let merkle = chunk_merkle_root(self.chunk_store.clone(), chunk_id, merkle_chunk_size).await?;
+        let data = merkle.get_cache().get_data(0)?;
+        Ok((HashValue::from(merkle.root()), data.to_vec()))
+    }
+}
+
+struct ConsumerContract {
+    contract: DsgContractObject<DMCContractData>,
+    chunk_size: u32,
+    chunks: Vec<ChunkId>,
+    // newest first, the same order as the state list of MetaData
+    states: Vec<DsgContractStateObject>,
+}
+
+// plays the user of the orders, issues challenges and checks the proofs the miner posts back
+pub struct MemConsumer {
+    owner_id: ObjectId,
+    device_id: DeviceId,
+    miner_id: ObjectId,
+    client: Arc<MemCyfsClient>,
+    chunk_store: Arc<MemChunkStore>,
+    dmc_client: DMCClient<MemDMCTxSender>,
+    contracts: Mutex<HashMap<ObjectId, ConsumerContract>>,
+    // the whole chunk list and the changed chunks of each state
+    state_chunks: Mutex<HashMap<ObjectId, (Vec<ChunkId>, Vec<ChunkId>)>>,
+    challenges: Mutex<HashMap<ObjectId, DsgChallengeObject>>,
+    proofs: Mutex<Vec<(ObjectId, bool)>>,
+    sampled_proofs: Mutex<Vec<(String, bool)>>,
+}
+
+impl MemConsumer {
+    pub fn owner_id(&self) -> &ObjectId {
+        &self.owner_id
+    }
+
+    pub fn device_id(&self) -> &DeviceId {
+        &self.device_id
+    }
+
+    pub fn dmc_client(&self) -> &DMCClient<MemDMCTxSender> {
+        &self.dmc_client
+    }
+
+    pub fn chunk_list(&self, contract_id: &ObjectId) -> Vec<ChunkId> {
+        self.contracts.lock().unwrap().get(contract_id).map(|v| v.chunks.clone()).unwrap_or_default()
+    }
+
+    // contract id and whether the proof was verified, in the order they arrived
+    pub fn proofs(&self) -> Vec<(ObjectId, bool)> {
+        self.proofs.lock().unwrap().clone()
+    }
+
+    // order id and whether the sampled pieces matched the merkle root on chain
+    pub fn sampled_proofs(&self) -> Vec<(String, bool)> {
+        self.sampled_proofs.lock().unwrap().clone()
+    }
+
+    async fn verify_sampled_proof(&self, req: &SampledProofReq) -> BuckyResult<bool> {
+        let challenge_id = ObjectId::from_str(req.challenge_id.as_str())?;
+        if !self.challenges.lock().unwrap().contains_key(&challenge_id) {
+            return Err(cyfs_err!(BuckyErrorCode::NotFound, "can't find challenge {}", challenge_id));
+        }
+        let proof = PieceSetProof::decode_raw(hex::decode(req.proof.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "decode sampled proof err {}", e)
+        })?.as_slice())?;
+        let challenge_ret = self.dmc_client.get_challenge_info(req.order_id.as_str(), Some(1)).await?;
+        let challenge = challenge_ret.rows.get(0).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "order {} has no challenge on chain", req.order_id)
+        })?;
+        let report = verify_sampled_proof(
+            challenge.merkle_root.as_str(),
+            challenge.data_block_count,
+            &proof,
+            MerkleHashAlgorithm::Sha256,
+            req.sample_count as usize,
+            challenge_id.as_slice())?;
+        Ok(report.is_valid() && report.challenge_matched)
+    }
+
+    async fn merkle_root(&self, contract: &ConsumerContract) -> BuckyResult<(HashValue, u64)> {
+        let chunk_size = contract.chunk_size as usize;
+        let meta_block = MetaData {
+            contract: contract.contract.clone(),
+            state_list: contract.states.clone()
+        }.to_vec()?;
+        let mut meta_ref = &meta_block[..];
+        let mut hash_list = Vec::new();
+        if meta_ref.len() > chunk_size {
+            hash_list.push(Self::memory_merkle_root(&meta_ref[..chunk_size], contract.chunk_size).await?);
+            meta_ref = &meta_ref[chunk_size..];
+        }
+        hash_list.push(Self::memory_merkle_root(meta_ref, contract.chunk_size).await?);
+        for chunk_id in contract.chunks.iter() {
+            let merkle = chunk_merkle_root(self.chunk_store.clone(), chunk_id, contract.chunk_size).await?;
+            hash_list.push(HashValue::from(merkle.root()));
+        }
+
+        let file_size = (hash_list.len() * chunk_size) as u64;
+        let data_block_count = if file_size % DSG_CHUNK_PIECE_SIZE == 0 { file_size / DSG_CHUNK_PIECE_SIZE } else { file_size / DSG_CHUNK_PIECE_SIZE + 1 };
+        let mut hash_store = HashVecStore::<Vec<u8>>::new::<MemVecCache>(hash_list.len() as u64)?;
+        for (index, hash) in hash_list.iter().enumerate() {
+            hash_store.set_node(0, index as u64, hash.as_slice().try_into().unwrap()).await?;
+        }
+        let merkle = MerkleTree::<Cursor<Vec<u8>>, HashVecStore<Vec<u8>>>::create_from_base(None, hash_store, 0).await?;
+        Ok((HashValue::from(merkle.root()), data_block_count))
+    }
+
+    async fn memory_merkle_root(data: &[u8], chunk_size: u32) -> BuckyResult<HashValue> {
+        let merkle = MerkleTree::create_from_raw(
+            MerkleMemoryChunkReader::new(data, chunk_size),
+            HashVecStore::<Vec<u8>>::new::<MemVecCache>(merkle_leafs(chunk_size))?).await?;
+        Ok(HashValue::from(merkle.root()))
+    }
+
+    fn new_state(&self, contract_id: &ObjectId, contract: &mut ConsumerContract, data: Vec<Vec<u8>>) -> BuckyResult<ObjectId> {
+        let changed = data.into_iter().map(|v| self.chunk_store.put(v)).collect::<BuckyResult<Vec<ChunkId>>>()?;
+        contract.chunks.extend(changed.iter().cloned());
+        let prev_change = contract.states.get(0).map(|v| DsgContractStateObjectRef::from(v).id());
+        let state = DsgContractStateObjectRef::new(contract_id.clone(), DsgContractState::DataSourceChanged(DsgDataSourceChangedState {
+            chunks: changed.clone(),
+            prev_change,
+            stored_hash: Some(hash_data(contract.chunks.to_vec()?.as_slice()))
+        }));
+        let state_id = DsgContractStateObjectRef::from(&state).id();
+        self.client.put_object(state_id.clone(), &state)?;
+        self.state_chunks.lock().unwrap().insert(state_id.clone(), (contract.chunks.clone(), changed));
+        contract.states.insert(0, state);
+        Ok(state_id)
+    }
+
+    // the user commits the merkle root of the data first, the miner's addmerkle has to match it
+    async fn commit_merkle_root(&self, order_id: &str, contract: &ConsumerContract) -> BuckyResult<()> {
+        let (merkle_root, data_block_count) = self.merkle_root(contract).await?;
+        self.dmc_client.add_merkle(order_id, merkle_root, data_block_count).await?;
+        Ok(())
+    }
+
+    pub async fn new_contract(&self, order_id: &str, chunk_size: u32, data: Vec<Vec<u8>>) -> BuckyResult<(ObjectId, ObjectId)> {
+        let now = bucky_time_now();
+        let contract = DsgContractObjectRef::new(self.owner_id.clone(), DsgContract {
+            data_source: DsgDataSource::Mutable(data.iter().map(|v| v.len() as u64).sum()),
+            storage: DsgStorage::Cache(DsgCacheStorage { pub_http: None }),
+            miner: self.miner_id.clone(),
+            start_at: now,
+            end_at: now + 24 * 3600 * 1000000,
+            witness_dec_id: None,
+            witness: DMCContractData {
+                order_id: order_id.to_string(),
+                miner_dmc_account: MEM_MINER_ACCOUNT.to_string(),
+                merkle_root: None,
+                chunk_size: Some(chunk_size),
+                merkle_hash: None,
+                storage_class: None,
+            }
+        });
+        let contract_id = contract.desc().calculate_id();
+        self.client.put_object(contract_id.clone(), &contract)?;
+
+        let mut consumer_contract = ConsumerContract {
+            contract,
+            chunk_size,
+            chunks: vec![],
+            states: vec![]
+        };
+        let state_id = self.new_state(&contract_id, &mut consumer_contract, data)?;
+        self.commit_merkle_root(order_id, &consumer_contract).await?;
+        self.contracts.lock().unwrap().insert(contract_id.clone(), consumer_contract);
+        Ok((contract_id, state_id))
+    }
+
+    // appends the data to the contract in a new DataSourceChanged state
+    pub async fn change_data(&self, contract_id: &ObjectId, data: Vec<Vec<u8>>) -> BuckyResult<ObjectId> {
+        let mut contract = self.contracts.lock().unwrap().remove(contract_id).ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find contract {}", contract_id)
+        })?;
+        let ret = match self.new_state(contract_id, &mut contract, data) {
+            Ok(state_id) => {
+                let order_id = contract.contract.desc().content().witness.order_id.clone();
+                self.commit_merkle_root(order_id.as_str(), &contract).await.map(|_| state_id)
+            }
+            Err(e) => Err(e)
+        };
+        self.contracts.lock().unwrap().insert(contract_id.clone(), contract);
+        ret
+    }
+
+    // a challenge of the latest state, live_time in microseconds
+    pub fn challenge(&self, contract_id: &ObjectId, live_time: u64) -> BuckyResult<DsgChallengeObject> {
+        let (state_id, chunks) = {
+            let contracts = self.contracts.lock().unwrap();
+            let contract = contracts.get(contract_id).ok_or_else(|| {
+                cyfs_err!(BuckyErrorCode::NotFound, "can't find contract {}", contract_id)
+            })?;
+            (DsgContractStateObjectRef::from(&contract.states[0]).id(), contract.chunks.clone())
+        };
+        let challenge = DsgChallengeObjectRef::new(
+            self.owner_id.clone(),
+            contract_id.clone(),
+            state_id,
+            &chunks,
+            &DsgChallengeOptions {
+                sample_count: 4,
+                sample_len: 64,
+                live_time
+            });
+        let challenge_id = DsgChallengeObjectRef::from(&challenge).id();
+        self.challenges.lock().unwrap().insert(challenge_id, challenge.clone());
+        Ok(challenge)
+    }
+}
+
+#[async_trait::async_trait]
+impl MemObjectHandler for MemConsumer {
+    async fn on_post(&self, _object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<Vec<u8>> {
+        let proof = DsgProofObject::clone_from_slice(object_raw.as_slice())?;
+        let proof_ref = DsgProofObjectRef::from(&proof);
+        let challenge = self.challenges.lock().unwrap().get(proof_ref.challenge()).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find challenge {}", proof_ref.challenge())
+        })?;
+        let challenge_ref = DsgChallengeObjectRef::from(&challenge);
+        let contract_id = challenge_ref.contract_id().clone();
+        let (chunks, changed) = self.state_chunks.lock().unwrap().get(challenge_ref.contract_state()).cloned().ok_or_else(|| {
+            cyfs_err!(BuckyErrorCode::NotFound, "can't find state {}", challenge_ref.contract_state())
+        })?;
+        let chunks = match challenge_ref.challenge_type() {
+            ChallengeType::Full => chunks,
+            ChallengeType::State => changed,
+        };
+        let verified = proof_ref.verify(challenge_ref, &chunks, Box::new(MinerChunkReader::new(self.chunk_store.clone()))).await?;
+        self.proofs.lock().unwrap().push((contract_id.clone(), verified));
+        if verified {
+            Ok(object_raw)
+        } else {
+            Err(cyfs_err!(BuckyErrorCode::InvalidData, "proof of contract {} verify failed", contract_id))
+        }
+    }
+}
+
+// takes the sampled proofs the miner posts after a dsg proof
+pub struct MemSampledProofHandler {
+    consumer: Arc<MemConsumer>,
+}
+
+#[async_trait::async_trait]
+impl MemObjectHandler for MemSampledProofHandler {
+    async fn on_post(&self, _object_id: ObjectId, object_raw: Vec<u8>) -> BuckyResult<Vec<u8>> {
+        let req: SampledProofReq = JSONObject::clone_from_slice(object_raw.as_slice())?.get()?;
+        let verified = self.consumer.verify_sampled_proof(&req).await?;
+        self.consumer.sampled_proofs.lock().unwrap().push((req.order_id.clone(), verified));
+        if verified {
+            Ok(object_raw)
+        } else {
+            Err(cyfs_err!(BuckyErrorCode::InvalidData, "sampled proof of order {} verify failed", req.order_id))
+        }
+    }
+}
+
+// a miner over the in-memory stores, its chain and tracker are a DMCSimulator
+pub struct MemHarness {
+    pub sim: DMCSimulator,
+    pub client: Arc<MemCyfsClient>,
+    pub chunk_store: Arc<MemChunkStore>,
+    pub meta_store: Arc<MemMetaStore>,
+    pub downloader: MemFileDownloader,
+    pub dmc: MemDMC,
+    pub miner: Arc<MemMiner>,
+}
+
+impl MemHarness {
+    pub async fn start() -> BuckyResult<Self> {
+        let sim = DMCSimulator::start().await?;
+        let owner = new_people()?;
+        let client = Arc::new(MemCyfsClient::new(new_device(Some(owner.desc().calculate_id()))?));
+        let chunk_store = Arc::new(MemChunkStore::new());
+        let meta_store = Arc::new(MemMetaStore::new(chunk_store.clone()));
+        let dmc_sender = MemDMCTxSender::new(MEM_MINER_ACCOUNT, sim.server(), SimpleSignatureProvider::new(vec![])?);
+        let dmc = DMC::new(
+            client.clone(),
+            dsg_dec_id(),
+            meta_store.clone(),
+            chunk_store.clone(),
+            sim.server(),
+            sim.server(),
+            MEM_MINER_ACCOUNT,
+            String::new(),
+            dmc_sender,
+            3600)?;
+        let downloader = MemFileDownloader::new(chunk_store.clone());
+        let miner = DmcDsgMiner::new(client.clone(), meta_store.clone(), chunk_store.clone(), dmc.clone(), downloader.clone());
+        Ok(Self {
+            sim,
+            client,
+            chunk_store,
+            meta_store,
+            downloader,
+            dmc,
+            miner
+        })
+    }
+
+    // a consumer with its own device and data, bound to the dmc account
+    pub async fn new_consumer(&self, dmc_account: &str) -> BuckyResult<Arc<MemConsumer>> {
+        let owner_id = new_people()?.desc().calculate_id();
+        let device = new_device(Some(owner_id.clone()))?;
+        let device_id = device.desc().device_id();
+        let chunk_store = Arc::new(MemChunkStore::new());
+        let dmc_sender = MemDMCTxSender::new(dmc_account, self.sim.server(), SimpleSignatureProvider::new(vec![])?);
+        let consumer = Arc::new(MemConsumer {
+            owner_id: owner_id.clone(),
+            device_id: device_id.clone(),
+            miner_id: self.client.local_device().desc().device_id().object_id().clone(),
+            client: self.client.clone(),
+            chunk_store: chunk_store.clone(),
+            dmc_client: DMCClient::new(dmc_account, self.sim.server(), self.sim.server(), dmc_sender),
+            contracts: Mutex::new(HashMap::new()),
+            state_chunks: Mutex::new(HashMap::new()),
+            challenges: Mutex::new(HashMap::new()),
+            proofs: Mutex::new(vec![]),
+            sampled_proofs: Mutex::new(vec![]),
+        });
+
+        self.client.put_object(device_id.object_id().clone(), &device)?;
+        self.client.set_ood(owner_id.clone(), device_id.object_id().clone());
+        self.client.add_handler(device_id.object_id().clone(), dsg_dec_id(), MEM_PROOF_PATH, consumer.clone());
+        self.client.add_handler(device_id.object_id().clone(), dsg_dec_id(), SAMPLED_PROOF_PATH, Arc::new(MemSampledProofHandler {
+            consumer: consumer.clone()
+        }));
+        self.downloader.add_source(device_id, chunk_store);
+        consumer.dmc_client.report_cyfs_info(&CyfsInfo {
+            addr: owner_id.to_string(),
+            http: None,
+            v: Some(3),
+            mid: None
+        }).await?;
+        Ok(consumer)
+    }
+
+    // an order of the consumer's dmc account with this miner
+    pub fn new_order(&self, dmc_account: &str, pst: u64) -> BuckyResult<String> {
+        let order_id = self.sim.add_order(self.sim.new_order(dmc_account, MEM_MINER_ACCOUNT, pst))?;
+        Ok(order_id.to_string())
+    }
+}
+
+#[cfg(all(test, not(feature = "no_dmc")))]
+mod test_mem_harness {
+    use std::sync::Arc;
+    use cyfs_base::*;
+    use crate::*;
+
+    const CHUNK_SIZE: u32 = 4096;
+    const LIVE_TIME: u64 = 3600 * 1000000;
+
+    fn test_data(seed: u8, count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![seed.wrapping_mul(16).wrapping_add(i as u8); CHUNK_SIZE as usize]).collect()
+    }
+
+    // a new order whose data is synced by the first challenge, the proof of that challenge is verified
+    async fn first_sync(harness: &MemHarness, consumer: &Arc<MemConsumer>, seed: u8) -> (u64, ObjectId) {
+        let order_id = harness.new_order("user", 1).unwrap();
+        let (contract_id, state_id) = consumer.new_contract(order_id.as_str(), CHUNK_SIZE, test_data(seed, 3)).await.unwrap();
+        let challenge = consumer.challenge(&contract_id, LIVE_TIME).unwrap();
+        harness.miner.on_challenge(challenge, consumer.device_id().object_id().clone()).await.unwrap();
+        harness.miner.sync_contract_once(contract_id.clone()).await.unwrap();
+        harness.miner.resp_proof_once(contract_id.clone()).await.unwrap();
+
+        assert_eq!(harness.miner.get_order_info(order_id.as_str()).await.unwrap(), (contract_id.clone(), state_id));
+        assert_eq!(consumer.proofs().last(), Some(&(contract_id.clone(), true)));
+        assert_eq!(consumer.sampled_proofs().last(), Some(&(order_id.clone(), true)));
+        (order_id.parse().unwrap(), contract_id)
+    }
+
+    #[test]
+    fn test_first_sync() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, contract_id) = first_sync(&harness, &consumer, 1).await;
+
+            let challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            assert_eq!(challenge.state, DMCChallengeState::ChallengeConsistent as u32);
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert_eq!(conn.contract_set().await.unwrap(), vec![contract_id.clone()]);
+            assert!(conn.contract_sync_set().await.unwrap().is_empty());
+            assert!(conn.contract_proof_set().await.unwrap().is_empty());
+            let chunk_list = conn.get_chunk_list(&contract_id).await.unwrap();
+            assert_eq!(chunk_list, consumer.chunk_list(&contract_id));
+            assert!(conn.get_contract_merkle_tree(&contract_id).await.unwrap().is_some());
+            for chunk_id in chunk_list.iter() {
+                assert!(harness.chunk_store.chunk_exists(chunk_id).await);
+            }
+            let contract_info = conn.get_contract_info(&contract_id).await.unwrap();
+            assert_eq!(contract_info.contract_status, ContractStatus::Storing);
+            assert_eq!(contract_info.stored_size, Some(3 * CHUNK_SIZE as u64));
+            assert_eq!(harness.miner.get_dsg_stat().await.unwrap().used_space, 3 * CHUNK_SIZE as u64);
+        });
+    }
+
+    #[test]
+    fn test_data_source_changed() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, contract_id) = first_sync(&harness, &consumer, 1).await;
+
+            // the simulated chain takes a new round of merkle roots for the changed data
+            let mut challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            challenge.state = DMCChallengeState::ChallengePrepare as u32;
+            challenge.merkle_root = String::new();
+            challenge.data_block_count = 0;
+            harness.sim.set_row("dmc.token", "dmchallenge", "dmc.token", order_id.to_string().as_str(), &challenge).unwrap();
+
+            let state_id = consumer.change_data(&contract_id, test_data(2, 2)).await.unwrap();
+            let challenge = consumer.challenge(&contract_id, LIVE_TIME).unwrap();
+            assert!(harness.miner.need_sync_chunk(&contract_id, &state_id).await.unwrap());
+            harness.miner.on_challenge(challenge, consumer.device_id().object_id().clone()).await.unwrap();
+            harness.miner.sync_contract_once(contract_id.clone()).await.unwrap();
+            harness.miner.resp_proof_once(contract_id.clone()).await.unwrap();
+
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeConsistent as u32);
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert_eq!(conn.get_contract_state_id(&contract_id).await.unwrap(), Some(state_id));
+            assert_eq!(conn.get_chunk_list(&contract_id).await.unwrap(), consumer.chunk_list(&contract_id));
+            assert_eq!(conn.get_contract_info(&contract_id).await.unwrap().stored_size, Some(5 * CHUNK_SIZE as u64));
+            assert_eq!(consumer.proofs(), vec![(contract_id.clone(), true), (contract_id.clone(), true)]);
+        });
+    }
+
+    #[test]
+    fn test_challenge_expired() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let order_id = harness.new_order("user", 1).unwrap();
+            let (contract_id, _) = consumer.new_contract(order_id.as_str(), CHUNK_SIZE, test_data(1, 3)).await.unwrap();
+            let challenge = consumer.challenge(&contract_id, 0).unwrap();
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+
+            // the contract is accepted, its data is never downloaded
+            harness.miner.on_challenge(challenge, consumer.device_id().object_id().clone()).await.unwrap();
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert_eq!(conn.contract_sync_set().await.unwrap(), vec![contract_id.clone()]);
+            harness.miner.sync_contract_once(contract_id.clone()).await.unwrap();
+
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert!(conn.contract_sync_set().await.unwrap().is_empty());
+            assert!(conn.contract_set().await.unwrap().is_empty());
+            for chunk_id in consumer.chunk_list(&contract_id).iter() {
+                assert!(!harness.chunk_store.chunk_exists(chunk_id).await);
+            }
+            assert_eq!(harness.sim.get_challenge(order_id.parse().unwrap()).unwrap().unwrap().state, DMCChallengeState::ChallengePrepare as u32);
+        });
+    }
+
+    #[test]
+    fn test_order_end() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, contract_id) = first_sync(&harness, &consumer, 1).await;
+
+            // a running order stays
+            harness.miner.check_contract_end_once().await.unwrap();
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert_eq!(conn.contract_set().await.unwrap(), vec![contract_id.clone()]);
+
+            // settle until the user pledge runs out
+            while harness.sim.get_order(order_id).unwrap().unwrap().state != DMCOrderState::OrderStateEnd as u8 {
+                harness.sim.settle_order(order_id).unwrap();
+            }
+            harness.miner.check_contract_end_once().await.unwrap();
+
+            let mut conn = harness.meta_store.create_meta_connection().await.unwrap();
+            assert!(conn.contract_set().await.unwrap().is_empty());
+            assert!(conn.contract_proof_set().await.unwrap().is_empty());
+            let stat = harness.miner.get_dsg_stat().await.unwrap();
+            assert_eq!(stat.used_space, 0);
+            assert_eq!(stat.order_count, 0);
+        });
+    }
+
+    #[test]
+    fn test_arbitration() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, _) = first_sync(&harness, &consumer, 1).await;
+
+            // the user challenges the last piece with a hash that no data matches
+            let challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            consumer.dmc_client().challenge(
+                order_id.to_string().as_str(),
+                challenge.data_block_count - 1,
+                hash_data(b"wrong"),
+                "nonce".to_string()).await.unwrap();
+
+            // the miner answers first, then proves the piece by the tree saved at sync
+            harness.dmc.check_challenge_once().await.unwrap();
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeRequest as u32);
+            harness.dmc.check_challenge_once().await.unwrap();
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeArbitrationUserPay as u32);
+        });
+    }
+
+    async fn tracked_txs(harness: &MemHarness) -> Vec<TrackedTx<DMCTrackedAction>> {
+        let state = harness.meta_store.get_setting("dmc_tracked_txs", "").await.unwrap();
+        let state: DMCTxTrackerState<DMCTrackedAction> = serde_json::from_str(state.as_str()).unwrap();
+        state.txs
+    }
+
+    #[test]
+    fn test_challenge_resp_failed() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, _) = first_sync(&harness, &consumer, 1).await;
+            let challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            consumer.dmc_client().challenge(
+                order_id.to_string().as_str(),
+                challenge.data_block_count - 1,
+                hash_data(b"wrong"),
+                "nonce".to_string()).await.unwrap();
+
+            // the answer is packed but fails, the chain is still waiting for it
+            harness.sim.hard_fail_next("anschallenge");
+            harness.dmc.check_challenge_once().await.unwrap();
+            harness.dmc.check_txs_once().await.unwrap();
+            assert!(tracked_txs(&harness).await.is_empty());
+
+            // the failure cleared the saved state, so the miner answers again instead of going on to arbitration
+            harness.dmc.check_challenge_once().await.unwrap();
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeRequest as u32);
+            harness.dmc.check_challenge_once().await.unwrap();
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeArbitrationUserPay as u32);
+        });
+    }
+
+    #[test]
+    fn test_challenge_resp_expired() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, _) = first_sync(&harness, &consumer, 1).await;
+            let challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            consumer.dmc_client().challenge(
+                order_id.to_string().as_str(),
+                challenge.data_block_count - 1,
+                hash_data(b"wrong"),
+                "nonce".to_string()).await.unwrap();
+
+            harness.sim.drop_next("anschallenge");
+            harness.dmc.check_challenge_once().await.unwrap();
+            harness.dmc.check_txs_once().await.unwrap();
+            let lost = tracked_txs(&harness).await;
+            assert_eq!(lost.len(), 1);
+            assert_eq!(lost[0].action, "anschallenge");
+            assert_eq!(lost[0].state, TrackedTxState::Pending);
+
+            // rescanned once past the expiration, then sent again with a fresh reference block
+            harness.sim.advance(DMC_TX_EXPIRE_SECONDS + 1);
+            harness.dmc.check_txs_once().await.unwrap();
+            harness.dmc.check_txs_once().await.unwrap();
+            let resent = tracked_txs(&harness).await;
+            assert_eq!(resent.len(), 1);
+            assert_eq!(resent[0].retries, 1);
+            assert_ne!(resent[0].trx_id, lost[0].trx_id);
+
+            harness.dmc.check_txs_once().await.unwrap();
+            assert!(tracked_txs(&harness).await.is_empty());
+            // the answer went through, the next round proves the piece
+            harness.dmc.check_challenge_once().await.unwrap();
+            assert_eq!(harness.sim.get_challenge(order_id).unwrap().unwrap().state, DMCChallengeState::ChallengeArbitrationUserPay as u32);
+        });
+    }
+
+    #[test]
+    fn test_corrupt_challenge_state() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (order_id, _) = first_sync(&harness, &consumer, 1).await;
+            let challenge = harness.sim.get_challenge(order_id).unwrap().unwrap();
+            consumer.dmc_client().challenge(
+                order_id.to_string().as_str(),
+                challenge.data_block_count - 1,
+                hash_data(b"wrong"),
+                "nonce".to_string()).await.unwrap();
+
+            harness.meta_store.set_setting("dmc_challenge_state".to_string(), "{".to_string()).await.unwrap();
+            assert_eq!(harness.dmc.check_challenge_once().await.unwrap_err().code(), BuckyErrorCode::InvalidData);
+            assert_eq!(harness.meta_store.get_setting("dmc_challenge_state", "").await.unwrap(), "{");
+        });
+    }
+
+    #[test]
+    fn test_self_audit() {
+        async_std::task::block_on(async {
+            let harness = MemHarness::start().await.unwrap();
+            let consumer = harness.new_consumer("user").await.unwrap();
+            let (_, contract_id) = first_sync(&harness, &consumer, 1).await;
+            let audit = SelfAudit::new(SelfAuditConfig::default(), harness.dmc.clone(), harness.meta_store.clone());
+
+            // more samples than pieces, every piece of the meta and the data chunks is proved
+            let piece_count = 64;
+            assert!(harness.dmc.audit_contract(&contract_id, piece_count, b"seed").await.unwrap());
+            assert!(audit.audit().await.unwrap().is_empty());
+
+            // a corrupted chunk is found by the pieces sampled from it
+            let chunk_id = consumer.chunk_list(&contract_id)[1].clone();
+            harness.chunk_store.save_chunk(&chunk_id, vec![0xff; CHUNK_SIZE as usize].as_slice()).await.unwrap();
+            assert!(!harness.dmc.audit_contract(&contract_id, piece_count, b"seed").await.unwrap());
+            assert_eq!(audit.audit().await.unwrap(), vec![contract_id.clone()]);
+        });
+    }
+}
@@ -1,15 +1,23 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::convert::{TryInto};
 use std::io::SeekFrom;
 use std::mem::size_of;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use sha2::{Digest};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use memmap2::MmapMut;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use cyfs_base::*;
+use dmc_dsg_base::{MerkleHasher, Sha256Hasher, piece_proof_root};
 use async_std::io::Cursor;
 use async_std::io::prelude::SeekExt;
 use async_std::io::ReadExt;
 
 #[async_trait::async_trait]
 pub trait HashStore: Send + Sync {
+    type Hasher: MerkleHasher;
     async fn get_node_list_len(&self, layer_number: u16) -> BuckyResult<u64>;
     async fn get_node(&self, layer_number: u16, index: u64) -> BuckyResult<&[u8;32]>;
     async fn set_node(&mut self, layer_number: u16, index: u64, hash: &[u8;32]) -> BuckyResult<()>;
@@ -30,14 +38,134 @@ impl VecCache<Vec<u8>> for MemVecCache {
     }
 }
 
-pub struct HashVecStore<T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MerkleCacheConfig {
+    // node caches are memory mapped files in this dir, the system temp dir if none
+    pub spill_dir: Option<PathBuf>,
+    // node caches smaller than this many bytes stay in memory
+    pub spill_threshold: u64,
+    // blocks of data hashed at the same time when a tree is built
+    pub hash_workers: usize,
+}
+
+impl Default for MerkleCacheConfig {
+    fn default() -> Self {
+        Self {
+            spill_dir: None,
+            spill_threshold: 64 * 1024 * 1024,
+            hash_workers: 4,
+        }
+    }
+}
+
+static MERKLE_CACHE_CONFIG: Lazy<RwLock<MerkleCacheConfig>> = Lazy::new(|| RwLock::new(MerkleCacheConfig::default()));
+
+pub fn set_merkle_cache_config(config: MerkleCacheConfig) {
+    *MERKLE_CACHE_CONFIG.write().unwrap() = config;
+}
+
+pub fn get_merkle_cache_config() -> MerkleCacheConfig {
+    MERKLE_CACHE_CONFIG.read().unwrap().clone()
+}
+
+// an anonymous file mapped into memory, the file is unlinked on creation so it's gone when this drops
+pub struct MmapVec {
+    mmap: MmapMut,
+    len: usize,
+}
+
+impl MmapVec {
+    pub fn create_in(dir: &PathBuf, len: u64) -> BuckyResult<Self> {
+        let file = tempfile::tempfile_in(dir).map_err(|e| {
+            let msg = format!("create spill file in {} err {}", dir.display(), e);
+            log::error!("{}", msg);
+            BuckyError::new(BuckyErrorCode::IoError, msg)
+        })?;
+        // an empty file can't be mapped
+        file.set_len(std::cmp::max(len, 1))?;
+        let mmap = unsafe { MmapMut::map_mut(&file) }.map_err(|e| {
+            let msg = format!("map spill file of {} bytes err {}", len, e);
+            log::error!("{}", msg);
+            BuckyError::new(BuckyErrorCode::IoError, msg)
+        })?;
+        Ok(Self {
+            mmap,
+            len: len as usize
+        })
+    }
+}
+
+impl Deref for MmapVec {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.mmap[..self.len]
+    }
+}
+
+impl DerefMut for MmapVec {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.mmap[..self.len]
+    }
+}
+
+pub struct MmapVecCache;
+
+impl VecCache<MmapVec> for MmapVecCache {
+    fn create(len: u64) -> BuckyResult<MmapVec> {
+        let dir = get_merkle_cache_config().spill_dir.unwrap_or(std::env::temp_dir());
+        MmapVec::create_in(&dir, len)
+    }
+}
+
+pub enum SpillVec {
+    Mem(Vec<u8>),
+    Mmap(MmapVec),
+}
+
+impl Deref for SpillVec {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SpillVec::Mem(v) => v.as_slice(),
+            SpillVec::Mmap(v) => v.deref(),
+        }
+    }
+}
+
+impl DerefMut for SpillVec {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            SpillVec::Mem(v) => v.as_mut_slice(),
+            SpillVec::Mmap(v) => v.deref_mut(),
+        }
+    }
+}
+
+// keeps small trees in memory and spills the big ones to disk
+pub struct SpillVecCache;
+
+impl VecCache<SpillVec> for SpillVecCache {
+    fn create(len: u64) -> BuckyResult<SpillVec> {
+        if len < get_merkle_cache_config().spill_threshold {
+            Ok(SpillVec::Mem(MemVecCache::create(len)?))
+        } else {
+            Ok(SpillVec::Mmap(MmapVecCache::create(len)?))
+        }
+    }
+}
+
+pub struct HashVecStore<T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>, H: MerkleHasher = Sha256Hasher> {
     layer_info: Vec<(u64, u64)>,
     cache: T,
     min_layer: u16,
     min_offset: u64,
+    _hasher: PhantomData<H>,
 }
 
-impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> HashVecStore<T> {
+impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>, H: MerkleHasher> HashVecStore<T, H> {
     pub fn new<C: VecCache<T>>(leafs: u64) -> BuckyResult<Self> {
         let mut count  = leafs;
         let mut cur_nodes = leafs;
@@ -64,6 +192,7 @@ impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> HashVecStore<
             cache,
             min_layer: 0,
             min_offset: 0,
+            _hasher: PhantomData,
         })
     }
 
@@ -119,12 +248,15 @@ impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> HashVecStore<
             cache: data,
             min_layer,
             min_offset,
+            _hasher: PhantomData,
         })
     }
 }
 
 #[async_trait::async_trait]
-impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> HashStore for HashVecStore<T> {
+impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>, H: MerkleHasher> HashStore for HashVecStore<T, H> {
+    type Hasher = H;
+
     async fn get_node_list_len(&self, layer_number: u16) -> BuckyResult<u64> {
         match self.layer_info.get(layer_number as usize) {
             Some((_, len)) => {
@@ -190,6 +322,218 @@ impl <T: Send + Sync + Deref<Target=[u8]> + DerefMut<Target=[u8]>> HashStore for
     }
 }
 
+// folds the leaf hashes into the tree as they come, a node is written as soon as both children are known
+pub struct MerkleStreamBuilder<CACHE: HashStore> {
+    cache: CACHE,
+    // the left node of each layer waiting for its right sibling
+    pending: Vec<Option<[u8;32]>>,
+    // nodes written at each layer
+    counts: Vec<u64>,
+}
+
+impl<CACHE: HashStore> MerkleStreamBuilder<CACHE> {
+    pub fn new(cache: CACHE) -> Self {
+        Self {
+            cache,
+            pending: vec![],
+            counts: vec![],
+        }
+    }
+
+    fn hash(left: &[u8], right: &[u8]) -> [u8;32] {
+        <CACHE::Hasher as MerkleHasher>::hash_pair(left, right)
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.counts.get(0).cloned().unwrap_or(0)
+    }
+
+    async fn push_node(&mut self, layer: usize, hash: [u8;32]) -> BuckyResult<()> {
+        let mut layer = layer;
+        let mut hash = hash;
+        loop {
+            if self.counts.len() <= layer {
+                self.counts.push(0);
+                self.pending.push(None);
+            }
+            self.cache.set_node(layer as u16, self.counts[layer], &hash).await?;
+            self.counts[layer] += 1;
+            match self.pending[layer].take() {
+                Some(left) => {
+                    hash = Self::hash(&left, &hash);
+                    layer += 1;
+                }
+                None => {
+                    self.pending[layer] = Some(hash);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn push_leaf(&mut self, hash: [u8;32]) -> BuckyResult<()> {
+        self.push_node(0, hash).await
+    }
+
+    // an odd node left at a layer pairs with itself, the same as the layer by layer build
+    pub async fn finish(mut self) -> BuckyResult<([u8;32], CACHE)> {
+        if self.leaf_count() == 0 {
+            let msg = format!("no leaf in merkle tree");
+            log::error!("{}", msg.as_str());
+            return Err(BuckyError::new(BuckyErrorCode::InvalidParam, msg));
+        }
+        let mut layer = 0;
+        loop {
+            if self.counts[layer] == 1 {
+                let root = self.pending[layer].take().unwrap();
+                return Ok((root, self.cache));
+            }
+            if let Some(node) = self.pending[layer].take() {
+                self.push_node(layer + 1, Self::hash(&node, &node)).await?;
+            }
+            layer += 1;
+        }
+    }
+}
+
+// a tree kept by layers, leafs can be replaced or appended and only the nodes above them are hashed again.
+// the data has the same layout as HashVecStore::get_data(0)
+// every layer stays in memory, about 64 bytes per leaf, and load and to_data copy the whole tree, so both are O(n) in the leafs.
+// it never spills to spill_dir like the MerkleTree caches do, the leafs are chunk roots, not pieces, so even a big contract keeps a small tree
+pub struct MerkleAccumulator<H: MerkleHasher = Sha256Hasher> {
+    layers: Vec<Vec<[u8;32]>>,
+    _hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> MerkleAccumulator<H> {
+    pub fn new() -> Self {
+        Self {
+            layers: vec![vec![]],
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn from_leafs(leafs: &[[u8;32]]) -> Self {
+        let mut accumulator = Self::new();
+        accumulator.append(leafs);
+        accumulator
+    }
+
+    pub fn load(leafs: u64, data: &[u8]) -> BuckyResult<Self> {
+        let mut layers = Vec::new();
+        let mut cur_nodes = leafs;
+        let mut offset = 0;
+        loop {
+            let end = offset + cur_nodes as usize * 32;
+            if end > data.len() {
+                let msg = format!("merkle data len {} is invalid for {} leafs", data.len(), leafs);
+                log::error!("{}", msg.as_str());
+                return Err(BuckyError::new(BuckyErrorCode::InvalidData, msg));
+            }
+            layers.push(data[offset..end].chunks(32).map(|v| v.try_into().unwrap()).collect());
+            offset = end;
+            if cur_nodes <= 1 {
+                break;
+            }
+            cur_nodes = (cur_nodes + 1) / 2;
+        }
+        if offset != data.len() {
+            let msg = format!("merkle data len {} is invalid for {} leafs", data.len(), leafs);
+            log::error!("{}", msg.as_str());
+            return Err(BuckyError::new(BuckyErrorCode::InvalidData, msg));
+        }
+        Ok(Self {
+            layers,
+            _hasher: PhantomData,
+        })
+    }
+
+    fn hash(left: &[u8], right: &[u8]) -> [u8;32] {
+        H::hash_pair(left, right)
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.layers[0].len() as u64
+    }
+
+    pub fn get_leaf(&self, index: u64) -> Option<&[u8;32]> {
+        self.layers[0].get(index as usize)
+    }
+
+    pub fn root(&self) -> BuckyResult<[u8;32]> {
+        match self.layers.last().unwrap().as_slice() {
+            [root] => Ok(root.clone()),
+            _ => {
+                let msg = format!("no leaf in merkle accumulator");
+                log::error!("{}", msg.as_str());
+                Err(BuckyError::new(BuckyErrorCode::NotFound, msg))
+            }
+        }
+    }
+
+    pub fn set_leaf(&mut self, index: u64, hash: &[u8;32]) -> BuckyResult<()> {
+        match self.layers[0].get_mut(index as usize) {
+            Some(leaf) => {
+                if leaf != hash {
+                    *leaf = hash.clone();
+                    self.update(index as usize, index as usize);
+                }
+                Ok(())
+            },
+            None => {
+                let msg = format!("can't find leaf {}", index);
+                log::error!("{}", msg.as_str());
+                Err(BuckyError::new(BuckyErrorCode::NotFound, msg))
+            }
+        }
+    }
+
+    pub fn append(&mut self, leafs: &[[u8;32]]) {
+        if leafs.len() == 0 {
+            return;
+        }
+        let start = self.layers[0].len();
+        self.layers[0].extend_from_slice(leafs);
+        let end = self.layers[0].len() - 1;
+        self.update(start, end);
+    }
+
+    // hash again the parents of the nodes from start to end at each layer, an odd last node pairs with itself
+    fn update(&mut self, start: usize, end: usize) {
+        let mut start = start;
+        let mut end = end;
+        let mut layer = 0;
+        while self.layers[layer].len() > 1 {
+            let parent_len = (self.layers[layer].len() + 1) / 2;
+            if self.layers.len() == layer + 1 {
+                self.layers.push(Vec::new());
+            }
+            self.layers[layer + 1].resize(parent_len, [0u8;32]);
+            start = start / 2;
+            end = end / 2;
+            for i in start..=end {
+                let left = &self.layers[layer][i * 2];
+                let right = self.layers[layer].get(i * 2 + 1).unwrap_or(left);
+                let hash = Self::hash(left, right);
+                self.layers[layer + 1][i] = hash;
+            }
+            layer += 1;
+        }
+        self.layers.truncate(layer + 1);
+    }
+
+    pub fn to_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.layers.iter().map(|v| v.len() * 32).sum());
+        for layer in self.layers.iter() {
+            for node in layer.iter() {
+                data.extend_from_slice(node);
+            }
+        }
+        data
+    }
+}
+
 pub struct MerkleTree<READ: async_std::io::Read + async_std::io::Seek + Send, CACHE: HashStore> {
     cache: CACHE,
     reader: Option<READ>,
@@ -200,16 +544,11 @@ pub const DSG_CHUNK_PIECE_SIZE: u64 = 1024;
 
 impl <READ: async_std::io::Read + async_std::io::Seek + Send + Unpin, CACHE: HashStore> MerkleTree<READ, CACHE> {
     fn hash(left: &[u8], right: &[u8]) -> [u8;32] {
-        let mut sha256 = sha2::Sha256::new();
-        sha256.update(left);
-        sha256.update(right);
-        sha256.finalize().into()
+        <CACHE::Hasher as MerkleHasher>::hash_pair(left, right)
     }
 
     fn hash_data(data: &[u8]) -> [u8;32] {
-        let mut sha256 = sha2::Sha256::new();
-        sha256.update(data);
-        sha256.finalize().into()
+        <CACHE::Hasher as MerkleHasher>::hash_data(data)
     }
 
     pub async fn create_from_raw(mut reader: READ, mut cache: CACHE) -> BuckyResult<Self> {
@@ -287,6 +626,59 @@ impl <READ: async_std::io::Read + async_std::io::Seek + Send + Unpin, CACHE: Has
         })
     }
 
+    // the same tree as create_from_raw, the blocks are hashed on up to workers blocking tasks while the next ones are read
+    pub async fn create_from_raw_parallel(mut reader: READ, cache: CACHE, workers: usize) -> BuckyResult<Self> {
+        const BLOCK_SIZE: usize = 1024 * 1024 * 4;
+        let workers = std::cmp::max(workers, 1);
+        let mut builder = MerkleStreamBuilder::new(cache);
+        let mut hashing = VecDeque::new();
+        loop {
+            let mut buf = Vec::<u8>::new();
+            buf.resize(BLOCK_SIZE, 0);
+            let mut pos = 0;
+            while pos < BLOCK_SIZE {
+                let read_size = reader.read(&mut buf[pos..]).await.map_err(|e| {
+                    let msg = format!("read error {}", e);
+                    log::error!("{}", msg);
+                    BuckyError::new(BuckyErrorCode::Failed, msg)
+                })?;
+                if read_size == 0 {
+                    break;
+                }
+                pos += read_size;
+            }
+            if pos == 0 {
+                break;
+            }
+            buf.truncate(pos);
+
+            let hash_data: fn(&[u8]) -> [u8;32] = <CACHE::Hasher as MerkleHasher>::hash_data;
+            hashing.push_back(async_std::task::spawn_blocking(move || {
+                buf.chunks(DSG_CHUNK_PIECE_SIZE as usize).map(hash_data).collect::<Vec<[u8;32]>>()
+            }));
+            if hashing.len() >= workers {
+                for hash in hashing.pop_front().unwrap().await {
+                    builder.push_leaf(hash).await?;
+                }
+            }
+            if pos < BLOCK_SIZE {
+                break;
+            }
+        }
+        while let Some(task) = hashing.pop_front() {
+            for hash in task.await {
+                builder.push_leaf(hash).await?;
+            }
+        }
+
+        let (root, cache) = builder.finish().await?;
+        Ok(Self {
+            reader: Some(reader),
+            cache,
+            root
+        })
+    }
+
     pub async fn create_from_base(reader: Option<READ>, mut cache: CACHE, base_layer: u16) -> BuckyResult<Self> {
         let root;
         let mut layer_number = base_layer + 1;
@@ -466,11 +858,73 @@ impl <READ: async_std::io::Read + async_std::io::Seek + Send + Unpin, CACHE: Has
         })
     }
 
+    // proves several pieces at once, a sibling is sent only if it can't be computed from the pieces
+    pub async fn gen_multi_proof(&mut self, indices: &[u64]) -> BuckyResult<MultiPieceProof> {
+        let leaf_count = self.cache.get_node_list_len(0).await?;
+        let mut proofs = BTreeMap::new();
+        for index in indices.iter() {
+            if *index >= leaf_count {
+                let msg = format!("piece {} is out of {}", index, leaf_count);
+                log::error!("{}", msg.as_str());
+                return Err(BuckyError::new(BuckyErrorCode::OutOfLimit, msg));
+            }
+            if !proofs.contains_key(index) {
+                proofs.insert(*index, self.gen_proof(*index).await?);
+            }
+        }
+        MultiPieceProof::from_proofs(leaf_count, proofs)
+    }
+
     pub fn get_cache(&self) -> &CACHE {
         &self.cache
     }
 }
 
+impl MultiPieceProof {
+    // merges the proofs of pieces in one tree of leaf_count leafs, the siblings shared by them are kept once
+    pub fn from_proofs(leaf_count: u64, proofs: BTreeMap<u64, SinglePieceProof>) -> BuckyResult<Self> {
+        if proofs.len() == 0 {
+            let msg = format!("no piece to prove");
+            log::error!("{}", msg.as_str());
+            return Err(BuckyError::new(BuckyErrorCode::InvalidParam, msg));
+        }
+
+        let mut hash_list = Vec::new();
+        // node index at the layer -> index of a piece under it
+        let mut nodes: BTreeMap<u64, u64> = proofs.keys().map(|v| (*v, *v)).collect();
+        let mut layer = 0;
+        let mut layer_len = leaf_count;
+        while layer_len > 1 {
+            let mut next = BTreeMap::new();
+            for (index, piece_index) in nodes.iter() {
+                if next.contains_key(&(index / 2)) {
+                    continue;
+                }
+                let sibling = index ^ 1;
+                if sibling < layer_len && !nodes.contains_key(&sibling) {
+                    let proof = proofs.get(piece_index).unwrap();
+                    if layer >= proof.path_list.len() {
+                        let msg = format!("proof of piece {} is shorter than a tree of {} leafs", proof.piece_index, leaf_count);
+                        log::error!("{}", msg.as_str());
+                        return Err(BuckyError::new(BuckyErrorCode::InvalidData, msg));
+                    }
+                    hash_list.push(proof.path_list[layer].clone());
+                }
+                next.insert(index / 2, *piece_index);
+            }
+            nodes = next;
+            layer += 1;
+            layer_len = (layer_len + 1) / 2;
+        }
+
+        Ok(MultiPieceProof {
+            leaf_count,
+            pieces: proofs.into_iter().map(|(index, proof)| (index, proof.piece)).collect(),
+            hash_list
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct SinglePieceProof {
     pub piece_index: u64,
@@ -535,42 +989,177 @@ impl <'de> RawDecode<'de> for SinglePieceProof {
 
 impl SinglePieceProof {
     pub fn verify(&self, root: &[u8;32]) -> bool {
-        // log::info!("verify piece {}", self.piece_index);
-        let mut cur_node: [u8;32] = {
-            let mut sha256 = sha2::Sha256::new();
-            sha256.update(self.piece.as_slice());
-            sha256.finalize().into()
-        };
-        // log::info!("verify {}", hex::encode(&cur_node));
-        let mut cur_index = self.piece_index;
-        for node in self.path_list.iter() {
-            let mut sha256 = sha2::Sha256::new();
+        self.verify_with::<Sha256Hasher>(root)
+    }
+
+    // the proof of a tree built with the hasher H
+    pub fn verify_with<H: MerkleHasher>(&self, root: &[u8;32]) -> bool {
+        &piece_proof_root(H::ALGORITHM, self.piece_index, self.piece.as_slice(), self.path_list.as_slice()) == root
+    }
+
+    // lifts the proof of a piece in one chunk's tree to the tree whose base layer are the chunk roots
+    pub async fn extend<CACHE: HashStore>(self, chunk_index: u64, chunk_leafs: u64, upper: &CACHE) -> BuckyResult<Self> {
+        let mut path_list = self.path_list;
+        let mut cur_index = chunk_index;
+        let mut cur_layer = upper.get_min_layer_number().await?;
+        loop {
+            let node_len = upper.get_node_list_len(cur_layer).await?;
+            if node_len == 1 {
+                break;
+            }
             if cur_index % 2 != 0 {
-                sha256.update(node);
-                log::info!("verify left {}", hex::encode(&node));
-                sha256.update(&cur_node);
-                cur_node = sha256.finalize().into();
+                path_list.push(upper.get_node(cur_layer, cur_index - 1).await?.clone());
+            } else if node_len > cur_index + 1 {
+                path_list.push(upper.get_node(cur_layer, cur_index + 1).await?.clone());
             } else {
-                sha256.update(&cur_node);
-                sha256.update(node);
-                // log::info!("verify right {}", hex::encode(&node));
-                cur_node = sha256.finalize().into();
+                path_list.push(upper.get_node(cur_layer, cur_index).await?.clone());
             }
-            // log::info!("verify {}", hex::encode(&cur_node));
-
             cur_index = cur_index / 2;
+            cur_layer += 1;
+        }
+        Ok(Self {
+            piece_index: chunk_index * chunk_leafs + self.piece_index,
+            piece: self.piece,
+            path_list
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct MultiPieceProof {
+    pub leaf_count: u64,
+    // sorted by piece index
+    pub pieces: Vec<(u64, Vec<u8>)>,
+    // the siblings that can't be computed from the pieces, layer by layer from the leafs
+    pub hash_list: Vec<[u8;32]>,
+}
+
+impl MultiPieceProof {
+    pub fn verify(&self, root: &[u8;32]) -> bool {
+        self.verify_with::<Sha256Hasher>(root)
+    }
+
+    pub fn verify_with<H: MerkleHasher>(&self, root: &[u8;32]) -> bool {
+        let mut nodes = BTreeMap::new();
+        for (index, piece) in self.pieces.iter() {
+            if *index >= self.leaf_count {
+                return false;
+            }
+            nodes.insert(*index, H::hash_data(piece.as_slice()));
+        }
+        if nodes.len() == 0 || nodes.len() != self.pieces.len() {
+            return false;
+        }
+
+        let mut hash_iter = self.hash_list.iter();
+        let mut layer_len = self.leaf_count;
+        while layer_len > 1 {
+            let mut next = BTreeMap::new();
+            for (index, node) in nodes.iter() {
+                if next.contains_key(&(index / 2)) {
+                    continue;
+                }
+                let sibling_index = index ^ 1;
+                let sibling = if sibling_index >= layer_len {
+                    node
+                } else if let Some(sibling) = nodes.get(&sibling_index) {
+                    sibling
+                } else if let Some(sibling) = hash_iter.next() {
+                    sibling
+                } else {
+                    return false;
+                };
+                let hash = if index % 2 == 0 {
+                    H::hash_pair(node, sibling)
+                } else {
+                    H::hash_pair(sibling, node)
+                };
+                next.insert(index / 2, hash);
+            }
+            nodes = next;
+            layer_len = (layer_len + 1) / 2;
+        }
+
+        hash_iter.next().is_none() && nodes.get(&0) == Some(root)
+    }
+}
+
+impl RawEncode for MultiPieceProof {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> BuckyResult<usize> {
+        let mut size = size_of::<u64>();
+        size += USize(self.pieces.len()).raw_measure(purpose)?;
+        for (_, piece) in self.pieces.iter() {
+            size += size_of::<u64>() + USize(piece.len()).raw_measure(purpose)? + piece.len();
+        }
+        size += USize(self.hash_list.len()).raw_measure(purpose)? + self.hash_list.len() * 32;
+        Ok(size)
+    }
+
+    fn raw_encode<'a>(&self, buf: &'a mut [u8], purpose: &Option<RawEncodePurpose>) -> BuckyResult<&'a mut [u8]> {
+        let mut buf = self.leaf_count.raw_encode(buf, purpose)?;
+        buf = USize(self.pieces.len()).raw_encode(buf, purpose)?;
+        for (index, piece) in self.pieces.iter() {
+            buf = index.raw_encode(buf, purpose)?;
+            buf = USize(piece.len()).raw_encode(buf, purpose)?;
+            if buf.len() < piece.len() {
+                log::error!("out of limit");
+                return Err(BuckyError::new(BuckyErrorCode::OutOfLimit, "out of limit"));
+            }
+            buf[..piece.len()].copy_from_slice(piece.as_slice());
+            buf = &mut buf[piece.len()..];
         }
+        buf = USize(self.hash_list.len()).raw_encode(buf, purpose)?;
+        for item in self.hash_list.iter() {
+            if buf.len() < 32 {
+                log::error!("out of limit");
+                return Err(BuckyError::new(BuckyErrorCode::OutOfLimit, "out of limit"));
+            }
+            buf[..32].copy_from_slice(item.as_slice());
+            buf = &mut buf[32..];
+        }
+        Ok(buf)
+    }
+}
 
-        // log::info!("verify {}", hex::encode(&cur_node));
-        &cur_node == root
+impl <'de> RawDecode<'de> for MultiPieceProof {
+    fn raw_decode(buf: &'de [u8]) -> BuckyResult<(Self, &'de [u8])> {
+        let (leaf_count, buf) = u64::raw_decode(buf)?;
+        let (len, mut buf) = USize::raw_decode(buf)?;
+        let mut pieces = Vec::new();
+        for _ in 0..len.0 {
+            let (index, tmp) = u64::raw_decode(buf)?;
+            let (piece_len, tmp) = USize::raw_decode(tmp)?;
+            if tmp.len() < piece_len.0 {
+                log::error!("out of limit");
+                return Err(BuckyError::new(BuckyErrorCode::OutOfLimit, "out of limit"));
+            }
+            pieces.push((index, tmp[..piece_len.0].to_vec()));
+            buf = &tmp[piece_len.0..];
+        }
+        let (len, mut buf) = USize::raw_decode(buf)?;
+        if buf.len() < len.0 * 32 {
+            log::error!("out of limit");
+            return Err(BuckyError::new(BuckyErrorCode::OutOfLimit, "out of limit"));
+        }
+        let mut hash_list = Vec::new();
+        for _ in 0..len.0 {
+            let hash: [u8;32] = (&buf[..32]).try_into().unwrap();
+            buf = &buf[32..];
+            hash_list.push(hash);
+        }
+        Ok((Self {
+            leaf_count,
+            pieces,
+            hash_list
+        }, buf))
     }
 }
 
 #[cfg(test)]
 mod test_merkle {
-    use crate::{DSG_CHUNK_PIECE_SIZE, HashStore, HashVecStore, MemVecCache, MerkleTree};
+    use crate::{DSG_CHUNK_PIECE_SIZE, HashStore, HashVecStore, MemVecCache, MerkleAccumulator, MerkleHashAlgorithm, MerkleTree, MmapVec, MmapVecCache, MultiPieceProof, PieceSetProof, Sha256Hasher, sample_piece_indices};
     use async_std::io::Cursor;
-    use cyfs_base::HashValue;
+    use cyfs_base::*;
 
     #[test]
     fn test() {
@@ -604,7 +1193,7 @@ mod test_merkle {
             }
 
             let mut i = 3;
-            let mut new_cache = HashVecStore::load(max_index as u64, tree.cache.get_data(i as u16).unwrap().to_vec()).unwrap();
+            let mut new_cache = HashVecStore::<Vec<u8>>::load(max_index as u64, tree.cache.get_data(i as u16).unwrap().to_vec()).unwrap();
             assert_eq!(new_cache.get_min_layer_number().await.unwrap(), 3);
 
             let mut tree = MerkleTree::load(Cursor::new(buf.as_slice()), new_cache).await.unwrap();
@@ -621,4 +1210,164 @@ mod test_merkle {
 
         });
     }
+
+    #[test]
+    fn test_parallel_build() {
+        async_std::task::block_on(async move {
+            let piece = DSG_CHUNK_PIECE_SIZE as usize;
+            let block = 1024 * 1024 * 4;
+            let lens = vec![1, piece, piece + 1, piece * 3, piece * 5 + 17, piece * 7, block - 1, block, block + piece * 3 + 5, block * 3 + 1];
+            for len in lens {
+                let buf: Vec<u8> = (0..len).map(|_| rand::random::<u8>()).collect();
+                let leafs = (len + piece - 1) / piece;
+                for workers in vec![1, 2, 4] {
+                    let tree = MerkleTree::create_from_raw(Cursor::new(buf.as_slice()), HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs as u64).unwrap()).await.unwrap();
+                    let mut parallel = MerkleTree::create_from_raw_parallel(Cursor::new(buf.as_slice()), HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs as u64).unwrap(), workers).await.unwrap();
+                    assert_eq!(tree.root(), parallel.root());
+                    assert_eq!(tree.get_cache().get_data(0).unwrap(), parallel.get_cache().get_data(0).unwrap());
+
+                    let root = parallel.root().clone();
+                    let proof = parallel.gen_proof(leafs as u64 - 1).await.unwrap();
+                    assert!(proof.verify(&root));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_accumulator() {
+        async_std::task::block_on(async move {
+            async fn build(leafs: &Vec<[u8;32]>) -> ([u8;32], Vec<u8>) {
+                let mut hash_store = HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs.len() as u64).unwrap();
+                for (index, hash) in leafs.iter().enumerate() {
+                    hash_store.set_node(0, index as u64, hash).await.unwrap();
+                }
+                let tree = MerkleTree::<Cursor<Vec<u8>>, _>::create_from_base(None, hash_store, 0).await.unwrap();
+                (tree.root().clone(), tree.get_cache().get_data(0).unwrap().to_vec())
+            }
+
+            let mut leafs: Vec<[u8;32]> = vec![rand::random()];
+            let mut accumulator: MerkleAccumulator = MerkleAccumulator::from_leafs(leafs.as_slice());
+            for append in [1, 1, 3, 2, 7, 16, 5, 33].iter() {
+                let new_leafs: Vec<[u8;32]> = (0..*append).map(|_| rand::random()).collect();
+                leafs.extend_from_slice(new_leafs.as_slice());
+                accumulator.append(new_leafs.as_slice());
+
+                let meta: [u8;32] = rand::random();
+                leafs[0] = meta.clone();
+                accumulator.set_leaf(0, &meta).unwrap();
+
+                let (root, data) = build(&leafs).await;
+                assert_eq!(accumulator.root().unwrap(), root);
+                assert_eq!(accumulator.to_data(), data);
+
+                accumulator = MerkleAccumulator::load(leafs.len() as u64, data.as_slice()).unwrap();
+                assert_eq!(accumulator.root().unwrap(), root);
+            }
+            assert!(MerkleAccumulator::<Sha256Hasher>::load(leafs.len() as u64 + 1, accumulator.to_data().as_slice()).is_err());
+        });
+    }
+
+    #[test]
+    fn test_mmap_cache() {
+        async_std::task::block_on(async move {
+            let len = 300 * DSG_CHUNK_PIECE_SIZE as usize + 100;
+            let buf: Vec<u8> = (0..len).map(|_| rand::random()).collect();
+            let leafs = 301u64;
+
+            let mem_tree = MerkleTree::create_from_raw(Cursor::new(buf.as_slice()), HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs).unwrap()).await.unwrap();
+            let mut mmap_tree = MerkleTree::create_from_raw(Cursor::new(buf.as_slice()), HashVecStore::<MmapVec>::new::<MmapVecCache>(leafs).unwrap()).await.unwrap();
+            assert_eq!(mem_tree.root(), mmap_tree.root());
+            assert_eq!(mem_tree.cache.get_data(0).unwrap(), mmap_tree.cache.get_data(0).unwrap());
+
+            let root = mmap_tree.root().clone();
+            for index in [0, 150, leafs - 1] {
+                let proof = mmap_tree.gen_proof(index).await.unwrap();
+                assert!(proof.verify(&root));
+            }
+
+            // the spill file is unlinked, nothing is left in the dir
+            let dir = tempfile::tempdir().unwrap();
+            let cache = MmapVec::create_in(&dir.path().to_path_buf(), 32 * leafs).unwrap();
+            assert_eq!(cache.len(), 32 * leafs as usize);
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        });
+    }
+
+    #[test]
+    fn test_extend_proof() {
+        async_std::task::block_on(async move {
+            let chunk_leafs = 4u64;
+            let chunk_len = (chunk_leafs * DSG_CHUNK_PIECE_SIZE) as usize;
+            let buf: Vec<u8> = (0..chunk_len * 5).map(|_| rand::random()).collect();
+            let mut tree = MerkleTree::create_from_raw(Cursor::new(buf.as_slice()), HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_leafs * 5).unwrap()).await.unwrap();
+            let root = tree.root().clone();
+
+            // the chunk roots are the base layer of the upper tree
+            let mut upper = HashVecStore::<Vec<u8>>::new::<MemVecCache>(5).unwrap();
+            for chunk_index in 0..5u64 {
+                let node = tree.cache.get_node(2, chunk_index).await.unwrap().clone();
+                upper.set_node(0, chunk_index, &node).await.unwrap();
+            }
+            let upper_tree = MerkleTree::<Cursor<Vec<u8>>, _>::create_from_base(None, upper, 0).await.unwrap();
+            assert_eq!(upper_tree.root(), &root);
+
+            for data_id in [0, 5, 13, chunk_leafs * 5 - 1] {
+                let chunk_index = data_id / chunk_leafs;
+                let start = chunk_index as usize * chunk_len;
+                let mut chunk_tree = MerkleTree::create_from_raw(Cursor::new(&buf[start..start + chunk_len]), HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_leafs).unwrap()).await.unwrap();
+                let proof = chunk_tree.gen_proof(data_id % chunk_leafs).await.unwrap();
+                let proof = proof.extend(chunk_index, chunk_leafs, upper_tree.get_cache()).await.unwrap();
+                assert_eq!(proof.piece_index, data_id);
+                assert!(proof.verify(&root));
+                let full = tree.gen_proof(data_id).await.unwrap();
+                assert_eq!(proof.path_list, full.path_list);
+            }
+        });
+    }
+
+    #[test]
+    fn test_multi_proof() {
+        async_std::task::block_on(async move {
+            let len = 77 * DSG_CHUNK_PIECE_SIZE as usize + 300;
+            let buf: Vec<u8> = (0..len).map(|_| rand::random()).collect();
+            let leafs = 78u64;
+            let mut tree = MerkleTree::create_from_raw(Cursor::new(buf.as_slice()), HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs).unwrap()).await.unwrap();
+            let root = tree.root().clone();
+
+            let indices = sample_piece_indices(leafs, 10, b"nonce");
+            assert_eq!(indices.len(), 10);
+            assert_eq!(indices, sample_piece_indices(leafs, 10, b"nonce"));
+            assert!(indices.iter().all(|v| *v < leafs));
+            assert_eq!(sample_piece_indices(leafs, 100, b"nonce").len(), leafs as usize);
+
+            let mut single_len = 0;
+            for index in indices.iter() {
+                single_len += tree.gen_proof(*index).await.unwrap().path_list.len();
+            }
+            let proof = tree.gen_multi_proof(indices.as_slice()).await.unwrap();
+            assert!(proof.verify(&root));
+            assert!(proof.hash_list.len() < single_len);
+
+            let proof = MultiPieceProof::clone_from_slice(proof.to_vec().unwrap().as_slice()).unwrap();
+            assert!(proof.verify(&root));
+            assert_eq!(proof.pieces.iter().map(|v| v.0).collect::<Vec<u64>>(), indices);
+            // the verifier decodes what the miner encodes
+            let set_proof = PieceSetProof::decode_raw(proof.to_vec().unwrap().as_slice()).unwrap();
+            assert!(set_proof.verify(MerkleHashAlgorithm::Sha256, &root));
+
+            let mut bad = proof.clone();
+            bad.pieces[0].1[0] ^= 1;
+            assert!(!bad.verify(&root));
+            let mut bad = proof.clone();
+            bad.hash_list.pop();
+            assert!(!bad.verify(&root));
+
+            // the last piece is shorter and has no sibling at some layers
+            let proof = tree.gen_multi_proof(&[0, leafs - 1, leafs - 1]).await.unwrap();
+            assert_eq!(proof.pieces.len(), 2);
+            assert!(proof.verify(&root));
+            assert!(tree.gen_multi_proof(&[leafs]).await.is_err());
+        });
+    }
 }
@@ -11,7 +11,9 @@ use dmc_dsg_base::*;
 use dmc_dsg_base::DSGJSON;
 use dmc_dsg_base::DSGJSONType;
 
-type Miner = DmcDsgMiner<SharedCyfsStack, CyfsStackMetaConnection, NocChunkStore, CyfsStackFileDownloader, RemoteDMCTxSender<SharedCyfsStack>>;
+// the noc keeps the downloaded chunks, the erasure coded volumes keep them once synced
+pub type OodChunkStore = ErasureChunkStore<NocChunkStore>;
+type Miner = DmcDsgMiner<SharedCyfsStack, CyfsStackMetaConnection, OodChunkStore, CyfsStackFileDownloader, MinerTxSender<SharedCyfsStack>>;
 
 #[derive(Clone)]
 pub struct OodMiner {
@@ -21,6 +21,10 @@ pub struct MinerStat {
 }
 
 const STAT_LOCKER: &str = "stat_locker";
+// pieces sampled for a challenge of the consumer besides the dsg proof
+pub const CHALLENGE_SAMPLE_PIECES: usize = 16;
+pub const SAMPLED_PROOF_OBJ_TYPE: u16 = 10004;
+pub const SAMPLED_PROOF_PATH: &str = "/dsg/service/proof/pieces/";
 
 pub struct DmcDsgMiner<
     CLIENT: CyfsClient,
@@ -202,6 +206,7 @@ impl<CLIENT: CyfsClient,
                     meta_merkle: vec![],
                     stored_size: Some(0),
                     sum_size: None,
+                    merkle_hash: MerkleHashAlgorithm::from_id(DsgContractObjectRef::from(&contract).witness().merkle_hash.unwrap_or(0))?,
                 }
             };
 
@@ -245,19 +250,6 @@ impl<CLIENT: CyfsClient,
         Ok(wait_list)
     }
 
-    async fn build_merkle_root(&self, chunk_merkle_root_list: &Vec<HashValue>) -> BuckyResult<HashValue> {
-        let mut hash_store = HashVecStore::<Vec<u8>>::new::<MemVecCache>(chunk_merkle_root_list.len() as u64)?;
-        for (index, hash) in chunk_merkle_root_list.iter().enumerate() {
-            hash_store.set_node(0, index as u64, hash.as_slice().try_into().unwrap()).await?;
-        }
-
-        let merkle_tree = MerkleTree::<async_std::io::Cursor<Vec<u8>>, HashVecStore<Vec<u8>>>::create_from_base(
-            None,
-            hash_store,
-            0).await?;
-        Ok(HashValue::from(merkle_tree.root()))
-    }
-
     #[tracing::instrument(skip(self), err)]
     async fn sync_contract_data_proc(&self, contract_id: ObjectId) -> BuckyResult<()> {
         let mut conn = self.meta_store.create_meta_connection().await?;
@@ -265,6 +257,12 @@ impl<CLIENT: CyfsClient,
         if contract_info.contract_status != ContractStatus::Syncing {
             return Ok(());
         }
+        // the chunk trees and the accumulator below are built with sha256
+        if contract_info.merkle_hash != Sha256Hasher::ALGORITHM {
+            let msg = format!("contract {} merkle hash {:?} can't be synced", contract_id.to_string(), contract_info.merkle_hash);
+            log::error!("{}", msg.as_str());
+            return Err(BuckyError::new(BuckyErrorCode::NotSupport, msg));
+        }
         let contract = conn.get_contract(&contract_id).await?;
         assert!(contract.is_some());
         let contract_ref = DsgContractObjectRef::from(contract.as_ref().unwrap());
@@ -318,6 +316,7 @@ impl<CLIENT: CyfsClient,
             conn.begin().await?;
             let mut contract_info = conn.get_contract_info(&contract_id).await?;
             let mut cur_chunk_list = conn.get_chunk_list(&contract_id).await?;
+            let prev_chunk_count = cur_chunk_list.len();
             cur_chunk_list.append(&mut change.chunks.clone());
             let hash = hash_data(cur_chunk_list.to_vec()?.as_slice());
             assert_eq!(&hash, change.stored_hash.as_ref().unwrap());
@@ -357,15 +356,45 @@ impl<CLIENT: CyfsClient,
             }
             let hash = self.build_meta_chunk_merkle_root(meta_ref, chunk_size as u32).await?;
             chunk_hash_list.push(hash);
-            contract_info.meta_merkle = chunk_hash_list.clone();
+            let prev_meta_merkle = std::mem::replace(&mut contract_info.meta_merkle, chunk_hash_list.clone());
 
-            let mut chunk_merkle_root_list = conn.get_chunk_merkle_root(
-                &cur_chunk_list,
-                chunk_size as u32).await?.into_iter().map(|v|v.1).collect();
-            chunk_hash_list.append(&mut chunk_merkle_root_list);
-            let file_size = (chunk_hash_list.len() * chunk_size) as u64;
+            // the tree saved at the last sync only takes the new chunks, it's built again when the meta chunk count changes
+            let saved_tree = if prev_meta_merkle.len() == chunk_hash_list.len() {
+                conn.get_contract_merkle_tree(&contract_id).await?
+            } else {
+                None
+            };
+            let prev_leafs = (prev_meta_merkle.len() + prev_chunk_count) as u64;
+            let accumulator = match saved_tree.map(|tree_data| MerkleAccumulator::<Sha256Hasher>::load(prev_leafs, tree_data.as_slice())) {
+                Some(Ok(mut accumulator)) => {
+                    for (index, hash) in chunk_hash_list.iter().enumerate() {
+                        accumulator.set_leaf(index as u64, hash.as_slice().try_into().unwrap())?;
+                    }
+                    let new_root_list: Vec<[u8;32]> = conn.get_chunk_merkle_root(
+                        &change.chunks,
+                        chunk_size as u32).await?.into_iter().map(|v| v.1.as_slice().try_into().unwrap()).collect();
+                    accumulator.append(new_root_list.as_slice());
+                    accumulator
+                },
+                saved_tree => {
+                    if let Some(Err(e)) = saved_tree {
+                        log::error!("contract {} load saved merkle tree err {}, rebuild the tree", contract_id.to_string(), e);
+                    }
+                    let mut chunk_merkle_root_list = conn.get_chunk_merkle_root(
+                        &cur_chunk_list,
+                        chunk_size as u32).await?.into_iter().map(|v|v.1).collect();
+                    chunk_hash_list.append(&mut chunk_merkle_root_list);
+                    let leaf_list: Vec<[u8;32]> = chunk_hash_list.iter().map(|v| v.as_slice().try_into().unwrap()).collect();
+                    MerkleAccumulator::<Sha256Hasher>::from_leafs(leaf_list.as_slice())
+                }
+            };
+            self.raw_data_store.set_storage_class(
+                change.chunks.as_slice(),
+                contract_ref.witness().storage_class.as_deref()).await?;
+            let file_size = accumulator.leaf_count() * chunk_size as u64;
             let data_block_count = if file_size % DSG_CHUNK_PIECE_SIZE == 0 { file_size / DSG_CHUNK_PIECE_SIZE} else { file_size / DSG_CHUNK_PIECE_SIZE + 1};
-            let merkle_root = self.build_merkle_root(&chunk_hash_list).await?;
+            let merkle_root = HashValue::from(&accumulator.root()?);
+            let tree_data = accumulator.to_data();
             if let Err(e) = self.dmc.report_merkle_hash(&contract_id, merkle_root, data_block_count as u64).await {
                 if get_app_err_code(&e) == DMC_DSG_ERROR_MERKLE_ROOT_VERIFY_FAILED {
                     conn.contract_sync_set_remove(&vec![contract_id.clone()]).await?;
@@ -377,6 +406,7 @@ impl<CLIENT: CyfsClient,
             } else {
                 let sum: usize = cur_chunk_list.iter().map(|v| v.len()).sum();
                 conn.save_chunk_list(&contract_id, cur_chunk_list).await?;
+                conn.save_contract_merkle_tree(&contract_id, tree_data).await?;
                 contract_info.stored_size = Some(sum as u64);
                 contract_info.contract_status = ContractStatus::Storing;
                 conn.set_contract_info(&contract_id, &contract_info).await?;
@@ -528,6 +558,7 @@ impl<CLIENT: CyfsClient,
         let mut conn = self.meta_store.create_meta_connection_named_locked(Self::get_contract_lock_name(&contract_id)).await?;
         if let Some(challenge) = conn.get_challenge(&contract_id).await? {
             let challenge_ref = DsgChallengeObjectRef::from(&challenge);
+            let challenge_id = challenge_ref.id();
             if challenge_ref.expire_at() < bucky_time_now() {
                 conn.begin().await?;
                 conn.contract_proof_set_remove(&vec![contract_id.clone()]).await?;
@@ -607,6 +638,10 @@ impl<CLIENT: CyfsClient,
                     conn.begin().await?;
                     conn.contract_proof_set_remove(&vec![contract_id.clone()]).await?;
                     conn.commit().await?;
+                    // the dsg proof decides the challenge, a consumer not taking sampled proofs yet only costs a log
+                    if let Err(e) = self.resp_sampled_proof(&contract_id, contract_ref.witness().order_id.as_str(), &challenge_id, &ood_id).await {
+                        error!("contract {} sampled proof err: {:?}", contract_id.to_string(), e);
+                    }
                 }
             }
         } else {
@@ -618,6 +653,34 @@ impl<CLIENT: CyfsClient,
         Ok(())
     }
 
+    // proves the pieces the challenge id samples from the whole order, so the consumer can check them against the merkle root on chain
+    async fn resp_sampled_proof(&self, contract_id: &ObjectId, order_id: &str, challenge_id: &ObjectId, ood_id: &ObjectId) -> BuckyResult<()> {
+        let proof = match self.dmc.gen_sampled_proof(contract_id, CHALLENGE_SAMPLE_PIECES, challenge_id.as_slice()).await? {
+            Some(proof) => proof,
+            None => {
+                info!("contract {} has no saved tree, no sampled proof", contract_id.to_string());
+                return Ok(());
+            }
+        };
+        let req = JSONObject::new(
+            dsg_dec_id(),
+            self.client.local_device().desc().owner().as_ref().unwrap().clone(),
+            SAMPLED_PROOF_OBJ_TYPE,
+            &SampledProofReq {
+                challenge_id: challenge_id.to_string(),
+                order_id: order_id.to_string(),
+                sample_count: CHALLENGE_SAMPLE_PIECES as u32,
+                proof: hex::encode(proof.to_vec()?)
+            })?;
+        let cyfs_path = CyfsPath::new(ood_id.clone(), dsg_dec_id(), SAMPLED_PROOF_PATH);
+        let _: JSONObject = self.client.put_object_with_resp2(
+            cyfs_path.to_path().as_str(),
+            req.desc().object_id(),
+            req.to_vec()?).await?;
+        info!("contract {} sampled proof of {} pieces success", contract_id.to_string(), proof.pieces.len());
+        Ok(())
+    }
+
     pub async fn start_proof_resp(self: &Arc<Self>) {
         let this = self.clone();
         spawn( async move {
@@ -718,12 +781,32 @@ impl<CLIENT: CyfsClient,
     }
 }
 
+// single rounds of the background loops, the in-memory harness steps through them
+#[cfg(any(test, feature = "simulator"))]
+impl<CLIENT: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DOWNLOADER: FileDownloader,
+    DMCTXSENDER: DMCTxSender> DmcDsgMiner<CLIENT, CONN, CHUNKSTORE, DOWNLOADER, DMCTXSENDER> {
+    pub async fn sync_contract_once(&self, contract_id: ObjectId) -> BuckyResult<()> {
+        self.sync_contract_data_proc(contract_id).await
+    }
+
+    pub async fn resp_proof_once(&self, contract_id: ObjectId) -> BuckyResult<()> {
+        self.resp_contract_proof(contract_id).await
+    }
+
+    pub async fn check_contract_end_once(&self) -> BuckyResult<()> {
+        self.check_contract_end().await
+    }
+}
+
 #[derive(Clone)]
 pub struct MinerChunkReader<CHUNKSTORE: ContractChunkStore> {
     raw_data_store: Arc<CHUNKSTORE>
 }
 impl<CHUNKSTORE: ContractChunkStore> MinerChunkReader<CHUNKSTORE> {
-    fn new(raw_data_store: Arc<CHUNKSTORE>) -> Self {
+    pub fn new(raw_data_store: Arc<CHUNKSTORE>) -> Self {
         Self{
             raw_data_store
         }
@@ -751,6 +834,9 @@ impl<CHUNKSTORE: ContractChunkStore> ChunkReader for MinerChunkReader<CHUNKSTORE
 #[derive(RawEncode, RawDecode, Clone, Debug)]
 pub struct ContractChunkList(pub Vec<ChunkId>);
 
+#[derive(RawEncode, RawDecode, Clone, Debug)]
+pub struct ContractMerkleTree(pub Vec<u8>);
+
 #[derive(RawEncode, RawDecode, Clone, Debug)]
 pub struct ContractSyncStatus(pub i64);
 
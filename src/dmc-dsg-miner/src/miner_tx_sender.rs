@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use crate::*;
+use dmc_dsg_base::*;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignerConfig {
+    // "dsg" leaves every transaction to the DMC DSG service,
+    // "keystore" or "remote" sign the light actions in the miner
+    pub provider: String,
+    // keystore aliases of the light key
+    pub key_aliases: Vec<String>,
+    // file holding the keystore passphrase, readable by the miner only
+    pub passphrase_file: Option<PathBuf>,
+    // remote signer holding the light key, http(s)://host:port or unix:/path
+    pub endpoint: String,
+}
+
+impl Default for SignerConfig {
+    fn default() -> Self {
+        Self {
+            provider: "dsg".to_string(),
+            key_aliases: vec![],
+            passphrase_file: None,
+            endpoint: "".to_string(),
+        }
+    }
+}
+
+impl SignerConfig {
+    // None if the DMC DSG service signs
+    pub fn to_provider_config(&self) -> BuckyResult<Option<SignatureProviderConfig>> {
+        let provider = match self.provider.as_str() {
+            "dsg" => return Ok(None),
+            "keystore" => {
+                let passphrase_file = self.passphrase_file.as_ref().ok_or_else(|| {
+                    cyfs_err!(BuckyErrorCode::InvalidParam, "keystore signer needs a passphrase_file")
+                })?;
+                let passphrase = std::fs::read_to_string(passphrase_file).map_err(|e| {
+                    cyfs_err!(BuckyErrorCode::IoError, "read {} failed {}", passphrase_file.display(), e)
+                })?;
+                let key_store = DMCKeyStore::open_default()?;
+                SignatureProviderType::Keystore {
+                    key_files: self.key_aliases.iter().map(|alias| key_store.key_path(alias.as_str())).collect(),
+                    passphrase: passphrase.trim_end_matches(|c| c == '\r' || c == '\n').to_string()
+                }
+            }
+            "remote" => SignatureProviderType::Remote {
+                endpoint: self.endpoint.clone(),
+                policy: SignPolicyType::Whitelist(LIGHT_LINKED_ACTIONS.iter().map(|(code, ty)| (code.to_string(), ty.to_string())).collect())
+            },
+            provider => {
+                return Err(cyfs_err!(BuckyErrorCode::InvalidParam, "unknown signer provider {}, expect dsg, keystore or remote", provider));
+            }
+        };
+        // the miner only ever signs with the light permission
+        Ok(Some(SignatureProviderConfig {
+            provider,
+            light_only: true
+        }))
+    }
+}
+
+// the light actions are signed by the configured provider if any, the rest by the DMC DSG service
+pub struct MinerTxSender<CLIENT: CyfsClient> {
+    dsg: RemoteDMCTxSender<CLIENT>,
+    light: Option<LocalDMCTxSender<Box<dyn SignatureProvider>>>,
+}
+
+impl<CLIENT: CyfsClient> MinerTxSender<CLIENT> {
+    pub async fn new(
+        config: &SignerConfig,
+        client: Arc<CLIENT>,
+        dsg_dec_id: ObjectId,
+        dmc_account: &str,
+        dmc_server: &str
+    ) -> BuckyResult<Self> {
+        let light = match config.to_provider_config()? {
+            Some(provider_config) => {
+                log::info!("sign light actions of {} with the {} signer", dmc_account, config.provider.as_str());
+                Some(LocalDMCTxSender::from_config(dmc_account, dmc_server, provider_config).await?)
+            }
+            None => None
+        };
+        Ok(Self {
+            dsg: RemoteDMCTxSender::new(client, dsg_dec_id),
+            light
+        })
+    }
+
+    fn light_sender(&self) -> &dyn DMCTxSender {
+        match self.light.as_ref() {
+            Some(light) => light,
+            None => &self.dsg
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<CLIENT: CyfsClient> DMCTxSender for MinerTxSender<CLIENT> {
+    async fn update_auth(&self, permission: String, parent: String, auth: Authority) -> BuckyResult<TransResult> {
+        self.dsg.update_auth(permission, parent, auth).await
+    }
+
+    async fn delete_auth(&self, permission: String, parent_permission: String) -> BuckyResult<TransResult> {
+        self.dsg.delete_auth(permission, parent_permission).await
+    }
+
+    async fn link_auth(&self, code: String, ty: String, permission: String) -> BuckyResult<TransResult> {
+        self.dsg.link_auth(code, ty, permission).await
+    }
+
+    async fn unlink_auth(&self, code: String, ty: String, permission: String) -> BuckyResult<TransResult> {
+        self.dsg.unlink_auth(code, ty, permission).await
+    }
+
+    async fn stake(&self, amount: &str) -> BuckyResult<TransResult> {
+        self.dsg.stake(amount).await
+    }
+
+    async fn unstake(&self, rate: f64) -> BuckyResult<TransResult> {
+        self.dsg.unstake(rate).await
+    }
+
+    async fn bill(&self, asset: String, price: f64, memo: String) -> BuckyResult<TransResult> {
+        self.dsg.bill(asset, price, memo).await
+    }
+
+    async fn unbill(&self, bill_id: u64, memo: String) -> BuckyResult<TransResult> {
+        self.dsg.unbill(bill_id, memo).await
+    }
+
+    async fn rebill(&self, bill_id: u64, asset: String, price: f64, memo: String) -> BuckyResult<TransResult> {
+        self.dsg.rebill(bill_id, asset, price, memo).await
+    }
+
+    async fn mint(&self, amount: &str) -> BuckyResult<TransResult> {
+        self.dsg.mint(amount).await
+    }
+
+    async fn add_merkle(&self, order_id: &str, merkle_root: HashValue, data_block_count: u64) -> BuckyResult<TransResult> {
+        self.light_sender().add_merkle(order_id, merkle_root, data_block_count).await
+    }
+
+    async fn challenge(&self, order_id: &str, data_id: u64, hash_data: HashValue, nonce: String) -> BuckyResult<TransResult> {
+        self.dsg.challenge(order_id, data_id, hash_data, nonce).await
+    }
+
+    async fn add_challenge_resp(&self, order_id: &str, reply_hash: HashValue) -> BuckyResult<TransResult> {
+        self.light_sender().add_challenge_resp(order_id, reply_hash).await
+    }
+
+    async fn arbitration(&self, order_id: &str, data: Vec<u8>, cut_merkle: Vec<HashValue>) -> BuckyResult<TransResult> {
+        self.light_sender().arbitration(order_id, data, cut_merkle).await
+    }
+
+    async fn report_cyfs_info(&self, info: &CyfsInfo) -> BuckyResult<TransResult> {
+        self.dsg.report_cyfs_info(info).await
+    }
+}
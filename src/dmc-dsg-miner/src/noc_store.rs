@@ -121,5 +121,21 @@ impl ContractChunkStore for NocChunkStore {
         false
     }
 
+    async fn delete_chunk(&self, chunk_id: &ChunkId) -> BuckyResult<()> {
+        self.stack.ndn_service().delete_data(NDNDeleteDataOutputRequest {
+            common: NDNOutputRequestCommon{
+                req_path: None,
+                dec_id: None,
+                level: NDNAPILevel::NDC,
+                target: None,
+                referer_object: vec![],
+                flags: 0,
+            },
+            object_id: chunk_id.object_id(),
+            inner_path: None,
+        }).await?;
+        Ok(())
+    }
+
 }
 
@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use crate::*;
+use dmc_dsg_base::*;
+
+const DAY_SECONDS: u64 = 24 * 3600;
+const MAX_PRICING_DECISIONS: usize = 1000;
+const PRICING_MEMO: &str = "pricing agent";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingConfig {
+    pub enable: bool,
+    // decide and record as usual but send nothing to chain
+    pub dry_run: bool,
+    // remote signer holding the owner key, http(s)://host:port or unix:/path, it signs repricing and withdrawing
+    pub signer: String,
+    // lowest DMC per PST the agent asks
+    pub floor_price: f64,
+    // matched share of the billed PST to aim at, the price goes up above it and down below it
+    pub target_utilisation: f64,
+    // the ask price moves at most this share of the price at the start of the day
+    pub max_change_per_day: f64,
+    // bills priced within this share of the ask price are left alone
+    pub reprice_threshold: f64,
+    pub check_interval: u64,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            dry_run: true,
+            signer: "".to_string(),
+            floor_price: 0f64,
+            target_utilisation: 0.8,
+            max_change_per_day: 0.1,
+            reprice_threshold: 0.02,
+            check_interval: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PricingState {
+    // ask price of the last round, 0 before the first one
+    pub price: f64,
+    pub day_start: u64,
+    pub day_price: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PricingBill {
+    pub bill_id: u64,
+    pub matched: u64,
+    pub unmatched: u64,
+    pub price: f64,
+}
+
+impl PricingBill {
+    pub fn from_record(bill: &BillRecord) -> BuckyResult<Self> {
+        Ok(Self {
+            bill_id: bill.primary,
+            matched: bill.matched.get_amount()?,
+            unmatched: bill.unmatched.get_amount()?,
+            price: bill.price
+        })
+    }
+}
+
+pub struct PricingInput {
+    pub avg_price: f64,
+    pub bills: Vec<PricingBill>,
+    // PST minted by the account, billed or not
+    pub minted: u64,
+    // PST the free disk can still take besides the matched orders
+    pub capacity: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PricingAction {
+    Hold,
+    Create { amount: u64, price: f64 },
+    // unbill and bill amount of the unmatched PST again at price
+    Rebill { bill_id: u64, amount: u64, from_price: f64, price: f64 },
+    Withdraw { bill_id: u64, amount: u64 },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PricingDecision {
+    pub time: u64,
+    pub avg_price: f64,
+    pub utilisation: f64,
+    pub capacity: u64,
+    pub price: f64,
+    pub action: PricingAction,
+    pub dry_run: bool,
+    // empty if nothing was sent to chain
+    pub result: String,
+}
+
+pub struct PricingPlan {
+    pub price: f64,
+    pub utilisation: f64,
+    pub actions: Vec<PricingAction>,
+}
+
+// the ask price of this round and the bill changes to get there, state carries the daily limit
+pub fn plan_pricing(config: &PricingConfig, state: &mut PricingState, input: &PricingInput, now: u64) -> PricingPlan {
+    let matched: u64 = input.bills.iter().map(|bill| bill.matched).sum();
+    let unmatched: u64 = input.bills.iter().map(|bill| bill.unmatched).sum();
+    let utilisation = if matched + unmatched == 0 {
+        config.target_utilisation
+    } else {
+        matched as f64 / (matched + unmatched) as f64
+    };
+
+    // with nothing on sale the market is the only reference
+    let base = if state.price > 0f64 && unmatched > 0 {
+        state.price
+    } else {
+        input.avg_price.max(config.floor_price)
+    };
+    if base <= 0f64 {
+        return PricingPlan {
+            price: 0f64,
+            utilisation,
+            actions: vec![PricingAction::Hold]
+        };
+    }
+    if state.day_price <= 0f64 || now >= state.day_start + DAY_SECONDS {
+        state.day_start = now;
+        state.day_price = if state.price > 0f64 { state.price } else { base };
+    }
+    let target = base * (1f64 + utilisation - config.target_utilisation);
+    let price = target.max(state.day_price * (1f64 - config.max_change_per_day))
+        .min(state.day_price * (1f64 + config.max_change_per_day))
+        .max(config.floor_price);
+    state.price = price;
+
+    let mut actions = Vec::new();
+    let mut over = unmatched.saturating_sub(input.capacity);
+    let mut bills: Vec<&PricingBill> = input.bills.iter().filter(|bill| bill.unmatched > 0).collect();
+    // the dearest bills are the least likely to match, withdraw from them first
+    bills.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap_or(Ordering::Equal));
+    for bill in bills.into_iter() {
+        let take = over.min(bill.unmatched);
+        over -= take;
+        let keep = bill.unmatched - take;
+        let reprice = (bill.price - price).abs() > price * config.reprice_threshold;
+        if keep == 0 {
+            actions.push(PricingAction::Withdraw {
+                bill_id: bill.bill_id,
+                amount: bill.unmatched
+            });
+        } else if take > 0 || reprice {
+            actions.push(PricingAction::Rebill {
+                bill_id: bill.bill_id,
+                amount: keep,
+                from_price: bill.price,
+                price: if reprice { price } else { bill.price }
+            });
+        }
+    }
+
+    let room = input.capacity.saturating_sub(unmatched);
+    let amount = input.minted.saturating_sub(matched + unmatched).min(room);
+    if amount > 0 {
+        actions.push(PricingAction::Create {
+            amount,
+            price
+        });
+    }
+    if actions.is_empty() {
+        actions.push(PricingAction::Hold);
+    }
+
+    PricingPlan {
+        price,
+        utilisation,
+        actions
+    }
+}
+
+pub async fn load_pricing_decisions<CONN: ContractMetaStore>(meta_store: &dyn MetaStore<CONN>) -> BuckyResult<Vec<PricingDecision>> {
+    let decisions = meta_store.get_setting("dmc_pricing_decisions", "[]").await?;
+    serde_json::from_str(decisions.as_str()).map_err(|e| {
+        cyfs_err!(BuckyErrorCode::InvalidData, "parse pricing decisions err {}", e)
+    })
+}
+
+pub struct PricingAgent<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> {
+    config: PricingConfig,
+    dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+    meta_store: Arc<dyn MetaStore<CONN>>,
+    dmc_server: String,
+    dmc_account: String,
+}
+pub type PricingAgentRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> = Arc<PricingAgent<STACK, CONN, CHUNKSTORE, DMCTXSENDER>>;
+
+impl<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> PricingAgent<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+    pub fn new(
+        config: PricingConfig,
+        dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+        meta_store: Arc<dyn MetaStore<CONN>>,
+        dmc_server: &str,
+        dmc_account: &str,
+    ) -> PricingAgentRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+        PricingAgentRef::new(Self {
+            config,
+            dmc,
+            meta_store,
+            dmc_server: dmc_server.to_string(),
+            dmc_account: dmc_account.to_string()
+        })
+    }
+
+    pub fn start(self: &PricingAgentRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>) {
+        if !self.config.enable {
+            return;
+        }
+        log::info!("start pricing agent, dry run {}", self.config.dry_run);
+
+        let this = self.clone();
+        #[cfg(not(feature = "no_dmc"))]
+        async_std::task::spawn(async move {
+            loop {
+                if let Err(e) = this.run_once().await {
+                    log::error!("pricing err {}", e);
+                }
+                async_std::task::sleep(std::time::Duration::from_secs(this.config.check_interval)).await;
+            }
+        });
+    }
+
+    // dry run keeps its own state, so its prices follow the same path the real run would
+    fn state_key(&self) -> &str {
+        if self.config.dry_run {
+            "dmc_pricing_dry_run_state"
+        } else {
+            "dmc_pricing_state"
+        }
+    }
+
+    async fn load_state(&self) -> BuckyResult<PricingState> {
+        let state = self.meta_store.get_setting(self.state_key(), "").await?;
+        if state.is_empty() {
+            return Ok(PricingState::default());
+        }
+        serde_json::from_str(state.as_str()).map_err(|e| {
+            cyfs_err!(BuckyErrorCode::InvalidData, "parse pricing state err {}", e)
+        })
+    }
+
+    async fn save_state(&self, state: &PricingState) -> BuckyResult<()> {
+        self.meta_store.set_setting(self.state_key().to_string(), serde_json::to_string(state).unwrap()).await
+    }
+
+    async fn record(&self, mut new_decisions: Vec<PricingDecision>) -> BuckyResult<()> {
+        let mut decisions = load_pricing_decisions(self.meta_store.as_ref()).await.unwrap_or_default();
+        decisions.append(&mut new_decisions);
+        if decisions.len() > MAX_PRICING_DECISIONS {
+            decisions.drain(..decisions.len() - MAX_PRICING_DECISIONS);
+        }
+        self.meta_store.set_setting("dmc_pricing_decisions".to_string(), serde_json::to_string(&decisions).unwrap()).await
+    }
+
+    // the dmc dsg service only signs new bills, unbill needs the owner key which stays in the signer
+    async fn owner_sender(&self) -> BuckyResult<LocalDMCTxSender<RemoteSignatureProvider<ActionWhitelistPolicy>>> {
+        if self.config.signer.is_empty() {
+            return Err(cyfs_err!(BuckyErrorCode::NotFound, "no signer configured for the owner key"));
+        }
+        let provider = RemoteSignatureProvider::open(
+            self.config.signer.as_str(),
+            ActionWhitelistPolicy::new(vec![
+                ("dmc.token".to_string(), "bill".to_string()),
+                ("dmc.token".to_string(), "unbill".to_string())])).await?;
+        Ok(LocalDMCTxSender::new(self.dmc_account.as_str(), self.dmc_server.as_str(), provider))
+    }
+
+    async fn apply(&self, action: &PricingAction) -> BuckyResult<()> {
+        match action {
+            PricingAction::Hold => {}
+            PricingAction::Create { amount, price } => {
+                self.dmc.bill(*amount, *price, PRICING_MEMO).await?;
+            }
+            PricingAction::Rebill { bill_id, amount, price, .. } => {
+                self.owner_sender().await?.rebill(*bill_id, amount.to_string(), *price, PRICING_MEMO.to_string()).await?;
+            }
+            PricingAction::Withdraw { bill_id, .. } => {
+                self.owner_sender().await?.unbill(*bill_id, PRICING_MEMO.to_string()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn run_once(&self) -> BuckyResult<Vec<PricingDecision>> {
+        let avg_price = self.dmc.get_avg_price().await?;
+        let mut bills = Vec::new();
+        for record in self.dmc.get_bill_list().await?.iter() {
+            bills.push(PricingBill::from_record(record)?);
+        }
+        let minted = self.dmc.get_pst_amount().await?;
+        let free_space = self.dmc.get_free_space().await?;
+        let used_space: u64 = self.meta_store.get_setting("used_space", "0").await?.parse().unwrap_or(0);
+        let matched: u64 = bills.iter().map(|bill| bill.matched).sum();
+        // the matched orders not stored yet will take their space
+        let pending = matched.saturating_sub(used_space / PST_SPACE);
+        let capacity = space_to_pst(free_space).saturating_sub(pending);
+
+        let input = PricingInput {
+            avg_price,
+            bills,
+            minted,
+            capacity
+        };
+        let mut state = self.load_state().await?;
+        let last_price = state.price;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let plan = plan_pricing(&self.config, &mut state, &input, now);
+
+        let mut decisions = Vec::new();
+        let mut failed = false;
+        for action in plan.actions.into_iter() {
+            let result = if self.config.dry_run || action == PricingAction::Hold {
+                String::new()
+            } else {
+                match self.apply(&action).await {
+                    Ok(_) => "ok".to_string(),
+                    Err(e) => {
+                        failed = true;
+                        format!("err {}", e)
+                    }
+                }
+            };
+            log::info!("pricing avg {} utilisation {:.2} capacity {} price {} action {:?} dry run {} result {}",
+                       avg_price, plan.utilisation, capacity, plan.price, action, self.config.dry_run, result.as_str());
+            decisions.push(PricingDecision {
+                time: now,
+                avg_price,
+                utilisation: plan.utilisation,
+                capacity,
+                price: plan.price,
+                action,
+                dry_run: self.config.dry_run,
+                result
+            });
+        }
+        // the bills still ask the last price, the next round starts from it again
+        if failed {
+            state.price = last_price;
+        }
+        self.save_state(&state).await?;
+        self.record(decisions.clone()).await?;
+        Ok(decisions)
+    }
+}
+
+#[cfg(test)]
+mod test_pricing {
+    use crate::{plan_pricing, PricingAction, PricingBill, PricingConfig, PricingInput, PricingState};
+
+    fn config() -> PricingConfig {
+        PricingConfig {
+            enable: true,
+            dry_run: false,
+            signer: "".to_string(),
+            floor_price: 1f64,
+            target_utilisation: 0.5,
+            max_change_per_day: 0.1,
+            reprice_threshold: 0.01,
+            check_interval: 3600
+        }
+    }
+
+    fn bill(bill_id: u64, matched: u64, unmatched: u64, price: f64) -> PricingBill {
+        PricingBill {
+            bill_id,
+            matched,
+            unmatched,
+            price
+        }
+    }
+
+    #[test]
+    fn test_first_bill() {
+        let mut state = PricingState::default();
+        let plan = plan_pricing(&config(), &mut state, &PricingInput {
+            avg_price: 2f64,
+            bills: vec![],
+            minted: 100,
+            capacity: 60
+        }, 1000);
+        assert_eq!(plan.price, 2f64);
+        assert_eq!(plan.actions, vec![PricingAction::Create { amount: 60, price: 2f64 }]);
+        assert_eq!(state.price, 2f64);
+        assert_eq!(state.day_start, 1000);
+    }
+
+    #[test]
+    fn test_daily_limit() {
+        let mut state = PricingState {
+            price: 2f64,
+            day_start: 1000,
+            day_price: 2f64
+        };
+        let input = PricingInput {
+            avg_price: 2f64,
+            bills: vec![bill(1, 0, 50, 2f64)],
+            minted: 50,
+            capacity: 100
+        };
+        // nothing matched, the price drops by the daily limit and stays there until the next day
+        let plan = plan_pricing(&config(), &mut state, &input, 2000);
+        assert!((plan.price - 1.8f64).abs() < 1e-9);
+        assert_eq!(plan.actions.len(), 1);
+        let plan = plan_pricing(&config(), &mut state, &input, 3000);
+        assert!((plan.price - 1.8f64).abs() < 1e-9);
+        let plan = plan_pricing(&config(), &mut state, &input, 1000 + 24 * 3600);
+        assert!((plan.price - 1.62f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_floor_price() {
+        let mut state = PricingState {
+            price: 1.05f64,
+            day_start: 1000,
+            day_price: 1.05f64
+        };
+        let plan = plan_pricing(&config(), &mut state, &PricingInput {
+            avg_price: 0.5f64,
+            bills: vec![bill(1, 0, 10, 1.05f64)],
+            minted: 10,
+            capacity: 100
+        }, 2000);
+        assert_eq!(plan.price, 1f64);
+    }
+
+    #[test]
+    fn test_withdraw_over_capacity() {
+        let mut state = PricingState {
+            price: 2f64,
+            day_start: 1000,
+            day_price: 2f64
+        };
+        let plan = plan_pricing(&config(), &mut state, &PricingInput {
+            avg_price: 2f64,
+            bills: vec![bill(1, 40, 30, 2f64), bill(2, 0, 20, 3f64), bill(3, 10, 0, 2f64)],
+            minted: 100,
+            capacity: 25
+        }, 2000);
+        // utilisation is 0.5, the price holds, the dearest bill goes first
+        assert_eq!(plan.price, 2f64);
+        assert_eq!(plan.actions, vec![
+            PricingAction::Withdraw { bill_id: 2, amount: 20 },
+            PricingAction::Rebill { bill_id: 1, amount: 25, from_price: 2f64, price: 2f64 },
+        ]);
+    }
+}
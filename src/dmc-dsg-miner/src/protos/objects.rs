@@ -8,6 +8,10 @@ pub struct DmcContractData {
     pub merkle_root: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
     #[prost(uint32, optional, tag="4")]
     pub chunk_size: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag="5")]
+    pub merkle_hash: ::core::option::Option<u32>,
+    #[prost(string, optional, tag="6")]
+    pub storage_class: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ContractInfo {
@@ -21,6 +25,8 @@ pub struct ContractInfo {
     pub stored_size: ::core::option::Option<u64>,
     #[prost(uint64, optional, tag="5")]
     pub sum_size: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag="6")]
+    pub merkle_hash: ::core::option::Option<u32>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MetaData {
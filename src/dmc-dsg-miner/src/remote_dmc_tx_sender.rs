@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use cyfs_base::{BuckyResult, HashValue, NamedObject, ObjectDesc, ObjectId, OwnerObjectDesc, RawConvertTo};
+use cyfs_base::{BuckyErrorCode, BuckyResult, HashValue, cyfs_err, NamedObject, ObjectDesc, ObjectId, OwnerObjectDesc, RawConvertTo};
 use serde::{Deserialize, Serialize};
 use dmc_dsg_base::{Authority, CyfsClient, CyfsInfo, CyfsPath, DMCTxSender, DSGJSON, JSONObject, TransResult};
 
@@ -127,6 +127,19 @@ impl<CLIENT: CyfsClient> DMCTxSender for RemoteDMCTxSender<CLIENT> {
         ret.get()
     }
 
+    async fn unstake(&self, rate: f64) -> BuckyResult<TransResult> {
+        Err(cyfs_err!(BuckyErrorCode::NotSupport, "unstake {} isn't supported by the dmc dsg service, use the miner cli", rate))
+    }
+
+    // the dmc dsg service has no unbill command, bills are withdrawn with the owner key, see the pricing signer
+    async fn unbill(&self, bill_id: u64, _memo: String) -> BuckyResult<TransResult> {
+        Err(cyfs_err!(BuckyErrorCode::NotSupport, "unbill {} isn't supported by the dmc dsg service, use the miner cli", bill_id))
+    }
+
+    async fn rebill(&self, bill_id: u64, _asset: String, _price: f64, _memo: String) -> BuckyResult<TransResult> {
+        Err(cyfs_err!(BuckyErrorCode::NotSupport, "rebill {} isn't supported by the dmc dsg service, use the miner cli", bill_id))
+    }
+
     async fn mint(&self, amount: &str) -> BuckyResult<TransResult> {
         let req = JSONObject::new(
             self.dec_id.clone(),
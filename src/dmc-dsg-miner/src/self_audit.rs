@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use cyfs_base::*;
+use serde::{Serialize, Deserialize};
+use crate::*;
+use dmc_dsg_base::*;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfAuditConfig {
+    // seconds between two audits, 0 to disable
+    pub interval: u64,
+    // pieces sampled from each contract per audit
+    pub samples: usize,
+}
+
+impl Default for SelfAuditConfig {
+    fn default() -> Self {
+        Self {
+            interval: 24 * 3600,
+            samples: 16,
+        }
+    }
+}
+
+// proves random pieces of the stored contracts to the miner itself, so lost data shows up before a challenge does
+pub struct SelfAudit<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> {
+    config: SelfAuditConfig,
+    dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+    meta_store: Arc<dyn MetaStore<CONN>>,
+}
+pub type SelfAuditRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> = Arc<SelfAudit<STACK, CONN, CHUNKSTORE, DMCTXSENDER>>;
+
+impl<
+    STACK: CyfsClient,
+    CONN: ContractMetaStore,
+    CHUNKSTORE: ContractChunkStore,
+    DMCTXSENDER: DMCTxSender> SelfAudit<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+    pub fn new(
+        config: SelfAuditConfig,
+        dmc: DMCRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>,
+        meta_store: Arc<dyn MetaStore<CONN>>,
+    ) -> SelfAuditRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER> {
+        SelfAuditRef::new(Self {
+            config,
+            dmc,
+            meta_store
+        })
+    }
+
+    pub fn start(self: &SelfAuditRef<STACK, CONN, CHUNKSTORE, DMCTXSENDER>) {
+        if self.config.interval == 0 || self.config.samples == 0 {
+            return;
+        }
+
+        let this = self.clone();
+        async_std::task::spawn(async move {
+            loop {
+                async_std::task::sleep(std::time::Duration::from_secs(this.config.interval)).await;
+                match this.audit().await {
+                    Ok(failed) => if failed.len() > 0 {
+                        log::error!("self audit failed on contracts {:?}", failed);
+                    },
+                    Err(e) => log::error!("self audit err {}", e)
+                }
+            }
+        });
+    }
+
+    // audits every stored contract, returns the ones whose data doesn't match
+    pub async fn audit(&self) -> BuckyResult<Vec<ObjectId>> {
+        let contract_list = self.meta_store.create_meta_connection().await?.contract_set().await?;
+        let mut failed = Vec::new();
+        for contract_id in contract_list.iter() {
+            // a new seed each round, the pieces of the last round say nothing about the others
+            let mut seed = contract_id.as_slice().to_vec();
+            seed.extend_from_slice(&bucky_time_now().to_be_bytes());
+            match self.dmc.audit_contract(contract_id, self.config.samples, seed.as_slice()).await {
+                Ok(true) => {},
+                Ok(false) => failed.push(contract_id.clone()),
+                Err(e) => log::error!("audit contract {} err {}", contract_id.to_string(), e)
+            }
+        }
+        Ok(failed)
+    }
+}
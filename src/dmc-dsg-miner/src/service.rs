@@ -94,6 +94,16 @@ impl DMCDsgService {
 
         if req_type == JsonProtocol::GetStat as u16 {
             self.on_get_stat().await
+        } else if req_type == JsonProtocol::GetPricingDecisions as u16 {
+            self.on_get_pricing_decisions(req.get()?).await
+        } else if req_type == JsonProtocol::GetAutoMintState as u16 {
+            self.on_get_auto_mint_state().await
+        } else if req_type == JsonProtocol::GetEarningsReport as u16 {
+            self.on_get_earnings_report(req.get()?).await
+        } else if req_type == JsonProtocol::GetDMCKey as u16 {
+            self.on_get_dmc_key(req.get()?).await
+        } else if req_type == JsonProtocol::SetDMCAccount as u16 {
+            self.on_set_dmc_account(req.get()?).await
         } else {
             Err(cyfs_err!(BuckyErrorCode::NotSupport, "req_type {}", req_type))
         }
@@ -109,26 +119,56 @@ impl DMCDsgService {
             )?))
     }
 
-    // async fn on_get_dmc_key(&self, dmc_account: String) -> BuckyResult<Option<JSONObject>> {
-    //     let ret = self.app.get_dmc_public_key(dmc_account).await?;
-    //     Ok(Some(JSONObject::new(
-    //         self.dec_id.clone(),
-    //         self.owner_id.clone(),
-    //         JsonProtocol::GetDMCKeyResp as u16,
-    //         &ret
-    //     )?))
-    // }
-    //
-    // async fn on_set_dmc_account(&self, req: SetDMCAccount) -> BuckyResult<Option<JSONObject>> {
-    //     self.app.set_dmc_account(req.dmc_account, req.dmc_key).await?;
-    //     Ok(Some(JSONObject::new(
-    //         self.dec_id.clone(),
-    //         self.owner_id.clone(),
-    //         JsonProtocol::SetDMCAccountResp as u16,
-    //         &"".to_string()
-    //     )?))
-    // }
-    //
+    async fn on_get_pricing_decisions(&self, count: u32) -> BuckyResult<Option<JSONObject>> {
+        let ret = self.app.get_pricing_decisions(count as usize).await?;
+        Ok(Some(JSONObject::new(
+            self.dec_id.clone(),
+            self.owner_id.clone(),
+            JsonProtocol::GetPricingDecisionsResp as u16,
+            &ret
+        )?))
+    }
+
+    async fn on_get_auto_mint_state(&self) -> BuckyResult<Option<JSONObject>> {
+        let ret = self.app.get_auto_mint_state().await?;
+        Ok(Some(JSONObject::new(
+            self.dec_id.clone(),
+            self.owner_id.clone(),
+            JsonProtocol::GetAutoMintStateResp as u16,
+            &ret
+        )?))
+    }
+
+    async fn on_get_earnings_report(&self, period: EarningsPeriod) -> BuckyResult<Option<JSONObject>> {
+        let ret = self.app.get_earnings_report(period).await?;
+        Ok(Some(JSONObject::new(
+            self.dec_id.clone(),
+            self.owner_id.clone(),
+            JsonProtocol::GetEarningsReportResp as u16,
+            &ret
+        )?))
+    }
+
+    async fn on_get_dmc_key(&self, dmc_account: String) -> BuckyResult<Option<JSONObject>> {
+        let ret = self.app.get_dmc_public_key(dmc_account.as_str())?;
+        Ok(Some(JSONObject::new(
+            self.dec_id.clone(),
+            self.owner_id.clone(),
+            JsonProtocol::GetDMCKeyResp as u16,
+            &ret
+        )?))
+    }
+
+    async fn on_set_dmc_account(&self, req: SetDMCAccount) -> BuckyResult<Option<JSONObject>> {
+        self.app.set_dmc_account(req).await?;
+        Ok(Some(JSONObject::new(
+            self.dec_id.clone(),
+            self.owner_id.clone(),
+            JsonProtocol::SetDMCAccountResp as u16,
+            &"".to_string()
+        )?))
+    }
+
     // async fn on_get_dmc_account(&self) -> BuckyResult<Option<JSONObject>> {
     //     let ret = self.app.get_dmc_account().await?;
     //     Ok(Some(JSONObject::new(
@@ -2,6 +2,7 @@ use std::collections::{BTreeSet, BTreeMap};
 use std::{sync::Arc};
 use std::convert::TryFrom;
 use async_trait::async_trait;
+use futures::StreamExt;
 use cyfs_base::*;
 use cyfs_lib::*;
 use cyfs_dsg_client::*;
@@ -13,16 +14,18 @@ pub const META_UPDATE_LOCKER: &str = "meta_update_locker";
 pub struct CyfsStackMetaStore {
     stack: SharedCyfsStackRef,
     settings: SettingRef,
+    chunk_store: Arc<OodChunkStore>,
 }
 
 impl CyfsStackMetaStore {
-    pub async fn create(stack: SharedCyfsStackRef) -> BuckyResult<Arc<CyfsStackMetaStore>> {
+    pub async fn create(stack: SharedCyfsStackRef, chunk_store: Arc<OodChunkStore>) -> BuckyResult<Arc<CyfsStackMetaStore>> {
         let settings = Setting::new(stack.clone());
         settings.load().await?;
 
         Ok(Arc::new(Self {
             stack,
-            settings
+            settings,
+            chunk_store
         }))
     }
 }
@@ -40,22 +43,26 @@ impl MetaStore<CyfsStackMetaConnection> for CyfsStackMetaStore {
 
     async fn create_meta_connection(&self) -> BuckyResult<MetaConnectionProxy<CyfsStackMetaConnection>> {
         let op_env = self.stack.root_state_stub(None, None).create_path_op_env().await?;
-        Ok(MetaConnectionProxy::new(CyfsStackMetaConnection::new(op_env, self.stack.clone())))
+        Ok(MetaConnectionProxy::new(CyfsStackMetaConnection::new(op_env, self.stack.clone(), self.chunk_store.clone())))
     }
 }
 
 pub struct CyfsStackMetaConnection {
     op_env: PathOpEnvStub,
     stack: Arc<SharedCyfsStack>,
+    // chunks are read through the configured store, so erasure coded chunks hash the same
+    chunk_store: Arc<OodChunkStore>,
 }
 
 impl CyfsStackMetaConnection {
     pub fn new(
         op_env: PathOpEnvStub,
-        stack: Arc<SharedCyfsStack>,) -> Self {
+        stack: Arc<SharedCyfsStack>,
+        chunk_store: Arc<OodChunkStore>,) -> Self {
         Self {
             op_env,
-            stack
+            stack,
+            chunk_store
         }
     }
 }
@@ -511,6 +518,7 @@ impl ContractMetaStore for CyfsStackMetaConnection {
                 meta_merkle: vec![],
                 stored_size: Some(0),
                 sum_size: None,
+                merkle_hash: MerkleHashAlgorithm::default(),
             })
         } else {
             let contract_info: ContractInfo = self.stack.get_object_from_noc::<RawObject>(info_id.unwrap()).await?.get()?;
@@ -704,6 +712,22 @@ impl ContractMetaStore for CyfsStackMetaConnection {
         Ok(())
     }
 
+    async fn get_contract_merkle_tree(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<Vec<u8>>> {
+        if let Some(obj_id) = self.get_by_path(format!("/miner/contracts/{}/", contract_id), "merkle_tree").await? {
+            let tree: ContractMerkleTree = self.get_object_from_noc::<RawObject>(obj_id).await?.get()?;
+            Ok(Some(tree.0))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn save_contract_merkle_tree(&mut self, contract_id: &ObjectId, tree_data: Vec<u8>) -> BuckyResult<()> {
+        let tree = ContractMerkleTree(tree_data);
+        self.save_by_path(format!("/miner/contracts/{}/", contract_id), "merkle_tree", None, None, Some(&tree)).await?;
+
+        Ok(())
+    }
+
     async fn get_challenge(&mut self, contract_id: &ObjectId) -> BuckyResult<Option<DsgChallengeObject>> {
         if let Some(obj_id) = self.get_by_path(format!("/miner/contracts/{}/", contract_id), "challenge").await? {
             match self.get_object_from_noc::<DsgChallengeObject>(obj_id).await {
@@ -749,23 +773,27 @@ impl ContractMetaStore for CyfsStackMetaConnection {
 
     async fn get_chunk_merkle_root(&mut self, chunk_list: &Vec<ChunkId>, merkle_chunk_size: u32) -> BuckyResult<Vec<(ChunkId, HashValue)>> {
         let leafs = if merkle_chunk_size % DSG_CHUNK_PIECE_SIZE as u32 == 0 { merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32 } else { merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32 + 1};
-        let mut hash_list = Vec::new();
-        let chunk_store = Arc::new(NocChunkStore::new(self.stack.clone()));
-        for chunk_id in chunk_list.iter() {
-            let merkle = MerkleTree::create_from_raw(
-                AsyncMerkleChunkReader::new(MerkleChunkReader::new(chunk_store.clone(), vec![chunk_id.clone()], merkle_chunk_size, None)),
-                HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs as u64)?).await?;
-            hash_list.push((chunk_id.clone(), HashValue::from(merkle.root())))
-        }
-        Ok(hash_list)
+        let chunk_store = self.chunk_store.clone();
+        let workers = get_merkle_cache_config().hash_workers;
+        // chunks are read and hashed concurrently, the results keep the order of chunk_list
+        let hash_list: Vec<BuckyResult<(ChunkId, HashValue)>> = futures::stream::iter(chunk_list.iter().map(|chunk_id| {
+            let chunk_store = chunk_store.clone();
+            async move {
+                let merkle = MerkleTree::create_from_raw_parallel(
+                    ContractDataReader::new(chunk_store, vec![chunk_id.clone()], merkle_chunk_size)?,
+                    HashVecStore::<SpillVec>::new::<SpillVecCache>(leafs as u64)?,
+                    workers).await?;
+                Ok((chunk_id.clone(), HashValue::from(merkle.root())))
+            }
+        })).buffered(std::cmp::max(workers, 1)).collect().await;
+        hash_list.into_iter().collect()
     }
 
     async fn get_chunk_merkle_data(&mut self, chunk_id: &ChunkId, merkle_chunk_size: u32) -> BuckyResult<(HashValue, Vec<u8>)> {
         let leafs = if merkle_chunk_size % DSG_CHUNK_PIECE_SIZE as u32 == 0 { merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32 } else { merkle_chunk_size / DSG_CHUNK_PIECE_SIZE as u32 + 1};
-        let chunk_store = Arc::new(NocChunkStore::new(self.stack.clone()));
         let merkle = MerkleTree::create_from_raw(
-            AsyncMerkleChunkReader::new(MerkleChunkReader::new(chunk_store.clone(), vec![chunk_id.clone()], merkle_chunk_size, None)),
-            HashVecStore::<Vec<u8>>::new::<MemVecCache>(leafs as u64)?).await?;
+            ContractDataReader::new(self.chunk_store.clone(), vec![chunk_id.clone()], merkle_chunk_size)?,
+            HashVecStore::<SpillVec>::new::<SpillVecCache>(leafs as u64)?).await?;
         let root = merkle.root();
         let data = merkle.get_cache().get_data(0)?;
         Ok((HashValue::from(root), data.to_vec()))